@@ -0,0 +1,159 @@
+use crate::{error_handler::HttpError, AppData, LoadedData};
+use actix_web::{http::header, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::OnceLock};
+use subtle::ConstantTimeEq;
+
+/// The token of the administration endpoints, read once from `ADMIN_TOKEN`.
+static ADMIN_TOKEN: OnceLock<Option<String>> = OnceLock::new();
+
+/// Gets the token of the administration endpoints.
+///
+/// # Returns
+///
+/// The value of `ADMIN_TOKEN`, `None` if it is not set or empty.
+pub fn admin_token() -> Option<&'static str> {
+    ADMIN_TOKEN
+        .get_or_init(|| {
+            std::env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty())
+        })
+        .as_deref()
+}
+
+/// Checks the bearer token of the request against the admin token.
+///
+/// # Arguments
+///
+/// * `authorization` - The `Authorization` header of the request, if any.
+/// * `token` - The admin token, `None` if the administration endpoints are disabled.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the request is authorized, otherwise a `HttpError` with status 403
+/// if the endpoints are disabled and 401 if the token is missing or wrong.
+fn authorize(authorization: Option<&str>, token: Option<&str>) -> Result<(), HttpError> {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            return Err(HttpError::new(
+                StatusCode::FORBIDDEN.as_u16(),
+                "The admin endpoints are disabled, set ADMIN_TOKEN to enable them".to_string(),
+            ))
+        }
+    };
+
+    match authorization.and_then(|authorization| authorization.strip_prefix("Bearer ")) {
+        // compared in constant time, so the response time does not leak how much of the token matched
+        Some(bearer) if bool::from(bearer.as_bytes().ct_eq(token.as_bytes())) => Ok(()),
+        _ => Err(HttpError::new(
+            StatusCode::UNAUTHORIZED.as_u16(),
+            "Invalid admin token".to_string(),
+        )),
+    }
+}
+
+/// Represents the outcome of a reload.
+///
+/// # Fields
+///
+/// * `confusion_groups` - The number of loaded confusion groups.
+/// * `number_of_ngrams` - The loaded number of n-grams.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadResult {
+    pub confusion_groups: usize,
+    pub number_of_ngrams: HashMap<i32, i64>,
+}
+
+/// Handles the reload of the confusion set and the number of n-grams.
+///
+/// The files are read again from `CONFUSION_SET_FILE` and `NUMBER_OF_NGRAMS_FILE` and swapped in
+/// at once, the requests in flight finish with the data they started with. The confusion group
+/// metrics start over. The request must carry `Authorization: Bearer <ADMIN_TOKEN>`.
+///
+/// # Arguments
+///
+/// * `req` - The request.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The new counts.
+///
+/// # Errors
+///
+/// If the request is not authorized, a `HttpResponse` with the error message will be returned.
+/// If the files can not be read or parsed, a `HttpResponse` with status 500 and the error message
+/// will be returned, and the old data is kept.
+#[post("/admin/reload")]
+async fn reload(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse, HttpError> {
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok());
+
+    authorize(authorization, admin_token())?;
+
+    let loaded = match web::block(LoadedData::read).await {
+        Ok(Ok(loaded)) => loaded,
+        Ok(Err(err)) => {
+            tracing::error!(error = %err, "reload failed");
+            // the caller is authenticated, the parse error is what they need to fix the files
+            return Ok(HttpResponse::InternalServerError().json(json!({ "message": err })));
+        }
+        Err(err) => {
+            return Err(HttpError::new(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                err.to_string(),
+            ))
+        }
+    };
+
+    let res = ReloadResult {
+        confusion_groups: loaded.confusion_set.len(),
+        number_of_ngrams: loaded.number_of_ngrams.clone(),
+    };
+
+    data.reload(loaded);
+
+    tracing::info!(confusion_groups = res.confusion_groups, "reloaded the data");
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Initializes the administration routes.
+///
+/// # Arguments
+///
+/// * `cfg` - The service configuration.
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(reload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        assert!(authorize(Some("Bearer s3cret"), Some("s3cret")).is_ok());
+
+        let err = authorize(Some("Bearer s3cret"), None).unwrap_err();
+        assert_eq!(err.error_status_code, 403);
+
+        let err = authorize(Some("Bearer wrong"), Some("s3cret")).unwrap_err();
+        assert_eq!(err.error_status_code, 401);
+
+        let err = authorize(Some("Bearer s3cres"), Some("s3cret")).unwrap_err();
+        assert_eq!(err.error_status_code, 401);
+
+        let err = authorize(Some("s3cret"), Some("s3cret")).unwrap_err();
+        assert_eq!(err.error_status_code, 401);
+
+        let err = authorize(None, Some("s3cret")).unwrap_err();
+        assert_eq!(err.error_status_code, 401);
+    }
+}
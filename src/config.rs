@@ -0,0 +1,194 @@
+use crate::{
+    db::{
+        keyspace, known_nodes, lookup_cache_capacity, lookup_cache_ttl, query_timeout,
+        startup_retry_secs,
+    },
+    n_grams::{
+        solver::model::{max_concurrency, sentence_boundaries},
+        solver::predictor::{
+            DEFAULT_K, DEFAULT_LOG_BASE, DEFAULT_POWER, DEFAULT_PREDICTOR, PREDICTORS,
+        },
+        word_freq_pair::{
+            duplicate_policy, max_rows_scanned, DuplicatePolicy, DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
+            PAGE_SIZE,
+        },
+    },
+    AppData,
+};
+use actix_web::{get, web, HttpResponse};
+use scylla::statement::Consistency;
+use serde::Serialize;
+
+/// Represents the effective runtime configuration, as resolved from the environment and the files.
+///
+/// # Fields
+///
+/// * `scylla_nodes` - The ScyllaDB nodes, with the credentials redacted.
+/// * `keyspace` - The keyspace of the n-gram tables.
+/// * `consistency` - The consistency level of the queries.
+/// * `startup_retry_secs` - How long the connection is retried at startup.
+/// * `query_timeout_ms` - The timeout of a query in milliseconds, `None` if the queries never time out.
+/// * `lookup_cache_capacity` - The capacity of the lookup cache in rows, `None` if the lookups are not cached.
+/// * `lookup_cache_ttl_secs` - How long a cached lookup is served.
+/// * `default_amount` - The default amount of word frequency pairs.
+/// * `page_size` - The number of rows fetched per page.
+/// * `max_rows_scanned` - The upper bound of the rows read for one varying index.
+/// * `duplicate_rows` - How the duplicate rows of the same word are merged.
+/// * `max_concurrency` - The upper bound of the queries the solver runs at once.
+/// * `sentence_boundaries` - Whether the solver queries the sentence boundaries.
+/// * `predictors` - The names of the predictors.
+/// * `default_predictor` - The predictor used when none is chosen.
+/// * `default_power` - The default power of the `power_sum` predictor.
+/// * `default_k` - The default k of the `lidstone` predictor.
+/// * `default_log_base` - The default base of the logarithm of the `max` and `sum` scores.
+/// * `confusion_groups` - The labels of the loaded confusion groups.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfig {
+    pub scylla_nodes: Vec<String>,
+    pub keyspace: &'static str,
+    pub consistency: String,
+    pub startup_retry_secs: u64,
+    pub query_timeout_ms: Option<u128>,
+    pub lookup_cache_capacity: Option<usize>,
+    pub lookup_cache_ttl_secs: u64,
+    pub default_amount: i32,
+    pub page_size: i32,
+    pub max_rows_scanned: usize,
+    pub duplicate_rows: DuplicatePolicy,
+    pub max_concurrency: usize,
+    pub sentence_boundaries: bool,
+    pub predictors: Vec<&'static str>,
+    pub default_predictor: &'static str,
+    pub default_power: f64,
+    pub default_k: f64,
+    pub default_log_base: f64,
+    pub confusion_groups: Vec<String>,
+}
+
+impl EffectiveConfig {
+    /// Creates the effective configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `scylla_nodes` - The ScyllaDB nodes.
+    /// * `consistency` - The consistency level of the queries.
+    /// * `group_labels` - The labels of the loaded confusion groups.
+    ///
+    /// # Returns
+    ///
+    /// The `EffectiveConfig` with the secrets redacted.
+    pub fn new(scylla_nodes: &[String], consistency: Consistency, group_labels: &[String]) -> Self {
+        EffectiveConfig {
+            scylla_nodes: scylla_nodes.iter().map(|node| redact_uri(node)).collect(),
+            keyspace: keyspace(),
+            consistency: format!("{:?}", consistency),
+            startup_retry_secs: startup_retry_secs(),
+            query_timeout_ms: query_timeout().map(|timeout| timeout.as_millis()),
+            lookup_cache_capacity: lookup_cache_capacity(),
+            lookup_cache_ttl_secs: lookup_cache_ttl().as_secs(),
+            default_amount: DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
+            page_size: PAGE_SIZE,
+            max_rows_scanned: max_rows_scanned(),
+            duplicate_rows: duplicate_policy(),
+            max_concurrency: max_concurrency(),
+            sentence_boundaries: sentence_boundaries(),
+            predictors: PREDICTORS.to_vec(),
+            default_predictor: DEFAULT_PREDICTOR,
+            default_power: DEFAULT_POWER,
+            default_k: DEFAULT_K,
+            default_log_base: DEFAULT_LOG_BASE,
+            confusion_groups: group_labels.to_vec(),
+        }
+    }
+}
+
+/// Redacts the credentials of the uri.
+///
+/// # Arguments
+///
+/// * `uri` - The uri, e.g. `user:password@127.0.0.1:9042`.
+///
+/// # Returns
+///
+/// The uri with everything before the `@` replaced by `***`.
+fn redact_uri(uri: &str) -> String {
+    match uri.rsplit_once('@') {
+        Some((_, host)) => format!("***@{}", host),
+        None => uri.to_string(),
+    }
+}
+
+/// Handles the configuration request.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The effective configuration.
+#[get("/config")]
+async fn get_config(data: web::Data<AppData>) -> HttpResponse {
+    let nodes = known_nodes().unwrap_or_default();
+
+    HttpResponse::Ok().json(EffectiveConfig::new(
+        &nodes,
+        data.consistency,
+        &data.loaded().group_labels,
+    ))
+}
+
+/// Initializes the configuration routes.
+///
+/// # Arguments
+///
+/// * `cfg` - The service configuration.
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_uri() {
+        assert_eq!(redact_uri("127.0.0.1:9042"), "127.0.0.1:9042");
+        assert_eq!(
+            redact_uri("scylla:s3cr@t@10.0.0.1:9042"),
+            "***@10.0.0.1:9042"
+        );
+    }
+
+    #[test]
+    fn test_effective_config() {
+        let config = EffectiveConfig::new(
+            &[
+                "user:password@127.0.0.1:9042".to_string(),
+                "127.0.0.2:9042".to_string(),
+            ],
+            Consistency::LocalQuorum,
+            &["yat".to_string(), "bi/bih".to_string()],
+        );
+        let json = serde_json::to_value(&config).unwrap();
+
+        assert_eq!(
+            json["scyllaNodes"],
+            serde_json::json!(["***@127.0.0.1:9042", "127.0.0.2:9042"])
+        );
+        assert_eq!(json["keyspace"], "n_grams");
+        assert_eq!(json["consistency"], "LocalQuorum");
+        assert_eq!(json["defaultAmount"], 50);
+        assert_eq!(
+            json["predictors"],
+            serde_json::json!(["max", "sum", "power_sum", "lidstone", "stupid_backoff"])
+        );
+        assert_eq!(json["defaultPredictor"], "max");
+        assert_eq!(
+            json["confusionGroups"],
+            serde_json::json!(["yat", "bi/bih"])
+        );
+        assert!(!json.to_string().contains("password"));
+    }
+}
@@ -1,8 +1,22 @@
+use crate::n_grams::word_freq_pair::PAGE_SIZE;
+use futures::{
+    future::BoxFuture,
+    stream::{Stream, StreamExt},
+};
 use scylla::{
     prepared_statement::PreparedStatement, serialize::row::SerializeRow, statement::Consistency,
     transport::iterator::RowIterator, Session, SessionBuilder,
 };
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
 
 pub static GET_FREQ_3: &str =
     "SELECT freq FROM n_grams.three_grams_1_2_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ?";
@@ -22,6 +36,15 @@ pub static GET_BY_SECOND_AND_THIRD_3: &str =
 pub static GET_BY_FIRST_AND_THIRD_3: &str =
     "SELECT word_2, freq FROM n_grams.three_grams_1_3_pk WHERE word_1 = ? AND word_3 = ?";
 
+/// The skip-bigrams of the first and the third word of the trigrams, every row is one skipped
+/// middle word, so a skip-bigram reads its whole partition.
+pub static GET_ALL_SKIP_VARYING_3_3: &str =
+    "SELECT word_3, freq FROM n_grams.three_grams_1_3_pk WHERE word_1 = ? AND word_3 IN ";
+
+/// The skip-bigrams of the first and the third word of the trigrams, with the first word varying.
+pub static GET_ALL_SKIP_VARYING_3_1: &str =
+    "SELECT word_1, freq FROM n_grams.three_grams_1_3_pk WHERE word_3 = ? AND word_1 IN ";
+
 pub static GET_BY_FIRST_AND_SECOND_3: &str =
     "SELECT word_3, freq FROM n_grams.three_grams_1_2_pk WHERE word_1 = ? AND word_2 = ?";
 
@@ -42,146 +65,2143 @@ pub static GET_BY_SECOND_2: &str =
 pub static GET_BY_FIRST_2: &str =
     "SELECT word_2, freq FROM n_grams.two_grams_1_pk WHERE word_1 = ?";
 
+pub static GET_FREQ_4: &str =
+    "SELECT freq FROM n_grams.four_grams_1_2_3_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ? AND word_4 = ?";
+
+pub static GET_BY_SECOND_THIRD_AND_FOURTH_4: &str =
+    "SELECT word_1, freq FROM n_grams.four_grams_2_3_4_pk WHERE word_2 = ? AND word_3 = ? AND word_4 = ?";
+
+pub static GET_BY_FIRST_THIRD_AND_FOURTH_4: &str =
+    "SELECT word_2, freq FROM n_grams.four_grams_1_3_4_pk WHERE word_1 = ? AND word_3 = ? AND word_4 = ?";
+
+pub static GET_BY_FIRST_SECOND_AND_FOURTH_4: &str =
+    "SELECT word_3, freq FROM n_grams.four_grams_1_2_4_pk WHERE word_1 = ? AND word_2 = ? AND word_4 = ?";
+
+pub static GET_BY_FIRST_SECOND_AND_THIRD_4: &str =
+    "SELECT word_4, freq FROM n_grams.four_grams_1_2_3_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ?";
+
+pub static GET_FREQ_5: &str =
+    "SELECT freq FROM n_grams.five_grams_1_2_3_4_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ? AND word_4 = ? AND word_5 = ?";
+
+pub static GET_BY_SECOND_THIRD_FOURTH_AND_FIFTH_5: &str =
+    "SELECT word_1, freq FROM n_grams.five_grams_2_3_4_5_pk WHERE word_2 = ? AND word_3 = ? AND word_4 = ? AND word_5 = ?";
+
+pub static GET_BY_FIRST_THIRD_FOURTH_AND_FIFTH_5: &str =
+    "SELECT word_2, freq FROM n_grams.five_grams_1_3_4_5_pk WHERE word_1 = ? AND word_3 = ? AND word_4 = ? AND word_5 = ?";
+
+pub static GET_BY_FIRST_SECOND_FOURTH_AND_FIFTH_5: &str =
+    "SELECT word_3, freq FROM n_grams.five_grams_1_2_4_5_pk WHERE word_1 = ? AND word_2 = ? AND word_4 = ? AND word_5 = ?";
+
+pub static GET_BY_FIRST_SECOND_THIRD_AND_FIFTH_5: &str =
+    "SELECT word_4, freq FROM n_grams.five_grams_1_2_3_5_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ? AND word_5 = ?";
+
+pub static GET_BY_FIRST_SECOND_THIRD_AND_FOURTH_5: &str =
+    "SELECT word_5, freq FROM n_grams.five_grams_1_2_3_4_pk WHERE word_1 = ? AND word_2 = ? AND word_3 = ? AND word_4 = ?";
+
+pub static GET_FREQ_1: &str = "SELECT freq FROM n_grams.one_grams WHERE word = ?";
+
 pub static GET_ALL_1: &str = "SELECT word, freq FROM n_grams.one_grams WHERE word = ?";
 
 pub static GET_ALL_VARYING_1: &str = "SELECT * FROM n_grams.one_grams WHERE word IN ";
 
+/// All the queries used by the application, used to validate the schema at startup.
+pub static ALL_QUERIES: [&str; 29] = [
+    GET_FREQ_3,
+    GET_ALL_3,
+    GET_ALL_VARYING_3_3,
+    GET_ALL_VARYING_3_1,
+    GET_ALL_SKIP_VARYING_3_3,
+    GET_ALL_SKIP_VARYING_3_1,
+    GET_BY_SECOND_AND_THIRD_3,
+    GET_BY_FIRST_AND_THIRD_3,
+    GET_BY_FIRST_AND_SECOND_3,
+    GET_FREQ_2,
+    GET_ALL_2,
+    GET_ALL_VARYING_2_2,
+    GET_ALL_VARYING_2_1,
+    GET_BY_SECOND_2,
+    GET_BY_FIRST_2,
+    GET_FREQ_4,
+    GET_BY_SECOND_THIRD_AND_FOURTH_4,
+    GET_BY_FIRST_THIRD_AND_FOURTH_4,
+    GET_BY_FIRST_SECOND_AND_FOURTH_4,
+    GET_BY_FIRST_SECOND_AND_THIRD_4,
+    GET_FREQ_5,
+    GET_BY_SECOND_THIRD_FOURTH_AND_FIFTH_5,
+    GET_BY_FIRST_THIRD_FOURTH_AND_FIFTH_5,
+    GET_BY_FIRST_SECOND_FOURTH_AND_FIFTH_5,
+    GET_BY_FIRST_SECOND_THIRD_AND_FIFTH_5,
+    GET_BY_FIRST_SECOND_THIRD_AND_FOURTH_5,
+    GET_FREQ_1,
+    GET_ALL_1,
+    GET_ALL_VARYING_1,
+];
+
+/// The keyspace the queries are written against, replaced by `keyspace` when they are run.
+pub static DEFAULT_KEYSPACE: &str = "n_grams";
+
+/// The keyspace of the n-gram tables, set once by `init` from `SCYLLA_KEYSPACE`.
+static KEYSPACE: OnceLock<Keyspace> = OnceLock::new();
+
+/// Represents the keyspace of the n-gram tables.
+///
+/// # Fields
+///
+/// * `name` - The name of the keyspace.
+/// * `queries` - Every query of `ALL_QUERIES` moved to the keyspace, by the original query.
+struct Keyspace {
+    name: String,
+    queries: HashMap<&'static str, String>,
+}
+
+impl Keyspace {
+    /// Creates a new `Keyspace`, moving every query of `ALL_QUERIES` to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the keyspace.
+    ///
+    /// # Returns
+    ///
+    /// A `Keyspace`.
+    fn new(name: String) -> Self {
+        let queries = ALL_QUERIES
+            .iter()
+            .map(|query| (*query, with_keyspace(query, &name)))
+            .collect();
+
+        Keyspace { name, queries }
+    }
+}
+
+/// Gets the keyspace of the n-gram tables.
+///
+/// # Returns
+///
+/// The keyspace set by `init`, or `DEFAULT_KEYSPACE` if the session is not initialized.
+pub fn keyspace() -> &'static str {
+    KEYSPACE
+        .get()
+        .map_or(DEFAULT_KEYSPACE, |keyspace| keyspace.name.as_str())
+}
+
+/// Moves the query to the keyspace set by `init`.
+///
+/// # Arguments
+///
+/// * `query` - The query, selecting from a table of `DEFAULT_KEYSPACE`.
+///
+/// # Returns
+///
+/// The query moved once by `init` if it is one of `ALL_QUERIES`, otherwise the query moved now,
+/// or the query itself if the session is not initialized.
+pub fn qualified_query(query: &str) -> Cow<'_, str> {
+    match KEYSPACE.get() {
+        Some(keyspace) => match keyspace.queries.get(query) {
+            Some(qualified) => Cow::Borrowed(qualified.as_str()),
+            None => Cow::Owned(with_keyspace(query, &keyspace.name)),
+        },
+        None => Cow::Borrowed(query),
+    }
+}
+
+/// Parses the keyspace name.
+///
+/// # Arguments
+///
+/// * `keyspace` - The keyspace name.
+///
+/// # Returns
+///
+/// A `Result` containing the keyspace if it is a valid unquoted CQL name, otherwise a `String` with the error message.
+pub fn parse_keyspace(keyspace: &str) -> Result<String, String> {
+    let valid = keyspace.len() <= 48
+        && keyspace.starts_with(|c: char| c.is_ascii_alphabetic())
+        && keyspace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    match valid {
+        true => Ok(keyspace.to_string()),
+        false => Err(format!("Invalid keyspace: {}", keyspace)),
+    }
+}
+
+/// Moves the query to the keyspace.
+///
+/// # Arguments
+///
+/// * `query` - The query, selecting from a table of `DEFAULT_KEYSPACE`.
+/// * `keyspace` - The keyspace.
+///
+/// # Returns
+///
+/// The query selecting from the table of the same name in the keyspace, the columns and the keys are kept.
+pub fn with_keyspace(query: &str, keyspace: &str) -> String {
+    query.replacen(
+        &format!("FROM {}.", DEFAULT_KEYSPACE),
+        &format!("FROM {}.", keyspace),
+        1,
+    )
+}
+
+/// Qualifies the table with the keyspace.
+///
+/// # Arguments
+///
+/// * `table` - The table, one of `N_GRAM_TABLES`.
+///
+/// # Returns
+///
+/// The table, including the keyspace.
+pub fn qualified_table(table: &str) -> String {
+    format!("{}.{}", keyspace(), table)
+}
+
+/// The n-gram tables that hold one row per distinct n-gram, by order.
+pub static N_GRAM_TABLES: [(i32, &str); 3] = [
+    (1, "one_grams"),
+    (2, "two_grams_1_pk"),
+    (3, "three_grams_1_2_pk"),
+];
+
+static PING: &str = "SELECT now() FROM system.local";
+
+static TABLE_EXISTS: &str =
+    "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ? AND table_name = ?";
+
 /// Represents the error that can occur when querying the database.
 ///
 /// # Fields
 ///
-/// * `ScyllaError` - The error that occurs when querying the database.
+/// * `ScyllaError` - The error that occurs when querying the database, with the message of the driver.
 /// * `NotFound` - The error that occurs when the query result is not found.
+#[derive(Debug, PartialEq)]
 pub enum QueryError {
-    ScyllaError,
+    ScyllaError(String),
     NotFound,
 }
 
-/// Initializes the ScyllaDB session.
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::ScyllaError(message) => write!(f, "Can not execute query: {}", message),
+            QueryError::NotFound => write!(f, "No results found"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// The delay before the first connection retry, doubled after every failure.
+static INITIAL_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// The upper bound of the delay between the connection retries.
+static MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Retries the connection with exponential backoff until it succeeds or the time runs out.
+///
+/// # Arguments
+///
+/// * `connect` - Attempts the connection.
+/// * `total` - How long to keep retrying, zero makes a single attempt.
+/// * `initial_delay` - The delay before the first retry.
 ///
 /// # Returns
 ///
-/// A `Result` containing the `Session` if the connection is successful, otherwise a `&'static str` with the error message.
+/// A `Result` containing the connection, otherwise the error of the last attempt.
+pub async fn retry_with_backoff<F, Fut, T, E>(
+    mut connect: F,
+    total: Duration,
+    initial_delay: Duration,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let deadline = Instant::now() + total;
+    let mut delay = initial_delay;
+
+    loop {
+        let err = match connect().await {
+            Ok(connection) => return Ok(connection),
+            Err(err) => err,
+        };
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(err);
+        }
+
+        tokio::time::sleep(delay.min(deadline - now)).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Parses the consistency level.
 ///
-/// # Errors
+/// # Arguments
 ///
-/// If the connection to ScyllaDB can not be established, a `&'static str` with the error message will be returned.
-pub async fn init() -> Result<Arc<Session>, &'static str> {
-    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+/// * `consistency` - The consistency level in the CQL notation, e.g. `LOCAL_QUORUM`, case insensitive.
+///
+/// # Returns
+///
+/// A `Result` containing the `Consistency` if the level is known, otherwise a `String` with the error message.
+pub fn parse_consistency(consistency: &str) -> Result<Consistency, String> {
+    match consistency.to_uppercase().as_str() {
+        "ANY" => Ok(Consistency::Any),
+        "ONE" => Ok(Consistency::One),
+        "TWO" => Ok(Consistency::Two),
+        "THREE" => Ok(Consistency::Three),
+        "QUORUM" => Ok(Consistency::Quorum),
+        "ALL" => Ok(Consistency::All),
+        "LOCAL_QUORUM" => Ok(Consistency::LocalQuorum),
+        "EACH_QUORUM" => Ok(Consistency::EachQuorum),
+        "LOCAL_ONE" => Ok(Consistency::LocalOne),
+        _ => Err(format!("Invalid consistency level: {}", consistency)),
+    }
+}
 
-    let session = match SessionBuilder::new().known_node(uri).build().await {
-        Ok(session) => session,
-        Err(_) => return Err("Failed to connect to ScyllaDB"),
+/// The ScyllaDB node connected to when neither `SCYLLA_NODES` nor `SCYLLA_URI` is set.
+pub static DEFAULT_SCYLLA_URI: &str = "127.0.0.1:9042";
+
+/// Parses the comma-separated ScyllaDB nodes.
+///
+/// # Arguments
+///
+/// * `nodes` - The nodes, e.g. `10.0.0.1:9042, 10.0.0.2:9042`.
+///
+/// # Returns
+///
+/// A `Result` containing the trimmed nodes, otherwise a `String` with the error message if there are none.
+pub fn parse_nodes(nodes: &str) -> Result<Vec<String>, String> {
+    let parsed: Vec<String> = nodes
+        .split(',')
+        .map(str::trim)
+        .filter(|node| !node.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    match parsed.is_empty() {
+        true => Err(format!("Invalid SCYLLA_NODES: {}", nodes)),
+        false => Ok(parsed),
+    }
+}
+
+/// Gets the ScyllaDB nodes the session is seeded with.
+///
+/// # Returns
+///
+/// A `Result` containing the nodes of `SCYLLA_NODES`, or the single `SCYLLA_URI` if it is not set,
+/// or `DEFAULT_SCYLLA_URI` if neither is set, otherwise a `String` with the error message.
+pub fn known_nodes() -> Result<Vec<String>, String> {
+    match std::env::var("SCYLLA_NODES") {
+        Ok(nodes) => parse_nodes(&nodes),
+        Err(_) => Ok(vec![
+            std::env::var("SCYLLA_URI").unwrap_or_else(|_| DEFAULT_SCYLLA_URI.to_string())
+        ]),
+    }
+}
+
+/// Gets the credentials of the ScyllaDB connection.
+///
+/// # Returns
+///
+/// An `Option` containing `SCYLLA_USER` and `SCYLLA_PASSWORD`, `None` if `SCYLLA_USER` is not set.
+/// The password is empty if only the user is set.
+pub fn credentials() -> Option<(String, String)> {
+    let user = std::env::var("SCYLLA_USER").ok()?;
+    let password = std::env::var("SCYLLA_PASSWORD").unwrap_or_default();
+
+    Some((user, password))
+}
+
+/// Builds the TLS context of the ScyllaDB connection.
+///
+/// The server certificate is verified against the CA certificate of `SCYLLA_CA_CERT`.
+///
+/// # Returns
+///
+/// A `Result` containing the `SslContext`, `None` if `SCYLLA_CA_CERT` is not set, otherwise a `String` with the error message.
+#[cfg(feature = "tls")]
+pub fn tls_context() -> Result<Option<openssl::ssl::SslContext>, String> {
+    use openssl::ssl::{SslContextBuilder, SslMethod, SslVerifyMode};
+
+    let ca_cert = match std::env::var("SCYLLA_CA_CERT") {
+        Ok(ca_cert) => ca_cert,
+        Err(_) => return Ok(None),
     };
 
-    let session = Arc::new(session);
+    let mut context = SslContextBuilder::new(SslMethod::tls()).map_err(|err| err.to_string())?;
+    context
+        .set_ca_file(&ca_cert)
+        .map_err(|err| format!("Invalid SCYLLA_CA_CERT {}: {}", ca_cert, err))?;
+    context.set_verify(SslVerifyMode::PEER);
 
-    Ok(session)
+    Ok(Some(context.build()))
 }
 
-/// Represents the query factory.
+/// Checks that TLS is not requested, the crate is built without the `tls` feature.
 ///
-/// # Fields
+/// # Returns
 ///
-/// * `preapred_query` - The prepared query.
-pub struct QueryFactory {
-    prepared_query: PreparedStatement,
+/// A `Result` containing `()`, otherwise a `String` with the error message if `SCYLLA_CA_CERT` is set.
+#[cfg(not(feature = "tls"))]
+pub fn tls_context() -> Result<(), String> {
+    match std::env::var("SCYLLA_CA_CERT") {
+        Ok(_) => Err("SCYLLA_CA_CERT requires the tls feature".to_string()),
+        Err(_) => Ok(()),
+    }
 }
 
-impl QueryFactory {
-    /// Builds the query factory.
-    ///
-    /// # Arguments
-    ///
-    /// * `session` - The ScyllaDB session.
-    /// * `query` - The query to be executed.
-    /// * `consistency` - The consistency level.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` containing the `QueryFactory` if the preparation is successful, otherwise a `&'static str` with the error message.
-    ///
-    /// # Errors
-    ///
-    /// If the query can not be prepared, a `&'static str` with the error message will be returned.
-    pub async fn build(
-        session: Arc<Session>,
-        query: &str,
-        consistency: Consistency,
-    ) -> Result<Self, &'static str> {
-        let mut prepared_query = match session.prepare(query).await {
-            Ok(prepared_query) => prepared_query,
-            Err(_) => return Err("Failed to prepare query"),
-        };
+/// Gets how long the connection is retried at startup.
+///
+/// # Returns
+///
+/// The value of `SCYLLA_STARTUP_RETRY_SECS` in seconds, `0` if it is not set.
+pub fn startup_retry_secs() -> u64 {
+    std::env::var("SCYLLA_STARTUP_RETRY_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(0)
+}
 
-        prepared_query.set_consistency(consistency);
+/// The default timeout of a query, in milliseconds.
+pub static DEFAULT_QUERY_TIMEOUT_MS: u64 = 10000;
 
-        Ok(QueryFactory { prepared_query })
+/// The start of the error message of a query that took longer than the timeout.
+pub static QUERY_TIMED_OUT: &str = "Query timed out";
+
+/// Gets the timeout of a query.
+///
+/// # Returns
+///
+/// The value of `SCYLLA_QUERY_TIMEOUT_MS` in milliseconds, `DEFAULT_QUERY_TIMEOUT_MS` if it is not
+/// set, `None` if it is `0` and the queries never time out.
+pub fn query_timeout() -> Option<Duration> {
+    let ms = std::env::var("SCYLLA_QUERY_TIMEOUT_MS")
+        .ok()
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_QUERY_TIMEOUT_MS);
+
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+/// The default number of rows the cache keeps of each kind, a frequency lookup counts as one row.
+pub static DEFAULT_LOOKUP_CACHE_CAPACITY: usize = 10000;
+
+/// The default time a cached lookup is served, in seconds.
+pub static DEFAULT_LOOKUP_CACHE_TTL_SECS: u64 = 300;
+
+/// Gets the capacity of the lookup cache.
+///
+/// # Returns
+///
+/// The value of `LOOKUP_CACHE_CAPACITY` in rows, `DEFAULT_LOOKUP_CACHE_CAPACITY` if it is not set,
+/// `None` if it is `0` and the lookups are not cached.
+pub fn lookup_cache_capacity() -> Option<usize> {
+    let capacity = std::env::var("LOOKUP_CACHE_CAPACITY")
+        .ok()
+        .and_then(|capacity| capacity.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_LOOKUP_CACHE_CAPACITY);
+
+    (capacity > 0).then_some(capacity)
+}
+
+/// Gets how long a cached lookup is served.
+///
+/// # Returns
+///
+/// The value of `LOOKUP_CACHE_TTL_SECS` in seconds, `DEFAULT_LOOKUP_CACHE_TTL_SECS` if it is not set.
+pub fn lookup_cache_ttl() -> Duration {
+    let secs = std::env::var("LOOKUP_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_LOOKUP_CACHE_TTL_SECS);
+
+    Duration::from_secs(secs)
+}
+
+/// Initializes the ScyllaDB session.
+///
+/// The session is seeded with every node of `SCYLLA_NODES`, see `known_nodes`.
+/// It authenticates with `SCYLLA_USER` and `SCYLLA_PASSWORD` if they are set, and is encrypted
+/// if `SCYLLA_CA_CERT` is set, which needs the `tls` feature. Otherwise the connection is plaintext.
+/// The connection is retried for `SCYLLA_STARTUP_RETRY_SECS` seconds, by default it is attempted once.
+/// The consistency level of the queries is read from `SCYLLA_CONSISTENCY`, `ONE` if it is not set.
+/// The keyspace of the n-gram tables is read from `SCYLLA_KEYSPACE`, `DEFAULT_KEYSPACE` if it is not set.
+///
+/// # Returns
+///
+/// A `Result` containing the `Session` and the `Consistency` if the connection is successful, otherwise a `String` with the error message.
+///
+/// # Errors
+///
+/// If `SCYLLA_CONSISTENCY` is not a known consistency level, a `String` with the error message will be returned.
+/// If `SCYLLA_KEYSPACE` is not a valid keyspace name, a `String` with the error message will be returned.
+/// If `SCYLLA_NODES` has no nodes, a `String` with the error message will be returned.
+/// If `SCYLLA_CA_CERT` can not be loaded, a `String` with the error message will be returned.
+/// If the connection to ScyllaDB can not be established, a `String` with the errors of the attempts will be returned.
+pub async fn init() -> Result<(Arc<Session>, Consistency), String> {
+    let consistency = match std::env::var("SCYLLA_CONSISTENCY") {
+        Ok(consistency) => parse_consistency(&consistency)?,
+        Err(_) => Consistency::One,
+    };
+
+    let keyspace = match std::env::var("SCYLLA_KEYSPACE") {
+        Ok(keyspace) => parse_keyspace(&keyspace)?,
+        Err(_) => DEFAULT_KEYSPACE.to_string(),
+    };
+    KEYSPACE.get_or_init(|| Keyspace::new(keyspace));
+
+    let nodes = known_nodes()?;
+    let credentials = credentials();
+    #[cfg(feature = "tls")]
+    let tls = tls_context()?;
+    #[cfg(not(feature = "tls"))]
+    tls_context()?;
+
+    let retry_secs = startup_retry_secs();
+
+    let errors = std::sync::Mutex::new(Vec::<String>::new());
+
+    let connect = || async {
+        let mut builder = nodes.iter().fold(SessionBuilder::new(), |builder, node| {
+            builder.known_node(node)
+        });
+        if let Some((user, password)) = &credentials {
+            builder = builder.user(user, password);
+        }
+        #[cfg(feature = "tls")]
+        {
+            builder = builder.ssl_context(tls.clone());
+        }
+        let session = builder.build().await;
+        if let Err(err) = &session {
+            tracing::warn!(nodes = ?nodes, error = %err, "failed to connect to ScyllaDB");
+            errors.lock().unwrap().push(err.to_string());
+        }
+        session
+    };
+
+    let session = match retry_with_backoff(
+        connect,
+        Duration::from_secs(retry_secs),
+        INITIAL_RETRY_DELAY,
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(_) => {
+            let errors = errors.into_inner().unwrap();
+            return Err(connection_error(&nodes, &errors));
+        }
+    };
+
+    let session = Arc::new(session);
+
+    Ok((session, consistency))
+}
+
+/// Describes the failed connection attempts.
+///
+/// # Arguments
+///
+/// * `nodes` - The nodes the session was seeded with.
+/// * `errors` - The error of every attempt.
+///
+/// # Returns
+///
+/// The error message, with the distinct errors in the order they first occurred.
+fn connection_error(nodes: &[String], errors: &[String]) -> String {
+    let mut distinct: Vec<&str> = vec![];
+    for err in errors {
+        if !distinct.contains(&err.as_str()) {
+            distinct.push(err);
+        }
     }
 
-    /// Executes the query.
-    ///
-    /// # Arguments
-    ///
-    /// * `session` - The ScyllaDB session.
-    /// * `params` - The query parameters.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` containing the `RowIterator` if the query is successful, otherwise a `QueryError`.
-    ///
-    /// # Errors
-    ///
-    /// If the query can not be executed, a `QueryError` will be returned.
-    /// If the query does not return any results, a `QueryError` will be returned.
-    pub async fn execute_one(
-        &self,
-        session: Arc<Session>,
-        params: impl SerializeRow,
-    ) -> Result<RowIterator, QueryError> {
-        let query = PreparedStatement::clone(&self.prepared_query);
-        let rows_stream = match session.execute_iter(query, params).await {
-            Ok(rows_stream) => rows_stream,
-            Err(_) => return Err(QueryError::ScyllaError),
-        };
+    format!(
+        "Failed to connect to ScyllaDB at {} after {} attempts: {}",
+        nodes.join(", "),
+        errors.len(),
+        distinct.join("; ")
+    )
+}
 
-        Ok(rows_stream)
+/// Checks that ScyllaDB is reachable.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if ScyllaDB responds, otherwise a `String` with the error message.
+pub async fn ping(session: Arc<Session>) -> Result<(), String> {
+    match session.query(PING, ()).await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("ScyllaDB is not reachable: {}", err)),
     }
 }
 
-/// Gets the n-gram string.
+/// Extracts the keyspace and the table name from the query.
 ///
 /// # Arguments
 ///
 /// * `query` - The query.
-/// * `static_params` - The static parameters.
-/// * `varying_param` - The varying parameter.
 ///
 /// # Returns
 ///
-/// The n-gram string.
-///
-/// # Panics
-///
-/// If the query is invalid, a panic will occur.
-pub fn get_n_gram_string(query: &str, static_params: &Vec<&str>, varying_param: &str) -> String {
-    if query.starts_with("SELECT word_3, freq FROM n_grams.three_grams_1_2_pk WHERE word_1 = ? AND word_2 = ? AND word_3 IN") {
-        let first = static_params.get(0).unwrap();
-        let second = static_params.get(1).unwrap();
-        format!("{} {} {}", first, second, varying_param)
-    } else if query.starts_with("SELECT word_1, freq FROM n_grams.three_grams_2_3_pk WHERE word_2 = ? AND word_3 = ? AND word_1 IN") {
-        let second = static_params.get(0).unwrap();
-        let third = static_params.get(1).unwrap();
-        format!("{} {} {}", varying_param, second, third)
-    } else if query.starts_with("SELECT word_2, freq FROM n_grams.two_grams_1_pk WHERE word_1 = ? AND word_2 IN") {
-        let first = static_params.get(0).unwrap();
-        format!("{} {}", first, varying_param)
-    } else if query.starts_with("SELECT word_1, freq FROM n_grams.two_grams_2_pk WHERE word_2 = ? AND word_1 IN") {
-        let second = static_params.get(0).unwrap();
-        format!("{} {}", varying_param, second)
-    } else if query.starts_with("SELECT * FROM n_grams.one_grams WHERE word IN") {
-        format!("{}", varying_param)
-    } else {
-        panic!("Invalid query: {}", query);
+/// An `Option` containing the keyspace and the table name if the query selects from a `keyspace.table`, otherwise `None`.
+pub fn extract_table_name(query: &str) -> Option<(&str, &str)> {
+    let mut tokens = query.split_whitespace();
+
+    tokens.find(|token| token.eq_ignore_ascii_case("FROM"))?;
+
+    tokens.next()?.split_once('.')
+}
+
+/// Validates that every table referenced by the queries exists in the schema.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if all the tables exist, otherwise a `String` with the error message.
+///
+/// # Errors
+///
+/// If the schema can not be read, or some tables are missing, a `String` with the error message will be returned.
+pub async fn validate_tables(session: Arc<Session>) -> Result<(), String> {
+    let queries: Vec<Cow<str>> = ALL_QUERIES
+        .iter()
+        .map(|query| qualified_query(query))
+        .collect();
+    let mut tables: Vec<(&str, &str)> = vec![];
+
+    for query in &queries {
+        match extract_table_name(query) {
+            Some(table) => {
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+            None => return Err(format!("Can not extract the table name from: {}", query)),
+        }
+    }
+
+    let mut missing: Vec<String> = vec![];
+
+    for (keyspace, table) in tables {
+        let result = match session.query(TABLE_EXISTS, (keyspace, table)).await {
+            Ok(result) => result,
+            Err(err) => return Err(format!("Failed to read the schema: {}", err)),
+        };
+
+        if result.rows_num().unwrap_or(0) == 0 {
+            missing.push(format!("{}.{}", keyspace, table));
+        }
+    }
+
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Missing tables: {}", missing.join(", "))),
+    }
+}
+
+/// Counts the rows of the table, which is the number of distinct n-grams.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+/// * `table` - The table, including the keyspace.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows if the query is successful, otherwise a `String` with the error message.
+pub async fn count_rows(session: Arc<Session>, table: &str) -> Result<i64, String> {
+    let query = format!("SELECT COUNT(*) FROM {}", table);
+
+    let result = match session.query(query, ()).await {
+        Ok(result) => result,
+        Err(err) => return Err(format!("Failed to count the rows of {}: {}", table, err)),
+    };
+
+    match result.single_row_typed::<(i64,)>() {
+        Ok((count,)) => Ok(count),
+        Err(err) => Err(format!(
+            "Failed to read the row count of {}: {}",
+            table, err
+        )),
+    }
+}
+
+/// Counts the distinct n-grams of every order in `N_GRAM_TABLES`.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+///
+/// # Returns
+///
+/// A `Result` containing the number of distinct n-grams by order if the queries are successful, otherwise a `String` with the error message.
+pub async fn count_distinct_ngrams(session: Arc<Session>) -> Result<HashMap<i32, i64>, String> {
+    let mut number_of_distinct_ngrams: HashMap<i32, i64> = HashMap::new();
+
+    for (n, table) in N_GRAM_TABLES {
+        let distinct = count_rows(Arc::clone(&session), &qualified_table(table)).await?;
+        number_of_distinct_ngrams.insert(n, distinct);
+    }
+
+    Ok(number_of_distinct_ngrams)
+}
+
+/// Sums the frequencies of the table, which is the total number of n-grams.
+///
+/// The rows are streamed and summed as `i64`, because `SUM` over the `int` column would overflow.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+/// * `table` - The table, including the keyspace.
+///
+/// # Returns
+///
+/// A `Result` containing the sum of the frequencies if the query is successful, otherwise a `String` with the error message.
+pub async fn sum_frequencies(session: Arc<Session>, table: &str) -> Result<i64, String> {
+    let query = format!("SELECT freq FROM {}", table);
+
+    let mut row_stream = match session.query_iter(query, ()).await {
+        Ok(rows) => rows.into_typed::<(i32,)>(),
+        Err(err) => {
+            return Err(format!(
+                "Failed to read the frequencies of {}: {}",
+                table, err
+            ))
+        }
+    };
+
+    let mut sum: i64 = 0;
+
+    while let Some(row) = row_stream.next().await {
+        match row {
+            Ok((freq,)) => sum += freq as i64,
+            Err(err) => {
+                return Err(format!(
+                    "Failed to read the frequencies of {}: {}",
+                    table, err
+                ))
+            }
+        }
+    }
+
+    Ok(sum)
+}
+
+/// Caches the statements by their query string, so every query is prepared only once.
+///
+/// # Fields
+///
+/// * `statements` - The prepared statements.
+///
+/// # Methods
+///
+/// * `new` - Creates an empty `StatementCache`.
+/// * `get_or_prepare` - Gets the statement, preparing it on the first use.
+/// * `len` - The number of cached statements.
+pub struct StatementCache<V> {
+    statements: RwLock<HashMap<String, Arc<V>>>,
+}
+
+/// The cache of the statements prepared against ScyllaDB.
+pub type PreparedStatementCache = StatementCache<PreparedStatement>;
+
+impl<V> StatementCache<V> {
+    /// Creates an empty `StatementCache`.
+    ///
+    /// # Returns
+    ///
+    /// A `StatementCache`.
+    pub fn new() -> Self {
+        StatementCache {
+            statements: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the statement, preparing it on the first use.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query string.
+    /// * `prepare` - Prepares the statement when it is not cached.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the cached statement, otherwise the error of the preparation.
+    pub async fn get_or_prepare<F, Fut, E>(&self, query: &str, prepare: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some(statement) = self.statements.read().await.get(query) {
+            return Ok(Arc::clone(statement));
+        }
+
+        let statement = Arc::new(prepare().await?);
+
+        let mut statements = self.statements.write().await;
+        let statement = statements.entry(query.to_string()).or_insert(statement);
+
+        Ok(Arc::clone(statement))
+    }
+
+    /// The number of cached statements.
+    ///
+    /// # Returns
+    ///
+    /// The number of cached statements.
+    pub async fn len(&self) -> usize {
+        self.statements.read().await.len()
+    }
+
+    /// Whether no statements are cached.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the cache is empty.
+    pub async fn is_empty(&self) -> bool {
+        self.statements.read().await.is_empty()
+    }
+}
+
+impl<V> Default for StatementCache<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Represents the query factory.
+///
+/// # Fields
+///
+/// * `preapred_query` - The prepared query.
+pub struct QueryFactory {
+    prepared_query: PreparedStatement,
+}
+
+impl QueryFactory {
+    /// Builds the query factory.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The ScyllaDB session.
+    /// * `query` - The query to be executed.
+    /// * `consistency` - The consistency level.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `QueryFactory` if the preparation is successful, otherwise a `String` with the error message.
+    ///
+    /// # Errors
+    ///
+    /// If the query can not be prepared, a `String` with the message of the driver will be returned.
+    pub async fn build(
+        session: Arc<Session>,
+        query: &str,
+        consistency: Consistency,
+    ) -> Result<Self, String> {
+        let mut prepared_query = match session.prepare(query).await {
+            Ok(prepared_query) => prepared_query,
+            Err(err) => return Err(format!("Failed to prepare query: {}", err)),
+        };
+
+        prepared_query.set_consistency(consistency);
+
+        Ok(QueryFactory { prepared_query })
+    }
+
+    /// Builds the query factory, preparing the query only if it is not in the cache.
+    ///
+    /// The statements are cached by the query, and prepared against the keyspace, see `qualified_query`.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The ScyllaDB session.
+    /// * `cache` - The shared prepared statement cache.
+    /// * `query` - The query to be executed, selecting from a table of `DEFAULT_KEYSPACE`.
+    /// * `consistency` - The consistency level.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `QueryFactory` if the preparation is successful, otherwise a `String` with the error message.
+    ///
+    /// # Errors
+    ///
+    /// If the query can not be prepared, a `String` with the message of the driver will be returned.
+    pub async fn build_cached(
+        session: Arc<Session>,
+        cache: &PreparedStatementCache,
+        query: &str,
+        consistency: Consistency,
+    ) -> Result<Self, String> {
+        let prepared_query = cache
+            .get_or_prepare(query, || async move {
+                match session.prepare(qualified_query(query).into_owned()).await {
+                    Ok(prepared_query) => Ok(prepared_query),
+                    Err(err) => Err(format!("Failed to prepare query: {}", err)),
+                }
+            })
+            .await?;
+
+        let mut prepared_query = PreparedStatement::clone(&prepared_query);
+
+        prepared_query.set_consistency(consistency);
+
+        Ok(QueryFactory { prepared_query })
+    }
+
+    /// Sets the number of rows fetched per page.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_size` - The page size.
+    ///
+    /// # Returns
+    ///
+    /// The `QueryFactory` with the page size set.
+    pub fn with_page_size(mut self, page_size: i32) -> Self {
+        self.prepared_query.set_page_size(page_size);
+        self
+    }
+
+    /// Executes the query.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The ScyllaDB session.
+    /// * `params` - The query parameters.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RowIterator` if the query is successful, otherwise a `QueryError`.
+    ///
+    /// # Errors
+    ///
+    /// If the query can not be executed, a `QueryError::ScyllaError` with the message of the driver will be returned.
+    pub async fn execute_one(
+        &self,
+        session: Arc<Session>,
+        params: impl SerializeRow,
+    ) -> Result<RowIterator, QueryError> {
+        let query = PreparedStatement::clone(&self.prepared_query);
+        let rows_stream = match session.execute_iter(query, params).await {
+            Ok(rows_stream) => rows_stream,
+            Err(err) => return Err(QueryError::ScyllaError(err.to_string())),
+        };
+
+        Ok(rows_stream)
+    }
+}
+
+/// Represents a varying query, the n-grams that only differ in one word.
+///
+/// # Fields
+///
+/// * `query` - The CQL query, the static parameters are bound first and the candidates after them.
+/// * `static_params` - The other words of the n-gram, in order.
+/// * `index` - The zero-based index of the varying word in the n-gram.
+/// * `candidates` - The words the varying word is restricted to, empty for any word.
+/// * `max_rows` - The upper bound of the rows read, `None` reads all of them.
+/// * `skipped` - The zero-based index of the word of the stored n-gram that is skipped, any word
+///   matches it. `None` for the contiguous n-grams.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VaryingQuery {
+    pub query: String,
+    pub static_params: Vec<String>,
+    pub index: usize,
+    pub candidates: Vec<String>,
+    pub max_rows: Option<usize>,
+    pub skipped: Option<usize>,
+}
+
+/// Represents the storage of the n-gram frequencies.
+///
+/// # Methods
+///
+/// * `frequency` - Gets the frequency of the n-gram.
+/// * `varying` - Gets the words, with their frequencies, that complete the varying query.
+pub trait NgramStore: Send + Sync {
+    /// Gets the frequency of the n-gram.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The CQL query, see `Queryable::get_query`.
+    /// * `n_gram` - The words of the n-gram.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the frequency, `None` if the n-gram is not stored, otherwise a `String` with the error message.
+    fn frequency<'a>(
+        &'a self,
+        query: &'a str,
+        n_gram: Vec<String>,
+    ) -> BoxFuture<'a, Result<Option<i32>, String>>;
+
+    /// Gets the words, with their frequencies, that complete the varying query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The varying query.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the words and their frequencies, otherwise a `String` with the error message.
+    fn varying<'a>(
+        &'a self,
+        query: &'a VaryingQuery,
+    ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>>;
+}
+
+/// The n-gram store backed by ScyllaDB.
+///
+/// # Fields
+///
+/// * `session` - The ScyllaDB session.
+/// * `cache` - The prepared statement cache.
+/// * `consistency` - The consistency level of the queries.
+pub struct ScyllaStore {
+    pub session: Arc<Session>,
+    pub cache: Arc<PreparedStatementCache>,
+    pub consistency: Consistency,
+}
+
+impl ScyllaStore {
+    /// Creates a new `ScyllaStore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The ScyllaDB session.
+    /// * `cache` - The prepared statement cache.
+    /// * `consistency` - The consistency level of the queries.
+    ///
+    /// # Returns
+    ///
+    /// A `ScyllaStore`.
+    pub fn new(
+        session: Arc<Session>,
+        cache: Arc<PreparedStatementCache>,
+        consistency: Consistency,
+    ) -> Self {
+        ScyllaStore {
+            session,
+            cache,
+            consistency,
+        }
+    }
+
+    /// Prepares the query, or takes it from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The CQL query.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `QueryFactory`, otherwise a `String` with the error message.
+    async fn factory(&self, query: &str) -> Result<QueryFactory, String> {
+        QueryFactory::build_cached(
+            Arc::clone(&self.session),
+            &self.cache,
+            query,
+            self.consistency,
+        )
+        .await
+    }
+}
+
+impl NgramStore for ScyllaStore {
+    fn frequency<'a>(
+        &'a self,
+        query: &'a str,
+        n_gram: Vec<String>,
+    ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+        Box::pin(async move {
+            let factory = self.factory(query).await?;
+
+            let mut rows = match factory.execute_one(Arc::clone(&self.session), n_gram).await {
+                Ok(rows) => rows.into_typed::<(i32,)>(),
+                Err(err) => return Err(err.to_string()),
+            };
+
+            match rows.next().await {
+                Some(Ok((freq,))) => Ok(Some(freq)),
+                Some(Err(err)) => Err(format!("Can not read the frequency: {}", err)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn varying<'a>(
+        &'a self,
+        query: &'a VaryingQuery,
+    ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+        Box::pin(async move {
+            let factory = self.factory(&query.query).await?.with_page_size(PAGE_SIZE);
+
+            let params: Vec<&String> = query
+                .static_params
+                .iter()
+                .chain(query.candidates.iter())
+                .collect();
+
+            let rows = match factory.execute_one(Arc::clone(&self.session), params).await {
+                Ok(rows) => rows.into_typed::<(String, i32)>(),
+                Err(err) => return Err(err.to_string()),
+            };
+
+            collect_rows(rows, query.max_rows.unwrap_or(usize::MAX)).await
+        })
+    }
+}
+
+/// Reads the rows, at most `max_rows` of them.
+///
+/// # Arguments
+///
+/// * `rows` - The rows.
+/// * `max_rows` - The upper bound of the rows read.
+///
+/// # Returns
+///
+/// A `Result` containing the rows if they can be read, otherwise a `String` with the error message.
+async fn collect_rows<S, E>(rows: S, max_rows: usize) -> Result<Vec<(String, i32)>, String>
+where
+    S: Stream<Item = Result<(String, i32), E>>,
+    E: std::fmt::Display,
+{
+    let mut rows = std::pin::pin!(rows.take(max_rows));
+    let mut result = vec![];
+
+    while let Some(row) = rows.next().await {
+        match row {
+            Ok(row) => result.push(row),
+            Err(err) => return Err(format!("Can not read the rows: {}", err)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// The n-gram store that fails the queries of the inner store that take longer than the timeout.
+///
+/// A slow node would otherwise hold the worker of the request until the driver gives up.
+///
+/// # Fields
+///
+/// * `store` - The inner store.
+/// * `timeout` - The timeout of every query, the rows of a varying query are read within it.
+pub struct TimeoutStore {
+    pub store: Arc<dyn NgramStore>,
+    pub timeout: Duration,
+}
+
+impl TimeoutStore {
+    /// Creates a new `TimeoutStore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The inner store.
+    /// * `timeout` - The timeout of every query.
+    ///
+    /// # Returns
+    ///
+    /// A `TimeoutStore`.
+    pub fn new(store: Arc<dyn NgramStore>, timeout: Duration) -> Self {
+        TimeoutStore { store, timeout }
+    }
+}
+
+/// Runs the query within the timeout.
+///
+/// # Arguments
+///
+/// * `timeout` - The timeout.
+/// * `query` - The query.
+///
+/// # Returns
+///
+/// The result of the query, otherwise a `String` starting with `QUERY_TIMED_OUT` if it took longer than the timeout.
+async fn with_timeout<T>(
+    timeout: Duration,
+    query: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    match tokio::time::timeout(timeout, query).await {
+        Ok(result) => result,
+        Err(_) => Err(format!(
+            "{} after {} ms",
+            QUERY_TIMED_OUT,
+            timeout.as_millis()
+        )),
+    }
+}
+
+impl NgramStore for TimeoutStore {
+    fn frequency<'a>(
+        &'a self,
+        query: &'a str,
+        n_gram: Vec<String>,
+    ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+        Box::pin(with_timeout(
+            self.timeout,
+            self.store.frequency(query, n_gram),
+        ))
+    }
+
+    fn varying<'a>(
+        &'a self,
+        query: &'a VaryingQuery,
+    ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+        Box::pin(with_timeout(self.timeout, self.store.varying(query)))
+    }
+}
+
+/// Caches the values up to the capacity, evicting the least recently used one.
+///
+/// A value is only served for the time to live after it was inserted, so a changed store is
+/// eventually seen. The capacity bounds the total weight of the values, every value weighs one
+/// unless the cache is created `with_weight`.
+///
+/// # Fields
+///
+/// * `entries` - The cached values.
+/// * `capacity` - The upper bound of the total weight of the cached values.
+/// * `ttl` - How long a value is served after it was inserted.
+/// * `weigh` - Gets the weight of a value.
+///
+/// # Methods
+///
+/// * `new` - Creates an empty `LruCache`, every value weighs one.
+/// * `with_weight` - Creates an empty `LruCache` with the weight of the values.
+/// * `get` - Gets the value, if it is cached and fresh.
+/// * `insert` - Caches the value.
+/// * `len` - The number of cached values.
+/// * `weight` - The total weight of the cached values.
+pub struct LruCache<K, V> {
+    entries: Mutex<LruEntries<K, V>>,
+    capacity: usize,
+    ttl: Duration,
+    weigh: fn(&V) -> usize,
+}
+
+/// The cached values with the order of their use.
+///
+/// # Fields
+///
+/// * `values` - The value, the time it was inserted and its last use, by the key.
+/// * `recency` - The key by its last use, the least recently used first.
+/// * `tick` - The counter of the uses.
+/// * `weight` - The total weight of the values.
+struct LruEntries<K, V> {
+    values: HashMap<K, (V, Instant, u64)>,
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+    weight: usize,
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    /// Creates an empty `LruCache`, every value weighs one.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The upper bound of the cached values.
+    /// * `ttl` - How long a value is served after it was inserted.
+    ///
+    /// # Returns
+    ///
+    /// An `LruCache`.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self::with_weight(capacity, ttl, |_| 1)
+    }
+
+    /// Creates an empty `LruCache` with the weight of the values.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The upper bound of the total weight of the cached values.
+    /// * `ttl` - How long a value is served after it was inserted.
+    /// * `weigh` - Gets the weight of a value, e.g. its number of rows.
+    ///
+    /// # Returns
+    ///
+    /// An `LruCache`.
+    pub fn with_weight(capacity: usize, ttl: Duration, weigh: fn(&V) -> usize) -> Self {
+        LruCache {
+            entries: Mutex::new(LruEntries {
+                values: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+                weight: 0,
+            }),
+            capacity,
+            ttl,
+            weigh,
+        }
+    }
+
+    /// Locks the entries.
+    ///
+    /// # Returns
+    ///
+    /// The guard of the entries.
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruEntries<K, V>> {
+        // the entries are consistent after every statement, a poisoned lock still holds valid data
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Gets the value, if it is cached and fresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key.
+    ///
+    /// # Returns
+    ///
+    /// The value, `None` if it is not cached or it is older than the time to live.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.lock();
+        let entries = &mut *entries;
+
+        let (value, inserted, last_used) = entries.values.get_mut(key)?;
+
+        if inserted.elapsed() >= self.ttl {
+            entries.recency.remove(last_used);
+            if let Some((value, _, _)) = entries.values.remove(key) {
+                entries.weight -= (self.weigh)(&value);
+            }
+            return None;
+        }
+
+        entries.tick += 1;
+        entries.recency.remove(last_used);
+        entries.recency.insert(entries.tick, key.clone());
+        *last_used = entries.tick;
+
+        Some(value.clone())
+    }
+
+    /// Caches the value, evicting the least recently used ones until it fits.
+    ///
+    /// A value heavier than the capacity is not cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key.
+    /// * `value` - The value.
+    pub fn insert(&self, key: K, value: V) {
+        let weight = (self.weigh)(&value);
+        let mut entries = self.lock();
+        let entries = &mut *entries;
+
+        if let Some((replaced, _, last_used)) = entries.values.remove(&key) {
+            entries.recency.remove(&last_used);
+            entries.weight -= (self.weigh)(&replaced);
+        }
+
+        if weight > self.capacity {
+            return;
+        }
+
+        while entries.weight + weight > self.capacity {
+            match entries.recency.pop_first() {
+                Some((_, evicted)) => {
+                    if let Some((evicted, _, _)) = entries.values.remove(&evicted) {
+                        entries.weight -= (self.weigh)(&evicted);
+                    }
+                }
+                None => break,
+            };
+        }
+
+        entries.weight += weight;
+        entries.tick += 1;
+        entries.recency.insert(entries.tick, key.clone());
+        entries
+            .values
+            .insert(key, (value, Instant::now(), entries.tick));
+    }
+
+    /// The number of cached values, the stale ones included until they are looked up or evicted.
+    ///
+    /// # Returns
+    ///
+    /// The number of cached values.
+    pub fn len(&self) -> usize {
+        self.lock().values.len()
+    }
+
+    /// The total weight of the cached values, the stale ones included.
+    ///
+    /// # Returns
+    ///
+    /// The total weight of the cached values.
+    pub fn weight(&self) -> usize {
+        self.lock().weight
+    }
+
+    /// Whether no values are cached.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The n-gram store that caches the successful lookups of the inner store.
+///
+/// The frequent words are queried again and again with the same parameters, e.g. by
+/// `VaryingQueryResult::get_one` and `WordFreqPair::from`. The failed lookups are not cached.
+/// The rows are shared with the cache, so a hit holds the lock only to take a reference.
+///
+/// # Fields
+///
+/// * `store` - The inner store.
+/// * `frequencies` - The frequencies, by the query and the words of the n-gram.
+/// * `varying` - The rows of the varying queries, weighed by their number of rows.
+pub struct CachingStore {
+    pub store: Arc<dyn NgramStore>,
+    pub frequencies: LruCache<(String, Vec<String>), Option<i32>>,
+    pub varying: LruCache<VaryingQuery, Arc<Vec<(String, i32)>>>,
+}
+
+impl CachingStore {
+    /// Creates a new `CachingStore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The inner store.
+    /// * `capacity` - The upper bound of the cached frequencies, and of the rows of the cached varying queries.
+    /// * `ttl` - How long a lookup is served from the cache.
+    ///
+    /// # Returns
+    ///
+    /// A `CachingStore`.
+    pub fn new(store: Arc<dyn NgramStore>, capacity: usize, ttl: Duration) -> Self {
+        CachingStore {
+            store,
+            frequencies: LruCache::new(capacity, ttl),
+            varying: LruCache::with_weight(capacity, ttl, |rows| rows.len().max(1)),
+        }
+    }
+}
+
+impl NgramStore for CachingStore {
+    fn frequency<'a>(
+        &'a self,
+        query: &'a str,
+        n_gram: Vec<String>,
+    ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+        Box::pin(async move {
+            let key = (query.to_string(), n_gram);
+
+            if let Some(frequency) = self.frequencies.get(&key) {
+                return Ok(frequency);
+            }
+
+            let frequency = self.store.frequency(query, key.1.clone()).await?;
+            self.frequencies.insert(key, frequency);
+
+            Ok(frequency)
+        })
+    }
+
+    fn varying<'a>(
+        &'a self,
+        query: &'a VaryingQuery,
+    ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+        Box::pin(async move {
+            if let Some(rows) = self.varying.get(query) {
+                return Ok(Vec::clone(&rows));
+            }
+
+            let rows = self.store.varying(query).await?;
+            self.varying.insert(query.clone(), Arc::new(rows.clone()));
+
+            Ok(rows)
+        })
+    }
+}
+
+/// The n-gram store backed by a `HashMap`, for tests and recorded data.
+///
+/// # Fields
+///
+/// * `counts` - The frequencies, keyed by the words of the n-gram joined by spaces.
+#[derive(Default)]
+pub struct InMemoryStore {
+    pub counts: HashMap<String, i32>,
+}
+
+impl InMemoryStore {
+    /// Creates a new `InMemoryStore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `counts` - The frequencies, keyed by the words of the n-gram joined by spaces.
+    ///
+    /// # Returns
+    ///
+    /// An `InMemoryStore`.
+    pub fn new(counts: HashMap<String, i32>) -> Self {
+        InMemoryStore { counts }
+    }
+}
+
+impl NgramStore for InMemoryStore {
+    fn frequency<'a>(
+        &'a self,
+        _query: &'a str,
+        n_gram: Vec<String>,
+    ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+        Box::pin(async move { Ok(self.counts.get(&n_gram.join(" ")).copied()) })
+    }
+
+    fn varying<'a>(
+        &'a self,
+        query: &'a VaryingQuery,
+    ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+        Box::pin(async move {
+            let mut rows: Vec<(String, i32)> = self
+                .counts
+                .iter()
+                .filter_map(|(n_gram, freq)| {
+                    let mut words: Vec<&str> = n_gram.split(' ').collect();
+                    let skipped = usize::from(query.skipped.is_some());
+                    if words.len() != query.static_params.len() + 1 + skipped {
+                        return None;
+                    }
+                    let word = words.remove(query.index);
+                    if let Some(skipped) = query.skipped {
+                        // the skipped word matches any word
+                        words.remove(skipped - usize::from(skipped > query.index));
+                    }
+                    let matches = words.iter().eq(query.static_params.iter())
+                        && (query.candidates.is_empty()
+                            || query.candidates.iter().any(|candidate| candidate == word));
+                    matches.then(|| (word.to_string(), *freq))
+                })
+                .collect();
+
+            // ScyllaDB returns the rows clustered by the word
+            rows.sort();
+            rows.truncate(query.max_rows.unwrap_or(usize::MAX));
+
+            Ok(rows)
+        })
+    }
+}
+
+/// Represents the shape of a solver query, which word of the n-gram varies.
+///
+/// # Variants
+///
+/// * `ThreeVary3` - The third word of a trigram varies, `GET_ALL_VARYING_3_3`.
+/// * `ThreeVary1` - The first word of a trigram varies, `GET_ALL_VARYING_3_1`.
+/// * `TwoVary2` - The second word of a bigram varies, `GET_ALL_VARYING_2_2`.
+/// * `TwoVary1` - The first word of a bigram varies, `GET_ALL_VARYING_2_1`.
+/// * `OneVary` - The unigram varies, `GET_ALL_VARYING_1`.
+/// * `SkipVary3` - The third word of a trigram varies and the second is skipped, `GET_ALL_SKIP_VARYING_3_3`.
+/// * `SkipVary1` - The first word of a trigram varies and the second is skipped, `GET_ALL_SKIP_VARYING_3_1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QueryShape {
+    ThreeVary3,
+    ThreeVary1,
+    TwoVary2,
+    TwoVary1,
+    OneVary,
+    SkipVary3,
+    SkipVary1,
+}
+
+/// The word that stands for the skipped word in the n-gram strings of the skip-grams.
+pub static SKIP_MARKER: &str = "*";
+
+impl QueryShape {
+    /// Gets the query of the shape, without the `IN` markers of the varying words.
+    ///
+    /// # Returns
+    ///
+    /// The query.
+    pub fn query(&self) -> &'static str {
+        match self {
+            QueryShape::ThreeVary3 => GET_ALL_VARYING_3_3,
+            QueryShape::ThreeVary1 => GET_ALL_VARYING_3_1,
+            QueryShape::TwoVary2 => GET_ALL_VARYING_2_2,
+            QueryShape::TwoVary1 => GET_ALL_VARYING_2_1,
+            QueryShape::OneVary => GET_ALL_VARYING_1,
+            QueryShape::SkipVary3 => GET_ALL_SKIP_VARYING_3_3,
+            QueryShape::SkipVary1 => GET_ALL_SKIP_VARYING_3_1,
+        }
+    }
+
+    /// Gets the order of the n-gram.
+    ///
+    /// The skip-grams are read from the trigrams, so they have the order of the trigrams.
+    ///
+    /// # Returns
+    ///
+    /// The number of words of the n-gram, the skipped one included.
+    pub fn order(&self) -> usize {
+        match self {
+            QueryShape::ThreeVary3
+            | QueryShape::ThreeVary1
+            | QueryShape::SkipVary3
+            | QueryShape::SkipVary1 => 3,
+            QueryShape::TwoVary2 | QueryShape::TwoVary1 => 2,
+            QueryShape::OneVary => 1,
+        }
+    }
+
+    /// Gets the position of the varying word in the n-gram.
+    ///
+    /// # Returns
+    ///
+    /// The zero based index of the varying word.
+    pub fn varying_index(&self) -> usize {
+        match self {
+            QueryShape::ThreeVary3 | QueryShape::SkipVary3 => 2,
+            QueryShape::TwoVary2 => 1,
+            QueryShape::ThreeVary1
+            | QueryShape::TwoVary1
+            | QueryShape::OneVary
+            | QueryShape::SkipVary1 => 0,
+        }
+    }
+
+    /// Gets the position of the skipped word in the n-gram.
+    ///
+    /// # Returns
+    ///
+    /// The zero based index of the skipped word, `None` for the contiguous n-grams.
+    pub fn skipped_index(&self) -> Option<usize> {
+        match self {
+            QueryShape::SkipVary3 | QueryShape::SkipVary1 => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Gets the n-gram string.
+///
+/// # Arguments
+///
+/// * `shape` - The shape of the query.
+/// * `static_params` - The static parameters.
+/// * `varying_param` - The varying parameter.
+///
+/// # Returns
+///
+/// The n-gram string, the varying parameter placed among the static ones as given by the shape,
+/// and `SKIP_MARKER` in place of the skipped word of the skip-grams.
+pub fn get_n_gram_string(shape: QueryShape, static_params: &[&str], varying_param: &str) -> String {
+    let mut words = static_params.to_vec();
+    words.insert(shape.varying_index().min(words.len()), varying_param);
+    if let Some(skipped) = shape.skipped_index() {
+        words.insert(skipped.min(words.len()), SKIP_MARKER);
+    }
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_collect_rows_stops_at_max_rows() {
+        let rows = futures::stream::iter(vec![
+            Ok::<(String, i32), &str>(("a".to_string(), 1)),
+            Ok(("b".to_string(), 3)),
+            Ok(("c".to_string(), 2)),
+            Ok(("d".to_string(), 10)),
+        ]);
+
+        let result = collect_rows(rows, 3).await.unwrap();
+
+        let words: Vec<&str> = result.iter().map(|(word, _)| word.as_str()).collect();
+        assert_eq!(words, vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_rows_keeps_the_error() {
+        let rows = futures::stream::iter(vec![Ok(("a".to_string(), 1)), Err("Connection reset")]);
+
+        assert_eq!(
+            collect_rows(rows, 10).await,
+            Err("Can not read the rows: Connection reset".to_string())
+        );
+    }
+
+    #[test]
+    fn test_query_error_display() {
+        let error = QueryError::ScyllaError("Keyspace 'n_grams' does not exist".to_string());
+
+        assert_eq!(
+            error.to_string(),
+            "Can not execute query: Keyspace 'n_grams' does not exist"
+        );
+        assert_eq!(QueryError::NotFound.to_string(), "No results found");
+    }
+
+    /// Answers from the inner store after the delay, like a slow node.
+    struct SlowStore {
+        store: InMemoryStore,
+        delay: Duration,
+    }
+
+    impl NgramStore for SlowStore {
+        fn frequency<'a>(
+            &'a self,
+            query: &'a str,
+            n_gram: Vec<String>,
+        ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.delay).await;
+                self.store.frequency(query, n_gram).await
+            })
+        }
+
+        fn varying<'a>(
+            &'a self,
+            query: &'a VaryingQuery,
+        ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.delay).await;
+                self.store.varying(query).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_store() {
+        let slow: Arc<dyn NgramStore> = Arc::new(SlowStore {
+            store: InMemoryStore::new(HashMap::from([("mene zahtjeva".to_string(), 87)])),
+            delay: Duration::from_millis(200),
+        });
+        let n_gram = vec!["mene".to_string(), "zahtjeva".to_string()];
+        let query = VaryingQuery {
+            query: GET_BY_FIRST_2.to_string(),
+            static_params: vec!["mene".to_string()],
+            index: 1,
+            candidates: vec![],
+            max_rows: None,
+            skipped: None,
+        };
+
+        let store = TimeoutStore::new(Arc::clone(&slow), Duration::from_millis(20));
+
+        assert_eq!(
+            store.frequency(GET_FREQ_2, n_gram.clone()).await,
+            Err("Query timed out after 20 ms".to_string())
+        );
+        assert!(store
+            .varying(&query)
+            .await
+            .unwrap_err()
+            .starts_with(QUERY_TIMED_OUT));
+
+        let store = TimeoutStore::new(slow, Duration::from_secs(5));
+
+        assert_eq!(store.frequency(GET_FREQ_2, n_gram).await, Ok(Some(87)));
+        assert_eq!(
+            store.varying(&query).await,
+            Ok(vec![("zahtjeva".to_string(), 87)])
+        );
+    }
+
+    /// Counts the lookups that reach the inner store.
+    struct CountingStore {
+        store: InMemoryStore,
+        lookups: AtomicUsize,
+    }
+
+    impl NgramStore for CountingStore {
+        fn frequency<'a>(
+            &'a self,
+            query: &'a str,
+            n_gram: Vec<String>,
+        ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            match n_gram.first().map(String::as_str) {
+                Some("greška") => Box::pin(async { Err("Connection refused".to_string()) }),
+                _ => self.store.frequency(query, n_gram),
+            }
+        }
+
+        fn varying<'a>(
+            &'a self,
+            query: &'a VaryingQuery,
+        ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            self.store.varying(query)
+        }
+    }
+
+    #[test]
+    fn test_lru_cache() {
+        let cache = LruCache::new(2, Duration::from_secs(60));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        // b is the least recently used
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+
+        cache.insert("a", 4);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), Some(4));
+
+        let stale = LruCache::new(2, Duration::ZERO);
+        stale.insert("a", 1);
+
+        assert_eq!(stale.get(&"a"), None);
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn test_lru_cache_weight() {
+        let cache: LruCache<&str, Vec<i32>> =
+            LruCache::with_weight(5, Duration::from_secs(60), |rows| rows.len());
+
+        cache.insert("a", vec![1, 2]);
+        cache.insert("b", vec![1, 2]);
+        assert_eq!(cache.weight(), 4);
+
+        // a is the least recently used, and evicted to make room for c
+        cache.insert("c", vec![1, 2]);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.get(&"a"), None);
+
+        // heavier than the capacity, so it is not cached
+        cache.insert("d", vec![1; 6]);
+
+        assert_eq!(cache.get(&"d"), None);
+        assert_eq!(cache.weight(), 4);
+
+        cache.insert("b", vec![1]);
+
+        assert_eq!(cache.weight(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_store() {
+        let counting = Arc::new(CountingStore {
+            store: InMemoryStore::new(HashMap::from([
+                ("mene zahtijeva".to_string(), 1204),
+                ("mene zahtjeva".to_string(), 87),
+            ])),
+            lookups: AtomicUsize::new(0),
+        });
+        let store = CachingStore::new(
+            Arc::clone(&counting) as Arc<dyn NgramStore>,
+            10,
+            Duration::from_secs(60),
+        );
+        let n_gram = vec!["mene".to_string(), "zahtjeva".to_string()];
+        let query = VaryingQuery {
+            query: GET_BY_FIRST_2.to_string(),
+            static_params: vec!["mene".to_string()],
+            index: 1,
+            candidates: vec![],
+            max_rows: None,
+            skipped: None,
+        };
+
+        for _ in 0..2 {
+            assert_eq!(
+                store.frequency(GET_FREQ_2, n_gram.clone()).await,
+                Ok(Some(87))
+            );
+            assert_eq!(store.varying(&query).await.unwrap().len(), 2);
+        }
+
+        assert_eq!(counting.lookups.load(Ordering::SeqCst), 2);
+
+        let other = vec!["mene".to_string(), "zahtijeva".to_string()];
+        assert_eq!(store.frequency(GET_FREQ_2, other).await, Ok(Some(1204)));
+        assert_eq!(counting.lookups.load(Ordering::SeqCst), 3);
+
+        // the failed lookups are not cached
+        let failing = vec!["greška".to_string(), "zahtjeva".to_string()];
+        for _ in 0..2 {
+            assert!(store.frequency(GET_FREQ_2, failing.clone()).await.is_err());
+        }
+        assert_eq!(counting.lookups.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("mene zahtijeva".to_string(), 1204),
+            ("mene zahtjeva".to_string(), 87),
+            ("mene voli".to_string(), 5000),
+            ("tebe zahtijeva".to_string(), 10),
+        ]));
+
+        assert_eq!(
+            store
+                .frequency(GET_FREQ_2, vec!["mene".to_string(), "zahtjeva".to_string()])
+                .await,
+            Ok(Some(87))
+        );
+        assert_eq!(
+            store
+                .frequency(GET_FREQ_2, vec!["tebe".to_string(), "voli".to_string()])
+                .await,
+            Ok(None)
+        );
+
+        let mut query = VaryingQuery {
+            query: GET_BY_FIRST_2.to_string(),
+            static_params: vec!["mene".to_string()],
+            index: 1,
+            candidates: vec![],
+            max_rows: None,
+            skipped: None,
+        };
+
+        assert_eq!(
+            store.varying(&query).await.unwrap(),
+            vec![
+                ("voli".to_string(), 5000),
+                ("zahtijeva".to_string(), 1204),
+                ("zahtjeva".to_string(), 87),
+            ]
+        );
+
+        query.candidates = vec!["zahtijeva".to_string(), "zahtjeva".to_string()];
+        query.max_rows = Some(1);
+
+        assert_eq!(
+            store.varying(&query).await.unwrap(),
+            vec![("zahtijeva".to_string(), 1204)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff_succeeds() {
+        let attempts = AtomicUsize::new(0);
+
+        let result = retry_with_backoff(
+            || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err("unreachable"),
+                    _ => Ok("connected"),
+                }
+            },
+            Duration::from_secs(1),
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert_eq!(result, Ok("connected"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff_gives_up() {
+        let attempts = AtomicUsize::new(0);
+
+        let result: Result<(), &str> = retry_with_backoff(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err("unreachable")
+            },
+            Duration::ZERO,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert_eq!(result, Err("unreachable"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_parse_nodes() {
+        assert_eq!(
+            parse_nodes("10.0.0.1:9042, 10.0.0.2:9042,,10.0.0.3:9042 ").unwrap(),
+            vec!["10.0.0.1:9042", "10.0.0.2:9042", "10.0.0.3:9042"]
+        );
+        assert_eq!(
+            parse_nodes("127.0.0.1:9042").unwrap(),
+            vec!["127.0.0.1:9042"]
+        );
+        assert!(parse_nodes(" , ").is_err());
+    }
+
+    #[test]
+    fn test_connection_error() {
+        let nodes = vec!["10.0.0.1:9042".to_string(), "10.0.0.2:9042".to_string()];
+        let errors = vec![
+            "Connection refused".to_string(),
+            "Timeout".to_string(),
+            "Connection refused".to_string(),
+        ];
+
+        assert_eq!(
+            connection_error(&nodes, &errors),
+            "Failed to connect to ScyllaDB at 10.0.0.1:9042, 10.0.0.2:9042 after 3 attempts: \
+             Connection refused; Timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_statement_cache_prepares_once() {
+        let cache: StatementCache<String> = StatementCache::new();
+        let prepares = AtomicUsize::new(0);
+
+        for _ in 0..3 {
+            let statement = cache
+                .get_or_prepare(GET_FREQ_2, || async {
+                    prepares.fetch_add(1, Ordering::SeqCst);
+                    Ok::<String, &str>(GET_FREQ_2.to_string())
+                })
+                .await;
+
+            assert_eq!(statement.unwrap().as_str(), GET_FREQ_2);
+        }
+
+        assert_eq!(prepares.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_statement_cache_does_not_cache_errors() {
+        let cache: StatementCache<String> = StatementCache::new();
+
+        let statement = cache
+            .get_or_prepare(GET_FREQ_2, || async { Err::<String, &str>("failed") })
+            .await;
+
+        assert!(statement.is_err());
+        assert!(cache.is_empty().await);
+    }
+
+    #[test]
+    fn test_get_n_gram_string() {
+        let static_params = vec!["od", "mene"];
+
+        assert_eq!(
+            get_n_gram_string(QueryShape::ThreeVary3, &static_params, "zahtjeva"),
+            "od mene zahtjeva"
+        );
+        assert_eq!(
+            get_n_gram_string(QueryShape::ThreeVary1, &static_params, "zahtjeva"),
+            "zahtjeva od mene"
+        );
+        assert_eq!(
+            get_n_gram_string(QueryShape::TwoVary2, &["mene"], "zahtjeva"),
+            "mene zahtjeva"
+        );
+        assert_eq!(
+            get_n_gram_string(QueryShape::TwoVary1, &["da"], "zahtjeva"),
+            "zahtjeva da"
+        );
+        assert_eq!(
+            get_n_gram_string(QueryShape::OneVary, &[], "zahtjeva"),
+            "zahtjeva"
+        );
+    }
+
+    #[test]
+    fn test_parse_consistency() {
+        assert_eq!(parse_consistency("ONE"), Ok(Consistency::One));
+        assert_eq!(
+            parse_consistency("local_quorum"),
+            Ok(Consistency::LocalQuorum)
+        );
+        assert!(parse_consistency("MOST").is_err());
+    }
+
+    #[test]
+    fn test_extract_table_name() {
+        assert_eq!(
+            extract_table_name(GET_FREQ_3),
+            Some(("n_grams", "three_grams_1_2_pk"))
+        );
+        assert_eq!(
+            extract_table_name(GET_ALL_VARYING_2_1),
+            Some(("n_grams", "two_grams_2_pk"))
+        );
+        assert_eq!(
+            extract_table_name(GET_ALL_VARYING_1),
+            Some(("n_grams", "one_grams"))
+        );
+    }
+
+    #[test]
+    fn test_extract_table_name_from_all_queries() {
+        for query in ALL_QUERIES {
+            assert!(extract_table_name(query).is_some(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_extract_table_name_without_keyspace() {
+        assert_eq!(extract_table_name("SELECT * FROM one_grams"), None);
+    }
+
+    #[test]
+    fn test_with_keyspace() {
+        assert_eq!(
+            with_keyspace(GET_FREQ_2, "corpus_hr"),
+            "SELECT freq FROM corpus_hr.two_grams_1_pk WHERE word_1 = ? AND word_2 = ?"
+        );
+        assert_eq!(with_keyspace(GET_FREQ_2, DEFAULT_KEYSPACE), GET_FREQ_2);
+
+        for query in ALL_QUERIES {
+            let moved = with_keyspace(query, "corpus_hr");
+            let (keyspace, table) = extract_table_name(&moved).unwrap();
+
+            assert_eq!(keyspace, "corpus_hr");
+            assert_eq!(Some(("n_grams", table)), extract_table_name(query));
+        }
+    }
+
+    #[test]
+    fn test_keyspace_moves_every_query() {
+        let keyspace = Keyspace::new("corpus_hr".to_string());
+
+        assert_eq!(keyspace.queries.len(), ALL_QUERIES.len());
+        assert_eq!(
+            keyspace.queries[GET_FREQ_2],
+            "SELECT freq FROM corpus_hr.two_grams_1_pk WHERE word_1 = ? AND word_2 = ?"
+        );
+    }
+
+    #[test]
+    fn test_parse_keyspace() {
+        assert_eq!(parse_keyspace("corpus_hr"), Ok("corpus_hr".to_string()));
+        assert!(parse_keyspace("").is_err());
+        assert!(parse_keyspace("1grams").is_err());
+        assert!(parse_keyspace("n_grams.one_grams").is_err());
+        assert!(parse_keyspace("n_grams; DROP").is_err());
     }
 }
@@ -1,3 +1,4 @@
+use crate::db::{QueryError, QUERY_TIMED_OUT};
 use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -32,6 +33,56 @@ impl HttpError {
             error_message,
         }
     }
+
+    /// Creates an error from the message of a failed n-gram query.
+    ///
+    /// # Arguments
+    ///
+    /// * `error_message` - The message of the error.
+    ///
+    /// # Returns
+    ///
+    /// A new `HttpError` with the status code 504 if the query timed out, see `TimeoutStore`,
+    /// otherwise 400 as the validation messages.
+    pub fn from_query(error_message: String) -> HttpError {
+        match error_message.contains(QUERY_TIMED_OUT) {
+            true => HttpError::new(StatusCode::GATEWAY_TIMEOUT.as_u16(), error_message),
+            false => HttpError::from(error_message),
+        }
+    }
+}
+
+impl From<String> for HttpError {
+    /// Creates a `Bad Request` error from a validation message.
+    ///
+    /// # Arguments
+    ///
+    /// * `error_message` - The message of the error.
+    ///
+    /// # Returns
+    ///
+    /// A new `HttpError` with the status code 400.
+    fn from(error_message: String) -> HttpError {
+        HttpError::new(StatusCode::BAD_REQUEST.as_u16(), error_message)
+    }
+}
+
+impl From<QueryError> for HttpError {
+    /// Creates an `Internal Server Error` from a failed query.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The query error.
+    ///
+    /// # Returns
+    ///
+    /// A new `HttpError` with the status code 500.
+    fn from(error: QueryError) -> HttpError {
+        HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            error.to_string(),
+        )
+    }
 }
 
 impl fmt::Display for HttpError {
@@ -58,7 +109,9 @@ impl ResponseError for HttpError {
     ///
     /// # Returns
     ///
-    /// An `HttpResponse` with the error message.
+    /// An `HttpResponse` with the error message, as a bare JSON string for the client errors.
+    /// The server errors hide their message behind `Internal server error`, the message is only logged,
+    /// except for the `Gateway Timeout` whose message tells the client which query timed out.
     /// If the status code is not valid, a `HttpResponse` with the status code `INTERNAL_SERVER_ERROR` will be returned.
     fn error_response(&self) -> HttpResponse {
         let status_code = match StatusCode::from_u16(self.error_status_code) {
@@ -66,11 +119,64 @@ impl ResponseError for HttpError {
             Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
-        let error_message = match status_code.as_u16() < 500 {
-            true => self.error_message.clone(),
-            false => "Internal server error".to_string(),
-        };
+        if status_code.as_u16() < 500 {
+            return HttpResponse::build(status_code).json(&self.error_message);
+        }
+
+        tracing::error!(status = status_code.as_u16(), error = %self.error_message, "request failed");
+
+        if status_code == StatusCode::GATEWAY_TIMEOUT {
+            return HttpResponse::build(status_code).json(json!({ "message": self.error_message }));
+        }
+
+        HttpResponse::build(status_code).json(json!({ "message": "Internal server error" }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[tokio::test]
+    async fn test_validation_error_is_bad_request() {
+        let error = HttpError::from("Invalid amount".to_string());
+        let response = error.error_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+
+        assert_eq!(body, "\"Invalid amount\"");
+    }
+
+    #[tokio::test]
+    async fn test_query_error_is_internal_server_error() {
+        let error = HttpError::from(QueryError::ScyllaError("timeout".to_string()));
+
+        assert_eq!(error.error_message, "Can not execute query: timeout");
+
+        let response = error.error_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+
+        assert_eq!(body, r#"{"message":"Internal server error"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout_is_gateway_timeout() {
+        let error = HttpError::from_query("Query timed out after 50 ms".to_string());
+
+        assert_eq!(error.error_status_code, 504);
+        assert_eq!(
+            HttpError::from_query("Invalid n-gram".to_string()).error_status_code,
+            400
+        );
+
+        let body = to_bytes(error.error_response().into_body()).await.unwrap();
 
-        HttpResponse::build(status_code).json(json!({ "message": error_message }))
+        assert_eq!(body, r#"{"message":"Query timed out after 50 ms"}"#);
     }
 }
@@ -0,0 +1,205 @@
+use crate::{db::ping, AppData};
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The orders of n-grams the predictors need counts for.
+pub static EXPECTED_ORDERS: [i32; 3] = [1, 2, 3];
+
+/// Represents the health status of the application.
+///
+/// # Variants
+///
+/// * `Healthy` - Everything is working.
+/// * `Degraded` - The application works, but some results may be off.
+/// * `Unhealthy` - The application can not serve requests.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+/// Represents the health report.
+///
+/// # Fields
+///
+/// * `status` - The overall status, the worst of all the checks.
+/// * `problems` - The problems that were found.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub problems: Vec<String>,
+}
+
+impl HealthReport {
+    /// Creates a new healthy `HealthReport`.
+    ///
+    /// # Returns
+    ///
+    /// A `HealthReport` without problems.
+    pub fn new() -> HealthReport {
+        HealthReport {
+            status: HealthStatus::Healthy,
+            problems: vec![],
+        }
+    }
+
+    /// Records a problem, lowering the status if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status caused by the problem.
+    /// * `problem` - The description of the problem.
+    pub fn report(&mut self, status: HealthStatus, problem: String) {
+        if status == HealthStatus::Unhealthy || self.status == HealthStatus::Healthy {
+            self.status = status;
+        }
+        self.problems.push(problem);
+    }
+}
+
+impl Default for HealthReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks that the loaded data is complete.
+///
+/// An empty confusion set makes the application unhealthy, missing counts make it degraded.
+///
+/// # Arguments
+///
+/// * `report` - The report to record the problems into.
+/// * `confusion_set` - The confusion set.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+pub fn check_data(
+    report: &mut HealthReport,
+    confusion_set: &[Vec<String>],
+    number_of_ngrams: &HashMap<i32, i64>,
+    number_of_distinct_ngrams: &HashMap<i32, i64>,
+) {
+    if confusion_set.is_empty() {
+        report.report(
+            HealthStatus::Unhealthy,
+            "The confusion set is empty".to_string(),
+        );
+    }
+
+    for n in EXPECTED_ORDERS {
+        if !number_of_ngrams.contains_key(&n) {
+            report.report(
+                HealthStatus::Degraded,
+                format!("Missing the number of {}-grams", n),
+            );
+        }
+        if !number_of_distinct_ngrams.contains_key(&n) {
+            report.report(
+                HealthStatus::Degraded,
+                format!("Missing the number of distinct {}-grams", n),
+            );
+        }
+    }
+}
+
+/// Handles the health check.
+///
+/// With `?deep=true` the loaded confusion set and counts are checked as well.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The report, with status 503 if the application is unhealthy.
+#[get("/health")]
+async fn health(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppData>,
+) -> HttpResponse {
+    let mut report = HealthReport::new();
+
+    if let Err(err) = ping(data.scy_session.clone()).await {
+        report.report(HealthStatus::Unhealthy, err);
+    }
+
+    if query
+        .get("deep")
+        .map(|deep| deep == "true")
+        .unwrap_or(false)
+    {
+        let loaded = data.loaded();
+
+        check_data(
+            &mut report,
+            &loaded.confusion_set,
+            &loaded.number_of_ngrams,
+            &data.number_of_distinct_ngrams,
+        );
+    }
+
+    match report.status {
+        HealthStatus::Unhealthy => HttpResponse::ServiceUnavailable().json(report),
+        _ => HttpResponse::Ok().json(report),
+    }
+}
+
+/// Initializes the health routes.
+///
+/// # Arguments
+///
+/// * `cfg` - The service configuration.
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(health);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts() -> HashMap<i32, i64> {
+        HashMap::from([(1, 1000), (2, 800), (3, 600)])
+    }
+
+    #[test]
+    fn test_check_data_healthy() {
+        let mut report = HealthReport::new();
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+
+        check_data(&mut report, &confusion_set, &counts(), &counts());
+
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert!(report.problems.is_empty());
+    }
+
+    #[test]
+    fn test_check_data_empty_confusion_set() {
+        let mut report = HealthReport::new();
+
+        check_data(&mut report, &[], &counts(), &HashMap::new());
+
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert_eq!(report.problems.len(), 4);
+    }
+
+    #[test]
+    fn test_check_data_missing_counts() {
+        let mut report = HealthReport::new();
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let mut distinct = counts();
+        distinct.remove(&3);
+
+        check_data(&mut report, &confusion_set, &counts(), &distinct);
+
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(
+            report.problems,
+            vec!["Missing the number of distinct 3-grams".to_string()]
+        );
+    }
+}
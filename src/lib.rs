@@ -1,4 +1,14 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    sync::{Arc, RwLock},
+};
+
+/// This module contains the administration endpoints.
+pub mod admin;
+
+/// This module contains the effective configuration endpoint.
+pub mod config;
 
 /// This module contains functions that handle the database operations.
 pub mod db;
@@ -6,12 +16,27 @@ pub mod db;
 /// This module contains the error handler.
 pub mod error_handler;
 
+/// This module contains the health check.
+pub mod health;
+
+/// This module contains the metrics of the application.
+pub mod metrics;
+
 /// This module contains the n-grams of the application.
 ///
 /// # Modules
 ///
-/// * `three_grams` - Contains the three-grams.
+/// * `one_grams` - Contains the one-grams.
 /// * `two_grams` - Contains the two-grams.
+/// * `three_grams` - Contains the three-grams.
+/// * `four_grams` - Contains the four-grams.
+/// * `five_grams` - Contains the five-grams.
+/// * `model` - Contains the query parameters and the results of the n-gram queries.
+/// * `normalizer` - Contains the normalizers of the queried words.
+/// * `routers` - Contains the routes of the n-grams.
+/// * `solver` - Contains the solver of the text check.
+/// * `vary_n_gram` - Contains the queries that vary the words of an n-gram.
+/// * `word_freq_pair` - Contains the word frequency pairs.
 pub mod n_grams;
 
 /// Represents the application data.
@@ -19,16 +44,144 @@ pub mod n_grams;
 /// # Fields
 ///
 /// * `scy_session` - The ScyllaDB session.
-/// * `confusion_set` - The confusion set.
-/// * `number_of_ngrams` - The number of n-grams.
+/// * `loaded` - The confusion set and the n-gram counts read from the files, swapped on reload.
 /// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+/// * `normalizer` - Normalizes the words of the n-gram queries.
+/// * `store` - The n-gram store the solver's queries run against.
+/// * `lookup_store` - The store the /n-gram lookups run against, with the lookup cache in front of it.
+/// * `consistency` - The consistency level of the queries.
 ///
 /// This struct is used to store the application data.
 pub struct AppData {
     pub scy_session: Arc<scylla::Session>,
+    pub loaded: RwLock<Arc<LoadedData>>,
+    pub number_of_distinct_ngrams: HashMap<i32, i64>,
+    pub normalizer: Arc<dyn n_grams::normalizer::Normalizer>,
+    pub store: Arc<dyn db::NgramStore>,
+    pub lookup_store: Arc<dyn db::NgramStore>,
+    pub consistency: scylla::statement::Consistency,
+}
+
+impl AppData {
+    /// Gets the currently loaded confusion set and n-gram counts.
+    ///
+    /// The handlers keep the returned snapshot for the whole request, so a reload in the middle
+    /// of a request does not mix the old and the new data.
+    ///
+    /// # Returns
+    ///
+    /// The `LoadedData` snapshot.
+    pub fn loaded(&self) -> Arc<LoadedData> {
+        // the lock is only held to clone or swap the Arc, a poisoned one still holds valid data
+        Arc::clone(&self.loaded.read().unwrap_or_else(|err| err.into_inner()))
+    }
+
+    /// Swaps in newly loaded data.
+    ///
+    /// # Arguments
+    ///
+    /// * `loaded` - The new confusion set and n-gram counts.
+    pub fn reload(&self, loaded: LoadedData) {
+        *self.loaded.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(loaded);
+    }
+}
+
+/// Represents the data read from `CONFUSION_SET_FILE` and `NUMBER_OF_NGRAMS_FILE`.
+///
+/// # Fields
+///
+/// * `confusion_set` - The confusion set.
+/// * `group_labels` - The labels of the confusion groups, in the order of the confusion set.
+/// * `canonical_words` - The preferred words of the directional confusion groups.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `metrics` - The hit counters of the confusion groups, they start over with every load.
+/// * `vocabulary` - The vocabulary of the edit candidates, read from `VOCABULARY_FILE` if it is set.
+pub struct LoadedData {
     pub confusion_set: Vec<Vec<String>>,
+    pub group_labels: Vec<String>,
+    pub canonical_words: HashSet<String>,
     pub number_of_ngrams: HashMap<i32, i64>,
-    pub number_of_distinct_ngrams: HashMap<i32, i64>,
+    pub metrics: metrics::ConfusionSetMetrics,
+    pub vocabulary: Option<Arc<HashSet<String>>>,
+}
+
+impl LoadedData {
+    /// Parses the contents of the confusion set and the number of n-grams files.
+    ///
+    /// # Arguments
+    ///
+    /// * `confusion_set` - The contents of the confusion set file.
+    /// * `number_of_ngrams` - The contents of the number of n-grams file.
+    /// * `policy` - The confusion set policy.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LoadedData` if both files are valid, otherwise a `String` with the error message.
+    pub fn parse(
+        confusion_set: String,
+        number_of_ngrams: String,
+        policy: ConfusionSetPolicy,
+    ) -> Result<LoadedData, String> {
+        let canonical_words = parse_canonical_words(&confusion_set);
+        let group_labels = parse_group_labels(&confusion_set);
+        let metrics = metrics::ConfusionSetMetrics::new(group_labels.clone());
+        let confusion_set = parse_confusion_set(confusion_set)
+            .map_err(|err| format!("Invalid confusion set: {}", err))?;
+
+        validate_confusion_set(&confusion_set, policy)
+            .map_err(|err| format!("Invalid confusion set: {}", err))?;
+
+        let number_of_ngrams = parse_number_of_ngrams(number_of_ngrams)
+            .map_err(|err| format!("Invalid number of n-grams file: {}", err))?;
+
+        Ok(LoadedData {
+            confusion_set,
+            group_labels,
+            canonical_words,
+            number_of_ngrams,
+            metrics,
+            vocabulary: None,
+        })
+    }
+
+    /// Reads the files set in `CONFUSION_SET_FILE` and `NUMBER_OF_NGRAMS_FILE`, with the
+    /// `CONFUSION_SET_POLICY` policy, and the optional `VOCABULARY_FILE`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LoadedData` if the files can be read and are valid, otherwise a `String` with the error message.
+    pub fn read() -> Result<LoadedData, String> {
+        let policy = env::var("CONFUSION_SET_POLICY").unwrap_or_else(|_| "all".to_string());
+        let policy = parse_confusion_set_policy(&policy)?;
+
+        let mut loaded = LoadedData::parse(
+            read_env_file("CONFUSION_SET_FILE")?,
+            read_env_file("NUMBER_OF_NGRAMS_FILE")?,
+            policy,
+        )?;
+
+        if env::var("VOCABULARY_FILE").is_ok_and(|path| !path.is_empty()) {
+            let vocabulary = parse_vocabulary(&read_env_file("VOCABULARY_FILE")?);
+            loaded.vocabulary = Some(Arc::new(vocabulary));
+        }
+
+        Ok(loaded)
+    }
+}
+
+/// Reads the file whose path is set in the environment variable.
+///
+/// # Arguments
+///
+/// * `var` - The name of the environment variable.
+///
+/// # Returns
+///
+/// A `Result` containing the contents of the file, otherwise a `String` with the error message.
+fn read_env_file(var: &str) -> Result<String, String> {
+    let path = env::var(var).map_err(|_| format!("{} is not set", var))?;
+
+    fs::read_to_string(&path).map_err(|err| format!("Could not read {}: {}", path, err))
 }
 
 /// Represents the form data.
@@ -36,11 +189,45 @@ pub struct AppData {
 /// # Fields
 ///
 /// * `text` - The text.
+/// * `distinct_counts` - Optional distinct n-gram counts that override the loaded ones for this request.
+/// * `early_stop` - Optional margin above which a confident bigram correction skips the trigram queries.
+/// * `predictor` - Optional name of the predictor, `max`, `sum`, `power_sum`, `lidstone` or `stupid_backoff`.
+/// * `power` - Optional power of the `power_sum` predictor.
+/// * `k` - Optional value the `lidstone` predictor adds to every n-gram frequency.
+/// * `log_base` - Optional base of the logarithm of the `max` and `sum` scores, greater than one, 10 by default.
+/// * `skip_backfill` - Optional flag to leave out the candidates Scylla returned no n-grams for, instead of scoring them with zero frequency.
+/// * `window` - Optional number of words on each side of the examined word, defaults to two.
+/// * `confusion_set` - Optional confusion groups that override the loaded ones for this request.
+/// * `skip_grams` - Optional flag to also query the skip-bigrams around the examined word, off by default.
+/// * `edit_candidates` - Optional flag to also examine the words outside the confusion set and the
+///   vocabulary against their edit candidates, off by default.
 ///
 /// This struct is used to store the form data.
 #[derive(serde::Deserialize)]
 pub struct FormData {
     pub text: String,
+    #[serde(default)]
+    pub distinct_counts: Option<HashMap<i32, i64>>,
+    #[serde(default)]
+    pub early_stop: Option<f64>,
+    #[serde(default)]
+    pub predictor: Option<String>,
+    #[serde(default)]
+    pub power: Option<f64>,
+    #[serde(default)]
+    pub k: Option<f64>,
+    #[serde(default)]
+    pub log_base: Option<f64>,
+    #[serde(default)]
+    pub skip_backfill: Option<bool>,
+    #[serde(default)]
+    pub window: Option<usize>,
+    #[serde(default)]
+    pub confusion_set: Option<Vec<Vec<String>>>,
+    #[serde(default)]
+    pub skip_grams: Option<bool>,
+    #[serde(default)]
+    pub edit_candidates: Option<bool>,
 }
 
 /// Parses the varying indexes from the query.
@@ -81,19 +268,120 @@ pub fn parse_varying_indexes(
 ///
 /// # Arguments
 ///
-/// * `amount` - The amount.
+/// * `amount` - The amount, a positive integer or `all`.
 ///
 /// # Returns
 ///
 /// A `Result` containing the `i32` if the amount is valid, otherwise a `String` with the error message.
+/// `all` is returned as `ALL_WORD_FREQ_PAIRS`, which does not truncate the pairs.
 ///
 /// # Errors
 ///
-/// If the amount is invalid, a `String` with the error message will be returned.
+/// If the amount is not a positive integer or `all`, a `String` with the error message will be returned.
 pub fn parse_amount(amount: &str) -> Result<i32, String> {
+    if amount == "all" {
+        return Ok(n_grams::word_freq_pair::ALL_WORD_FREQ_PAIRS);
+    }
+
     match amount.parse::<i32>() {
-        Ok(amount) => Ok(amount),
-        Err(_) => Err("Invalid amount".to_string()),
+        Ok(amount) if amount > 0 => Ok(amount),
+        _ => Err(format!(
+            "Invalid amount: {}, expected a positive integer or all",
+            amount
+        )),
+    }
+}
+
+/// Parses the minimum frequency from the query.
+///
+/// # Arguments
+///
+/// * `min_freq` - The minimum frequency.
+///
+/// # Returns
+///
+/// A `Result` containing the `i32` if the minimum frequency is a non-negative integer, otherwise a `String` with the error message.
+pub fn parse_min_freq(min_freq: &str) -> Result<i32, String> {
+    match min_freq.parse::<i32>() {
+        Ok(min_freq) if min_freq >= 0 => Ok(min_freq),
+        _ => Err("Invalid min_freq".to_string()),
+    }
+}
+
+/// Parses the offset from the query.
+///
+/// # Arguments
+///
+/// * `offset` - The number of word frequency pairs to skip.
+///
+/// # Returns
+///
+/// A `Result` containing the `usize` if the offset is a non-negative integer, otherwise a `String` with the error message.
+pub fn parse_offset(offset: &str) -> Result<usize, String> {
+    match offset.parse::<usize>() {
+        Ok(offset) => Ok(offset),
+        Err(_) => Err("Invalid offset".to_string()),
+    }
+}
+
+/// Represents the order of the word frequency pairs.
+///
+/// # Variants
+///
+/// * `Asc` - The rarest pairs first.
+/// * `Desc` - The most frequent pairs first.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Parses the sort order from the query.
+///
+/// # Arguments
+///
+/// * `order` - The order, `asc` or `desc`.
+///
+/// # Returns
+///
+/// A `Result` containing the `SortOrder` if the order is valid, otherwise a `String` with the error message.
+pub fn parse_sort_order(order: &str) -> Result<SortOrder, String> {
+    match order {
+        "asc" => Ok(SortOrder::Asc),
+        "desc" => Ok(SortOrder::Desc),
+        _ => Err(format!("Invalid order: {}", order)),
+    }
+}
+
+/// Parses the top k from the query.
+///
+/// # Arguments
+///
+/// * `top_k` - The number of best candidates to return.
+///
+/// # Returns
+///
+/// A `Result` containing the `usize` if the top k is valid, otherwise a `String` with the error message.
+pub fn parse_top_k(top_k: &str) -> Result<usize, String> {
+    match top_k.parse::<usize>() {
+        Ok(top_k) => Ok(top_k),
+        Err(_) => Err("Invalid top_k".to_string()),
+    }
+}
+
+/// Parses the score scale from the query.
+///
+/// # Arguments
+///
+/// * `score_scale` - The scale the scores are multiplied by.
+///
+/// # Returns
+///
+/// A `Result` containing the `i64` if the scale is a positive integer, otherwise a `String` with the error message.
+pub fn parse_score_scale(score_scale: &str) -> Result<i64, String> {
+    match score_scale.parse::<i64>() {
+        Ok(score_scale) if score_scale > 0 => Ok(score_scale),
+        _ => Err("Invalid score_scale".to_string()),
     }
 }
 
@@ -116,24 +404,388 @@ pub fn parse_n(query: &HashMap<String, String>) -> Result<i32, String> {
     }
 }
 
+/// Sanitizes the word by removing zero-width and control characters.
+///
+/// Words with zero-width joiners or byte order marks would otherwise never match the corpus.
+/// When the word is altered, it is reported in the logs.
+///
+/// # Arguments
+///
+/// * `word` - The word.
+///
+/// # Returns
+///
+/// The word without U+200B to U+200D, U+FEFF and control characters.
+pub fn sanitize_word(word: &str) -> String {
+    let sanitized: String = word
+        .chars()
+        .filter(|c| !matches!(c, '\u{200B}'..='\u{200D}' | '\u{FEFF}') && !c.is_control())
+        .collect();
+
+    if sanitized != word {
+        tracing::warn!(word = ?word, sanitized = ?sanitized, "sanitized word");
+    }
+
+    sanitized
+}
+
+/// Folds the Croatian diacritics to their ASCII equivalents.
+///
+/// The folding is best-effort, the corpus keeps the diacritics, so a folded word only matches
+/// the n-grams that were stored without them.
+///
+/// # Arguments
+///
+/// * `word` - The word.
+///
+/// # Returns
+///
+/// The word with `č` and `ć` replaced by `c`, `š` by `s`, `ž` by `z` and `đ` by `d`, keeping the case.
+pub fn normalize_diacritics(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            'č' | 'ć' => 'c',
+            'Č' | 'Ć' => 'C',
+            'š' => 's',
+            'Š' => 'S',
+            'ž' => 'z',
+            'Ž' => 'Z',
+            'đ' => 'd',
+            'Đ' => 'D',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Validates the distinct n-gram counts supplied with a request.
+///
+/// # Arguments
+///
+/// * `distinct_counts` - The distinct n-gram counts.
+/// * `number_of_ngrams` - The number of n-grams.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if there is a count for every order in `number_of_ngrams`, otherwise a `String` with the error message.
+pub fn validate_distinct_counts(
+    distinct_counts: &HashMap<i32, i64>,
+    number_of_ngrams: &HashMap<i32, i64>,
+) -> Result<(), String> {
+    let mut orders = number_of_ngrams.keys().collect::<Vec<&i32>>();
+    orders.sort();
+
+    for n in orders {
+        match distinct_counts.get(n) {
+            Some(count) if *count >= 0 => {}
+            Some(_) => return Err(format!("Invalid distinct count for {}-grams", n)),
+            None => return Err(format!("Missing distinct count for {}-grams", n)),
+        }
+    }
+
+    Ok(())
+}
+
 /// Parses the confusion set.
 ///
+/// Each line is one confusion group. A line starting with `>` marks a directional group whose
+/// first word is the canonical (preferred) member, e.g. `> bih bi`. A group can be labeled by
+/// starting it with a word ending in `:`, e.g. `yat: zahtijeva zahtjeva`.
+/// Blank lines are skipped and the repeated words of a group are kept once.
+///
 /// # Arguments
 ///
 /// * `contents` - The contents of the confusion set.
 ///
 /// # Returns
 ///
-/// A `Vec<Vec<String>>` containing the confusion set.
-pub fn parse_confusion_set(contents: String) -> Vec<Vec<String>> {
+/// A `Result` containing the confusion set, otherwise a `String` with the error message.
+///
+/// # Errors
+///
+/// If a line has fewer than two distinct words, a group needs a word to confuse with, a `String`
+/// with the line number will be returned.
+pub fn parse_confusion_set(contents: String) -> Result<Vec<Vec<String>>, String> {
     let mut confusion_set: Vec<Vec<String>> = vec![];
 
-    for line in contents.lines() {
-        let words = line.split_whitespace().map(|s| s.to_string()).collect();
-        confusion_set.push(words);
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let words = group_words(line);
+        if words.len() < 2 {
+            return Err(format!(
+                "Line {}: a confusion group needs at least two distinct words, found: {}",
+                i + 1,
+                line.trim()
+            ));
+        }
+        confusion_set.push(words.into_iter().map(|s| s.to_string()).collect());
     }
 
-    confusion_set
+    Ok(confusion_set)
+}
+
+/// Represents how words that are in more than one confusion group are handled.
+///
+/// # Variants
+///
+/// * `All` - The word is analyzed against every group it is in.
+/// * `Disjoint` - The groups must not overlap, an overlap is an error at load.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConfusionSetPolicy {
+    All,
+    Disjoint,
+}
+
+/// Parses the confusion set policy.
+///
+/// # Arguments
+///
+/// * `policy` - The policy, `all` or `disjoint`.
+///
+/// # Returns
+///
+/// A `Result` containing the `ConfusionSetPolicy` if the policy is valid, otherwise a `String` with the error message.
+pub fn parse_confusion_set_policy(policy: &str) -> Result<ConfusionSetPolicy, String> {
+    match policy {
+        "all" => Ok(ConfusionSetPolicy::All),
+        "disjoint" => Ok(ConfusionSetPolicy::Disjoint),
+        _ => Err(format!("Invalid confusion set policy: {}", policy)),
+    }
+}
+
+/// Validates the confusion set against the policy.
+///
+/// # Arguments
+///
+/// * `confusion_set` - The confusion set.
+/// * `policy` - The policy.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the confusion set follows the policy, otherwise a `String` with the error message.
+pub fn validate_confusion_set(
+    confusion_set: &[Vec<String>],
+    policy: ConfusionSetPolicy,
+) -> Result<(), String> {
+    if policy == ConfusionSetPolicy::All {
+        return Ok(());
+    }
+
+    let mut seen: HashSet<&String> = HashSet::new();
+
+    for group in confusion_set {
+        for word in group.iter().collect::<HashSet<&String>>() {
+            if !seen.insert(word) {
+                return Err(format!("{} is in more than one confusion group", word));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the confusion set sent with a request.
+///
+/// # Arguments
+///
+/// * `confusion_set` - The confusion set.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the set is not empty and every group has at least two distinct
+/// words, otherwise a `String` with the error message.
+pub fn validate_request_confusion_set(confusion_set: &[Vec<String>]) -> Result<(), String> {
+    if confusion_set.is_empty() {
+        return Err("The confusion set is empty".to_string());
+    }
+
+    for group in confusion_set {
+        let valid_words = group
+            .iter()
+            .all(|word| !word.is_empty() && !word.contains(char::is_whitespace));
+        let distinct: HashSet<&String> = group.iter().collect();
+
+        if !valid_words || distinct.len() < 2 {
+            return Err(format!(
+                "Invalid confusion group: {:?}, expected at least two distinct words",
+                group
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the canonical words of the directional confusion groups.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the confusion set.
+///
+/// # Returns
+///
+/// A `HashSet<String>` containing the first word of every line marked with `>`.
+pub fn parse_canonical_words(contents: &str) -> HashSet<String> {
+    let mut canonical_words: HashSet<String> = HashSet::new();
+
+    for line in contents.lines().filter(|line| is_group_line(line)) {
+        if let Some(line) = strip_directional_marker(line) {
+            let (_, line) = strip_label(line);
+            if let Some(word) = line.split_whitespace().next() {
+                canonical_words.insert(word.to_string());
+            }
+        }
+    }
+
+    canonical_words
+}
+
+/// Parses the vocabulary of the edit candidates.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the vocabulary, one word per line, optionally followed by its frequency.
+///
+/// # Returns
+///
+/// A `HashSet<String>` containing the lowercased first word of every line.
+pub fn parse_vocabulary(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Parses the labels of the confusion groups.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the confusion set.
+///
+/// # Returns
+///
+/// A `Vec<String>` with the label of every group, in the order of `parse_confusion_set`.
+/// Unlabeled groups are labeled with their words joined by `/`.
+pub fn parse_group_labels(contents: &str) -> Vec<String> {
+    let mut labels: Vec<String> = vec![];
+
+    for line in contents.lines().filter(|line| is_group_line(line)) {
+        let line = strip_directional_marker(line).unwrap_or(line);
+        let label = match strip_label(line) {
+            (Some(label), _) => label.to_string(),
+            (None, _) => group_words(line).join("/"),
+        };
+        labels.push(label);
+    }
+
+    labels
+}
+
+/// Selects the confusion groups by their labels.
+///
+/// # Arguments
+///
+/// * `confusion_set` - The confusion set.
+/// * `labels` - The labels of the groups, in the order of the confusion set.
+/// * `selected` - The comma separated labels of the selected groups.
+///
+/// # Returns
+///
+/// A `Result` containing the selected groups, otherwise a `String` with the error message.
+///
+/// # Errors
+///
+/// If a label does not belong to any group, a `String` with the error message will be returned.
+pub fn select_groups(
+    confusion_set: &[Vec<String>],
+    labels: &[String],
+    selected: &str,
+) -> Result<Vec<Vec<String>>, String> {
+    let selected: Vec<&str> = selected.split(',').map(|label| label.trim()).collect();
+
+    for label in &selected {
+        if !labels.iter().any(|l| l == label) {
+            return Err(format!("Unknown group: {}", label));
+        }
+    }
+
+    Ok(confusion_set
+        .iter()
+        .zip(labels.iter())
+        .filter(|(_, label)| selected.contains(&label.as_str()))
+        .map(|(group, _)| group.clone())
+        .collect())
+}
+
+/// Checks whether the confusion set line is a group of at least two words.
+///
+/// # Arguments
+///
+/// * `line` - The line of the confusion set.
+///
+/// # Returns
+///
+/// `true` if the line has at least two distinct words besides the directional marker and the label.
+fn is_group_line(line: &str) -> bool {
+    group_words(line).len() >= 2
+}
+
+/// Splits the words of a confusion set line.
+///
+/// # Arguments
+///
+/// * `line` - The line of the confusion set.
+///
+/// # Returns
+///
+/// The words besides the directional marker and the label, a repeated word only at its first position.
+fn group_words(line: &str) -> Vec<&str> {
+    let line = strip_directional_marker(line).unwrap_or(line);
+    let (_, line) = strip_label(line);
+    let mut words: Vec<&str> = vec![];
+
+    for word in line.split_whitespace() {
+        if !words.contains(&word) {
+            words.push(word);
+        }
+    }
+
+    words
+}
+
+/// Splits the label from a confusion set line.
+///
+/// # Arguments
+///
+/// * `line` - The line of the confusion set, without the directional marker.
+///
+/// # Returns
+///
+/// The label if the line starts with one, and the rest of the line.
+fn strip_label(line: &str) -> (Option<&str>, &str) {
+    let line = line.trim_start();
+
+    match line.split_once(char::is_whitespace) {
+        Some((first, rest)) if first.len() > 1 && first.ends_with(':') => {
+            (Some(&first[..first.len() - 1]), rest)
+        }
+        _ => (None, line),
+    }
+}
+
+/// Strips the directional marker from a confusion set line.
+///
+/// # Arguments
+///
+/// * `line` - The line of the confusion set.
+///
+/// # Returns
+///
+/// An `Option` containing the rest of the line if it is marked as directional, otherwise `None`.
+fn strip_directional_marker(line: &str) -> Option<&str> {
+    line.trim_start().strip_prefix('>')
 }
 
 /// Parses the number of n-grams.
@@ -142,28 +794,92 @@ pub fn parse_confusion_set(contents: String) -> Vec<Vec<String>> {
 ///
 /// * `contents` - The contents of the number of n-grams.
 ///
+/// Each line holds the order and the count, e.g. `2 443937190`. Blank lines are skipped.
+///
 /// # Returns
 ///
-/// A `HashMap<i32, i64>` containing the number of n-grams.
-pub fn parse_number_of_ngrams(contents: String) -> HashMap<i32, i64> {
+/// A `Result` containing the `HashMap<i32, i64>` with the number of n-grams, otherwise a `String` with the error message.
+///
+/// # Errors
+///
+/// If a line is malformed or has an unsupported order, a `String` with the line number and content will be returned.
+pub fn parse_number_of_ngrams(contents: String) -> Result<HashMap<i32, i64>, String> {
     let mut number_of_ngrams: HashMap<i32, i64> = HashMap::new();
 
-    for line in contents.lines() {
+    let valid_n_values = [1, 2, 3, 4, 5];
+
+    for (i, line) in contents.lines().enumerate() {
         let parts = line.split_whitespace().collect::<Vec<&str>>();
-        let n = parts[0].parse::<i32>().unwrap();
-        let number = parts[1].parse::<i64>().unwrap();
+
+        let (n, number) = match parts.as_slice() {
+            [] => continue,
+            [n, number] => (n, number),
+            _ => return Err(format!("Line {}: expected `n count`: {}", i + 1, line)),
+        };
+
+        let n = match n.parse::<i32>() {
+            Ok(n) if valid_n_values.contains(&n) => n,
+            _ => return Err(format!("Line {}: invalid n: {}", i + 1, line)),
+        };
+
+        let number = match number.parse::<i64>() {
+            Ok(number) => number,
+            Err(_) => return Err(format!("Line {}: invalid count: {}", i + 1, line)),
+        };
+
         number_of_ngrams.insert(n, number);
     }
 
-    let valid_n_values = vec![1, 2, 3];
+    Ok(number_of_ngrams)
+}
 
-    for n in number_of_ngrams.keys() {
-        if !valid_n_values.contains(n) {
-            panic!("Invalid ngram in number of ngrams file");
-        }
+/// Formats the number of n-grams in the format `parse_number_of_ngrams` expects.
+///
+/// # Arguments
+///
+/// * `number_of_ngrams` - The number of n-grams.
+///
+/// # Returns
+///
+/// A `String` with one `n count` line per order, sorted by the order.
+pub fn format_number_of_ngrams(number_of_ngrams: &HashMap<i32, i64>) -> String {
+    let mut orders = number_of_ngrams.keys().collect::<Vec<&i32>>();
+    orders.sort();
+
+    let mut contents = String::new();
+
+    for n in orders {
+        contents.push_str(&format!("{} {}\n", n, number_of_ngrams[n]));
     }
 
-    number_of_ngrams
+    contents
+}
+
+/// The content type of the CSV responses.
+pub static CSV_CONTENT_TYPE: &str = "text/csv";
+
+/// The content type of the streamed responses, one JSON object per line.
+pub static NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Formats the fields as a CSV row.
+///
+/// # Arguments
+///
+/// * `fields` - The fields.
+///
+/// # Returns
+///
+/// The row ending with a newline, the fields with a comma, a quote or a line break are quoted.
+pub fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.to_string(),
+        })
+        .collect();
+
+    format!("{}\n", fields.join(","))
 }
 
 #[cfg(test)]
@@ -183,7 +899,194 @@ mod tests {
     fn test_parse_varying_indexes_invalid_index() {
         let indexes = parse_varying_indexes("1,2,3a", MOCK_VALIDATION_FN);
 
-        assert_eq!(indexes.is_err(), true);
+        assert!(indexes.is_err());
+    }
+
+    #[test]
+    fn test_parse_confusion_set_directional() {
+        let contents = "> bih bi\nsto što".to_string();
+
+        let confusion_set = parse_confusion_set(contents.clone()).unwrap();
+        let canonical_words = parse_canonical_words(&contents);
+
+        assert_eq!(confusion_set, vec![vec!["bih", "bi"], vec!["sto", "što"]]);
+        assert_eq!(canonical_words, HashSet::from(["bih".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_vocabulary() {
+        let vocabulary = parse_vocabulary("Zagreb 1200\n\n  kuća\nkuća 7\n");
+
+        assert_eq!(
+            vocabulary,
+            HashSet::from(["zagreb".to_string(), "kuća".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_confusion_set_skips_blank_lines() {
+        let contents = "bi bih\n\n   \nje jest\n".to_string();
+
+        let confusion_set = parse_confusion_set(contents.clone()).unwrap();
+        let labels = parse_group_labels(&contents);
+
+        assert_eq!(confusion_set, vec![vec!["bi", "bih"], vec!["je", "jest"]]);
+        assert_eq!(labels, vec!["bi/bih", "je/jest"]);
+    }
+
+    #[test]
+    fn test_parse_confusion_set_rejects_single_words() {
+        assert_eq!(
+            parse_confusion_set("bi bih\n\n> sto\n".to_string()),
+            Err(
+                "Line 3: a confusion group needs at least two distinct words, found: > sto"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_confusion_set("yat: zahtijeva".to_string()),
+            Err(
+                "Line 1: a confusion group needs at least two distinct words, found: yat: zahtijeva"
+                    .to_string()
+            )
+        );
+        assert!(parse_confusion_set("je je".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_confusion_set_dedupes_words() {
+        let contents = "bi bih bi\n> sto što sto".to_string();
+
+        let confusion_set = parse_confusion_set(contents.clone()).unwrap();
+
+        assert_eq!(confusion_set, vec![vec!["bi", "bih"], vec!["sto", "što"]]);
+        assert_eq!(parse_group_labels(&contents), vec!["bi/bih", "sto/što"]);
+        assert_eq!(
+            parse_canonical_words(&contents),
+            HashSet::from(["sto".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_group_labels() {
+        let contents = "yat: zahtijeva zahtjeva\n> bih: bih bi\nsto što".to_string();
+
+        let confusion_set = parse_confusion_set(contents.clone()).unwrap();
+        let canonical_words = parse_canonical_words(&contents);
+        let labels = parse_group_labels(&contents);
+
+        assert_eq!(
+            confusion_set,
+            vec![
+                vec!["zahtijeva", "zahtjeva"],
+                vec!["bih", "bi"],
+                vec!["sto", "što"]
+            ]
+        );
+        assert_eq!(canonical_words, HashSet::from(["bih".to_string()]));
+        assert_eq!(labels, vec!["yat", "bih", "sto/što"]);
+    }
+
+    #[test]
+    fn test_select_groups() {
+        let contents = "yat: zahtijeva zahtjeva\nsto što";
+        let confusion_set = parse_confusion_set(contents.to_string()).unwrap();
+        let labels = parse_group_labels(contents);
+
+        assert_eq!(
+            select_groups(&confusion_set, &labels, "yat"),
+            Ok(vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]])
+        );
+        assert_eq!(
+            select_groups(&confusion_set, &labels, "yat,je"),
+            Err("Unknown group: je".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_confusion_set_overlap() {
+        let confusion_set = parse_confusion_set("bi bih\nbi bii".to_string()).unwrap();
+
+        assert_eq!(
+            parse_confusion_set_policy("all"),
+            Ok(ConfusionSetPolicy::All)
+        );
+        assert!(validate_confusion_set(&confusion_set, ConfusionSetPolicy::All).is_ok());
+        assert_eq!(
+            validate_confusion_set(&confusion_set, ConfusionSetPolicy::Disjoint),
+            Err("bi is in more than one confusion group".to_string())
+        );
+        assert!(parse_confusion_set_policy("first").is_err());
+    }
+
+    #[test]
+    fn test_validate_distinct_counts() {
+        let number_of_ngrams = HashMap::from([(1, 1000), (2, 800), (3, 600)]);
+
+        let complete = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let missing = HashMap::from([(1, 100), (3, 60)]);
+
+        assert!(validate_distinct_counts(&complete, &number_of_ngrams).is_ok());
+        assert_eq!(
+            validate_distinct_counts(&missing, &number_of_ngrams),
+            Err("Missing distinct count for 2-grams".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_number_of_ngrams() {
+        let counts = HashMap::from([(3, 1744646259), (1, 3275612), (2, 443937190)]);
+
+        let contents = format_number_of_ngrams(&counts);
+
+        assert_eq!(contents, "1 3275612\n2 443937190\n3 1744646259\n");
+        assert_eq!(parse_number_of_ngrams(contents), Ok(counts));
+    }
+
+    #[test]
+    fn test_parse_number_of_ngrams_missing_column() {
+        let contents = "1 3275612\n2\n".to_string();
+
+        assert_eq!(
+            parse_number_of_ngrams(contents),
+            Err("Line 2: expected `n count`: 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_number_of_ngrams_invalid_count() {
+        let contents = "1 3275612\n2 many\n".to_string();
+
+        assert_eq!(
+            parse_number_of_ngrams(contents),
+            Err("Line 2: invalid count: 2 many".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_number_of_ngrams_invalid_n() {
+        let contents = "6 100\n".to_string();
+
+        assert_eq!(
+            parse_number_of_ngrams(contents),
+            Err("Line 1: invalid n: 6 100".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sanitize_word() {
+        assert_eq!(sanitize_word("za\u{200D}htjeva"), "zahtjeva");
+        assert_eq!(sanitize_word("\u{FEFF}sam\u{200B}"), "sam");
+        assert_eq!(sanitize_word("gle\u{0007}dao"), "gledao");
+        assert_eq!(sanitize_word("Krleža"), "Krleža");
+    }
+
+    #[test]
+    fn test_normalize_diacritics() {
+        assert_eq!(normalize_diacritics("čćšžđ"), "ccszd");
+        assert_eq!(normalize_diacritics("ČĆŠŽĐ"), "CCSZD");
+        assert_eq!(normalize_diacritics("Krleža"), "Krleza");
+        assert_eq!(normalize_diacritics("gledao"), "gledao");
     }
 
     #[test]
@@ -191,12 +1094,130 @@ mod tests {
         let amount = parse_amount("1");
 
         assert_eq!(amount, Ok(1));
+        assert_eq!(
+            parse_amount("all"),
+            Ok(n_grams::word_freq_pair::ALL_WORD_FREQ_PAIRS)
+        );
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("0"), Ok(0));
+        assert_eq!(parse_offset("20"), Ok(20));
+        assert!(parse_offset("-1").is_err());
+        assert!(parse_offset("a").is_err());
+    }
+
+    #[test]
+    fn test_parse_score_scale() {
+        assert_eq!(parse_score_scale("1000"), Ok(1000));
+        assert!(parse_score_scale("0").is_err());
+        assert!(parse_score_scale("1.5").is_err());
+    }
+
+    #[test]
+    fn test_parse_sort_order() {
+        assert_eq!(parse_sort_order("asc"), Ok(SortOrder::Asc));
+        assert_eq!(parse_sort_order("desc"), Ok(SortOrder::Desc));
+        assert!(parse_sort_order("up").is_err());
+    }
+
+    #[test]
+    fn test_parse_top_k() {
+        assert_eq!(parse_top_k("2"), Ok(2));
+        assert!(parse_top_k("-2").is_err());
     }
 
     #[test]
     fn test_parse_amount_invalid_amount() {
         let amount = parse_amount("1a");
 
-        assert_eq!(amount.is_err(), true);
+        assert!(amount.is_err());
+        assert_eq!(
+            parse_amount("0"),
+            Err("Invalid amount: 0, expected a positive integer or all".to_string())
+        );
+        assert!(parse_amount("-1").is_err());
+    }
+
+    #[test]
+    fn test_csv_row() {
+        assert_eq!(csv_row(&["1", "zahtijeva", "5"]), "1,zahtijeva,5\n");
+        assert_eq!(
+            csv_row(&["mene zahtjeva, da", "rekao je \"da\""]),
+            "\"mene zahtjeva, da\",\"rekao je \"\"da\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_request_confusion_set() {
+        let form: FormData = serde_json::from_str(
+            r#"{"text": "On od mene zahtjeva da dođem.", "confusion_set": [["zahtijeva", "zahtjeva"]]}"#,
+        )
+        .unwrap();
+        let confusion_set = form.confusion_set.unwrap();
+
+        assert_eq!(
+            confusion_set,
+            vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]]
+        );
+        assert!(validate_request_confusion_set(&confusion_set).is_ok());
+
+        assert!(validate_request_confusion_set(&[]).is_err());
+        assert!(validate_request_confusion_set(&[vec!["bi".to_string()]]).is_err());
+        assert!(
+            validate_request_confusion_set(&[vec!["bi".to_string(), "bi".to_string()]]).is_err()
+        );
+        assert!(
+            validate_request_confusion_set(&[vec!["bi".to_string(), "bi h".to_string()]]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_loaded_data_parse() {
+        let loaded = LoadedData::parse(
+            "yat: zahtijeva zahtjeva\n> bih bi".to_string(),
+            "1 100\n2 50".to_string(),
+            ConfusionSetPolicy::All,
+        )
+        .unwrap();
+
+        assert_eq!(
+            loaded.confusion_set,
+            vec![vec!["zahtijeva", "zahtjeva"], vec!["bih", "bi"]]
+        );
+        assert_eq!(loaded.canonical_words, HashSet::from(["bih".to_string()]));
+        assert_eq!(loaded.number_of_ngrams, HashMap::from([(1, 100), (2, 50)]));
+        assert_eq!(loaded.metrics.snapshot().len(), 2);
+
+        let err = LoadedData::parse(
+            "bi bih\nbi biti".to_string(),
+            "1 100".to_string(),
+            ConfusionSetPolicy::Disjoint,
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.starts_with("Invalid confusion set"));
+
+        let err = LoadedData::parse(
+            "bi bih".to_string(),
+            "1 a".to_string(),
+            ConfusionSetPolicy::All,
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.starts_with("Invalid number of n-grams file"));
+
+        let err = LoadedData::parse(
+            "bi bih\nsto".to_string(),
+            "1 100".to_string(),
+            ConfusionSetPolicy::All,
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.starts_with("Invalid confusion set: Line 2"));
     }
 }
@@ -1,15 +1,24 @@
 use actix_cors::Cors;
 use actix_web::{web::Data, App, HttpServer};
 use context_analyzer::{
-    db, n_grams::routers, parse_confusion_set, parse_number_of_ngrams, AppData,
+    admin, config, db, format_number_of_ngrams, health, metrics,
+    n_grams::{normalizer::get_normalizer, routers},
+    parse_number_of_ngrams, AppData, LoadedData,
 };
 use dotenv::dotenv;
 use listenfd::ListenFd;
-use std::{env, fs};
+use scylla::Session;
+use std::{
+    collections::HashMap,
+    env, fs,
+    sync::{Arc, RwLock},
+};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 /// The main function of the application.
 ///
 /// This function initializes the application and starts the server.
+/// When started with the `compute-counts` argument, it writes the n-gram count files instead.
 ///
 /// # Returns
 ///
@@ -21,39 +30,128 @@ use std::{env, fs};
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let session = match db::init().await {
-        Ok(session) => session,
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+
+    let (session, consistency) = match db::init().await {
+        Ok(connection) => connection,
         Err(e) => {
-            eprintln!("{}", e);
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to connect to ScyllaDB",
-            ));
+            tracing::error!(error = %e, "failed to connect to ScyllaDB");
+            return Err(std::io::Error::other("Failed to connect to ScyllaDB"));
         }
     };
 
-    let conf_set_file_path = env::var("CONFUSION_SET_FILE").unwrap();
-    let number_of_ngrams_file_path = env::var("NUMBER_OF_NGRAMS_FILE").unwrap();
-    let number_of_distinct_ngrams_file_path = env::var("NUMBER_OF_DISTINCT_NGRAMS_FILE").unwrap();
+    if env::var("VALIDATE_SCHEMA").is_ok_and(|validate| validate == "true") {
+        if let Err(e) = db::validate_tables(Arc::clone(&session)).await {
+            tracing::error!(error = %e, "schema validation failed");
+            return Err(std::io::Error::other("Schema validation failed"));
+        }
+    }
 
-    let contents = fs::read_to_string(conf_set_file_path).expect("Could not read the file");
+    if env::args().nth(1).as_deref() == Some("compute-counts") {
+        return compute_counts(session).await;
+    }
 
-    let confusion_set: Vec<Vec<String>> = parse_confusion_set(contents);
+    let loaded = match LoadedData::read() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load the confusion set and counts");
+            return Err(std::io::Error::other(
+                "Failed to load the confusion set and counts",
+            ));
+        }
+    };
 
-    let contents = fs::read_to_string(number_of_ngrams_file_path).expect("Could not read the file");
+    let number_of_distinct_ngrams = match env::var("COMPUTE_DISTINCT_COUNTS")
+        .is_ok_and(|compute| compute == "true")
+    {
+        true => match db::count_distinct_ngrams(Arc::clone(&session)).await {
+            Ok(number_of_distinct_ngrams) => {
+                for (n, _) in db::N_GRAM_TABLES {
+                    tracing::info!(
+                        n,
+                        distinct = number_of_distinct_ngrams[&n],
+                        "distinct n-grams"
+                    );
+                }
+                number_of_distinct_ngrams
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to compute the distinct n-gram counts");
+                return Err(std::io::Error::other(
+                    "Failed to compute the distinct n-gram counts",
+                ));
+            }
+        },
+        false => {
+            let number_of_distinct_ngrams_file_path =
+                match env::var("NUMBER_OF_DISTINCT_NGRAMS_FILE") {
+                    Ok(path) => path,
+                    Err(e) => {
+                        tracing::error!(error = %e, "NUMBER_OF_DISTINCT_NGRAMS_FILE is not set");
+                        return Err(std::io::Error::other(
+                            "NUMBER_OF_DISTINCT_NGRAMS_FILE is not set",
+                        ));
+                    }
+                };
+            let contents = match fs::read_to_string(&number_of_distinct_ngrams_file_path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    tracing::error!(
+                        error = %e,
+                        path = %number_of_distinct_ngrams_file_path,
+                        "failed to read the number of distinct n-grams file"
+                    );
+                    return Err(std::io::Error::other(
+                        "Failed to read the number of distinct n-grams file",
+                    ));
+                }
+            };
 
-    let number_of_ngrams = parse_number_of_ngrams(contents);
+            match parse_number_of_ngrams(contents) {
+                Ok(number_of_distinct_ngrams) => number_of_distinct_ngrams,
+                Err(e) => {
+                    tracing::error!(error = %e, "invalid number of distinct n-grams file");
+                    return Err(std::io::Error::other(
+                        "Invalid number of distinct n-grams file",
+                    ));
+                }
+            }
+        }
+    };
 
-    let contents =
-        fs::read_to_string(number_of_distinct_ngrams_file_path).expect("Could not read the file");
+    let normalizer =
+        get_normalizer(env::var("NORMALIZE_WORDS").is_ok_and(|normalize| normalize == "true"));
 
-    let number_of_distinct_ngrams = parse_number_of_ngrams(contents);
+    let store: Arc<dyn db::NgramStore> = Arc::new(db::ScyllaStore::new(
+        Arc::clone(&session),
+        Arc::new(db::PreparedStatementCache::new()),
+        consistency,
+    ));
+    let store: Arc<dyn db::NgramStore> = match db::query_timeout() {
+        Some(timeout) => Arc::new(db::TimeoutStore::new(store, timeout)),
+        None => store,
+    };
+    // only the /n-gram lookups are cached, the solver's queries would evict the hot words
+    let lookup_store: Arc<dyn db::NgramStore> = match db::lookup_cache_capacity() {
+        Some(capacity) => Arc::new(db::CachingStore::new(
+            Arc::clone(&store),
+            capacity,
+            db::lookup_cache_ttl(),
+        )),
+        None => Arc::clone(&store),
+    };
 
     let data = Data::new(AppData {
-        scy_session: session,
-        confusion_set,
-        number_of_ngrams,
+        scy_session: Arc::clone(&session),
+        loaded: RwLock::new(Arc::new(loaded)),
         number_of_distinct_ngrams,
+        normalizer,
+        store,
+        lookup_store,
+        consistency,
     });
 
     let mut listenfd = ListenFd::from_env();
@@ -67,6 +165,10 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .app_data(data.clone())
             .configure(routers::init_routes)
+            .configure(health::init_routes)
+            .configure(metrics::init_routes)
+            .configure(config::init_routes)
+            .configure(admin::init_routes)
     });
 
     server = match listenfd.take_tcp_listener(0)? {
@@ -80,3 +182,53 @@ async fn main() -> std::io::Result<()> {
 
     server.run().await
 }
+
+/// Computes the n-gram counts from the database and writes the count files.
+///
+/// The total number of n-grams is written to `NUMBER_OF_NGRAMS_FILE` and the number of
+/// distinct n-grams to `NUMBER_OF_DISTINCT_NGRAMS_FILE`.
+///
+/// # Arguments
+///
+/// * `session` - The ScyllaDB session.
+///
+/// # Returns
+///
+/// A `std::io::Result` containing the result of the function.
+///
+/// # Errors
+///
+/// If the counts can not be computed or the files can not be written, an error is returned.
+async fn compute_counts(session: Arc<Session>) -> std::io::Result<()> {
+    let number_of_ngrams_file_path = env::var("NUMBER_OF_NGRAMS_FILE").unwrap();
+    let number_of_distinct_ngrams_file_path = env::var("NUMBER_OF_DISTINCT_NGRAMS_FILE").unwrap();
+
+    let mut number_of_ngrams: HashMap<i32, i64> = HashMap::new();
+    let mut number_of_distinct_ngrams: HashMap<i32, i64> = HashMap::new();
+
+    for (n, table) in db::N_GRAM_TABLES {
+        let table = db::qualified_table(table);
+        let total = db::sum_frequencies(Arc::clone(&session), &table)
+            .await
+            .map_err(std::io::Error::other)?;
+        let distinct = db::count_rows(Arc::clone(&session), &table)
+            .await
+            .map_err(std::io::Error::other)?;
+
+        tracing::info!(n, total, distinct, "counted the n-grams");
+
+        number_of_ngrams.insert(n, total);
+        number_of_distinct_ngrams.insert(n, distinct);
+    }
+
+    fs::write(
+        number_of_ngrams_file_path,
+        format_number_of_ngrams(&number_of_ngrams),
+    )?;
+    fs::write(
+        number_of_distinct_ngrams_file_path,
+        format_number_of_ngrams(&number_of_distinct_ngrams),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use crate::{
+    n_grams::solver::{
+        correction::best_candidate,
+        predictor::{PredictionResult, PredictionResults},
+    },
+    AppData,
+};
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Represents the counters of a confusion group.
+///
+/// # Fields
+///
+/// * `label` - The label of the group.
+/// * `triggered` - How many contexts were checked against the group.
+/// * `corrected` - How many of those contexts suggested a different word.
+struct GroupCounters {
+    label: String,
+    triggered: AtomicU64,
+    corrected: AtomicU64,
+}
+
+/// Represents the snapshot of the counters of a confusion group.
+///
+/// # Fields
+///
+/// * `label` - The label of the group.
+/// * `triggered` - How many contexts were checked against the group.
+/// * `corrected` - How many of those contexts suggested a different word.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GroupMetrics {
+    pub label: String,
+    pub triggered: u64,
+    pub corrected: u64,
+}
+
+/// Tracks how often every confusion group fires, so noisy groups can be found.
+///
+/// # Methods
+///
+/// * `new` - Creates the counters for the labeled groups.
+/// * `record` - Records the predictions of a check.
+/// * `record_result` - Records the prediction of a context.
+/// * `snapshot` - Reads the counters.
+pub struct ConfusionSetMetrics {
+    groups: Vec<GroupCounters>,
+}
+
+impl ConfusionSetMetrics {
+    /// Creates the counters for the labeled groups.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The labels of the groups, in the order of the confusion set.
+    ///
+    /// # Returns
+    ///
+    /// A `ConfusionSetMetrics` with all the counters at zero.
+    pub fn new(labels: Vec<String>) -> ConfusionSetMetrics {
+        let groups = labels
+            .into_iter()
+            .map(|label| GroupCounters {
+                label,
+                triggered: AtomicU64::new(0),
+                corrected: AtomicU64::new(0),
+            })
+            .collect();
+
+        ConfusionSetMetrics { groups }
+    }
+
+    /// Records the predictions of a check.
+    ///
+    /// Every context counts as a trigger for the group it was scored against, and as a correction
+    /// if the best scoring candidate is a different word.
+    ///
+    /// # Arguments
+    ///
+    /// * `confusion_set` - The confusion set the counters were created for.
+    /// * `predictions` - The predictions.
+    pub fn record(&self, confusion_set: &[Vec<String>], predictions: &PredictionResults) {
+        for result in &predictions.results {
+            self.record_result(confusion_set, result);
+        }
+    }
+
+    /// Records the prediction of a context, e.g. of a streamed check.
+    ///
+    /// # Arguments
+    ///
+    /// * `confusion_set` - The confusion set the counters were created for.
+    /// * `result` - The prediction of the context.
+    pub fn record_result(&self, confusion_set: &[Vec<String>], result: &PredictionResult) {
+        let corrected = match best_candidate(result) {
+            Some(best) => *best != result.word_examined,
+            None => false,
+        };
+
+        for (group, counters) in confusion_set.iter().zip(self.groups.iter()) {
+            if !group.contains(&result.word_examined)
+                || !result
+                    .results
+                    .keys()
+                    .all(|candidate| group.contains(candidate))
+            {
+                continue;
+            }
+            counters.triggered.fetch_add(1, Ordering::Relaxed);
+            if corrected {
+                counters.corrected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Reads the counters.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<GroupMetrics>` with the counters of every group.
+    pub fn snapshot(&self) -> Vec<GroupMetrics> {
+        self.groups
+            .iter()
+            .map(|counters| GroupMetrics {
+                label: counters.label.clone(),
+                triggered: counters.triggered.load(Ordering::Relaxed),
+                corrected: counters.corrected.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Handles the metrics.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The counters of the confusion groups.
+#[get("/metrics")]
+async fn get_metrics(data: web::Data<AppData>) -> HttpResponse {
+    HttpResponse::Ok().json(data.loaded().metrics.snapshot())
+}
+
+/// Initializes the metrics routes.
+///
+/// # Arguments
+///
+/// * `cfg` - The service configuration.
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_metrics);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn prediction(word_examined: &str, results: &[(&str, f64)]) -> PredictionResult {
+        PredictionResult {
+            context: format!("On od mene {} da", word_examined),
+            word_examined: word_examined.to_string(),
+            results: results
+                .iter()
+                .map(|(word, score)| (word.to_string(), *score))
+                .collect::<HashMap<String, f64>>(),
+            normalized: HashMap::new(),
+            unigram_only: vec![],
+            max_matched_order: HashMap::new(),
+            rows_fetched: None,
+            explanation: None,
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let confusion_set = vec![
+            vec!["zahtijeva".to_string(), "zahtjeva".to_string()],
+            vec!["sto".to_string(), "što".to_string()],
+        ];
+        let metrics = ConfusionSetMetrics::new(vec!["yat".to_string(), "sto/što".to_string()]);
+        let predictions = PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results: vec![
+                prediction("zahtjeva", &[("zahtijeva", 1.0), ("zahtjeva", 2.0)]),
+                prediction("zahtijeva", &[("zahtijeva", 1.0), ("zahtjeva", 2.0)]),
+            ],
+            sentences: 2,
+            errors: vec![],
+            perplexity: vec![],
+        };
+
+        metrics.record(&confusion_set, &predictions);
+
+        assert_eq!(
+            metrics.snapshot(),
+            vec![
+                GroupMetrics {
+                    label: "yat".to_string(),
+                    triggered: 2,
+                    corrected: 1,
+                },
+                GroupMetrics {
+                    label: "sto/što".to_string(),
+                    triggered: 0,
+                    corrected: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_result() {
+        let confusion_set = vec![vec!["sto".to_string(), "što".to_string()]];
+        let metrics = ConfusionSetMetrics::new(vec!["sto/što".to_string()]);
+
+        metrics.record_result(
+            &confusion_set,
+            &prediction("sto", &[("sto", 3.0), ("što", 1.0)]),
+        );
+        metrics.record_result(
+            &confusion_set,
+            &prediction("što", &[("sto", 3.0), ("što", 1.0)]),
+        );
+
+        assert_eq!(
+            metrics.snapshot(),
+            vec![GroupMetrics {
+                label: "sto/što".to_string(),
+                triggered: 2,
+                corrected: 1,
+            }]
+        );
+    }
+}
@@ -8,6 +8,22 @@ pub mod solver;
 /// This module contains the n-grams of the application.
 pub mod model;
 
+/// The `five_grams` module.
+///
+/// This module contains the implementation of five-grams.
+mod five_grams;
+/// The `four_grams` module.
+///
+/// This module contains the implementation of four-grams.
+mod four_grams;
+/// The `normalizer` module.
+///
+/// This module contains the normalization of the queried words.
+pub mod normalizer;
+/// The `one_grams` module.
+///
+/// This module contains the implementation of one-grams.
+mod one_grams;
 /// The `router` module.
 ///
 /// This module contains the routers of the application.
@@ -27,7 +43,7 @@ mod vary_n_gram;
 /// The `word_freq_pair` module.
 ///
 /// This module contains the implementation of word frequency pairs.
-mod word_freq_pair;
+pub mod word_freq_pair;
 
 /// Behavior needed for querying the database.
 pub trait Queryable {
@@ -0,0 +1,6 @@
+/// This module contains the five grams of the application
+///
+/// # Modules
+///
+/// * `model` - Contains the model of the five grams.
+pub mod model;
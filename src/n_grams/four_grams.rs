@@ -0,0 +1,6 @@
+/// This module contains the four grams of the application
+///
+/// # Modules
+///
+/// * `model` - Contains the model of the four grams.
+pub mod model;
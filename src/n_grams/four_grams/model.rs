@@ -0,0 +1,272 @@
+use crate::{
+    db::{
+        GET_BY_FIRST_SECOND_AND_FOURTH_4, GET_BY_FIRST_SECOND_AND_THIRD_4,
+        GET_BY_FIRST_THIRD_AND_FOURTH_4, GET_BY_SECOND_THIRD_AND_FOURTH_4, GET_FREQ_4,
+    },
+    n_grams::{Printable, Queryable},
+    sanitize_word,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Represents the four gram that is given as input.
+///
+/// # Fields
+///
+/// * `word1` - The first word of the four-gram.
+/// * `word2` - The second word of the four-gram.
+/// * `word3` - The third word of the four-gram.
+/// * `word4` - The fourth word of the four-gram.
+///
+/// # Implements
+///
+/// * `Queryable` - Provides methods to query the database.
+/// * `Printable` - Provides method for printing.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FourGramInput {
+    pub word1: String,
+    pub word2: String,
+    pub word3: String,
+    pub word4: String,
+}
+
+impl FourGramInput {
+    /// Creates a new `FourGramInput` from the given query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query that contains the four-gram.
+    ///
+    /// The words are sanitized, see `sanitize_word`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FourGramInput` if the query is valid, otherwise a `String` with the error message.
+    pub fn from(query: &HashMap<String, String>) -> Result<FourGramInput, String> {
+        let word1 = match query.get("word1") {
+            Some(word1) => word1,
+            None => return Err("word1 is required".to_string()),
+        };
+
+        let word2 = match query.get("word2") {
+            Some(word2) => word2,
+            None => return Err("word2 is required".to_string()),
+        };
+
+        let word3 = match query.get("word3") {
+            Some(word3) => word3,
+            None => return Err("word3 is required".to_string()),
+        };
+
+        let word4 = match query.get("word4") {
+            Some(word4) => word4,
+            None => return Err("word4 is required".to_string()),
+        };
+
+        Ok(FourGramInput {
+            word1: sanitize_word(word1),
+            word2: sanitize_word(word2),
+            word3: sanitize_word(word3),
+            word4: sanitize_word(word4),
+        })
+    }
+}
+
+impl Queryable for FourGramInput {
+    fn to_vec(&self) -> Vec<&str> {
+        vec![&self.word1, &self.word2, &self.word3, &self.word4]
+    }
+
+    fn get_query(&self, index: Option<i32>) -> Result<&str, String> {
+        match index {
+            Some(index) => match index {
+                1 => Ok(GET_BY_SECOND_THIRD_AND_FOURTH_4),
+                2 => Ok(GET_BY_FIRST_THIRD_AND_FOURTH_4),
+                3 => Ok(GET_BY_FIRST_SECOND_AND_FOURTH_4),
+                4 => Ok(GET_BY_FIRST_SECOND_AND_THIRD_4),
+                _ => Err("Invalid index".to_string()),
+            },
+            None => Ok(GET_FREQ_4),
+        }
+    }
+
+    fn get_input(&self, index: i32) -> Result<Vec<&String>, String> {
+        match index {
+            1 => Ok(vec![&self.word2, &self.word3, &self.word4]),
+            2 => Ok(vec![&self.word1, &self.word3, &self.word4]),
+            3 => Ok(vec![&self.word1, &self.word2, &self.word4]),
+            4 => Ok(vec![&self.word1, &self.word2, &self.word3]),
+            _ => Err("Invalid index".to_string()),
+        }
+    }
+
+    fn get_word(&self, index: i32) -> Result<&String, String> {
+        match index {
+            1 => Ok(&self.word1),
+            2 => Ok(&self.word2),
+            3 => Ok(&self.word3),
+            4 => Ok(&self.word4),
+            _ => Err("Invalid index".to_string()),
+        }
+    }
+}
+
+impl Printable for FourGramInput {
+    fn print(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.word1, self.word2, self.word3, self.word4
+        )
+    }
+}
+
+/// Validates the indexes.
+///
+/// # Arguments
+///
+/// * `indexes` - The indexes to validate.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the indexes are valid, otherwise a `String` with the error message.
+pub fn validate(indexes: &Vec<i32>) -> Result<(), String> {
+    let mut new = vec![];
+    for index in indexes {
+        if *index < 1 || *index > 4 {
+            return Err("Invalid index".to_string());
+        }
+        if new.contains(index) {
+            return Err("Invalid index".to_string());
+        }
+        new.push(*index);
+    }
+    if new.len() != indexes.len() {
+        return Err("Invalid index".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn four_gram() -> FourGramInput {
+        FourGramInput {
+            word1: "hello".to_string(),
+            word2: "world".to_string(),
+            word3: "foo".to_string(),
+            word4: "bar".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_creating_four_gram_input() {
+        let mut query = HashMap::new();
+
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+        query.insert("word4".to_string(), "bar".to_string());
+
+        let four_gram = FourGramInput::from(&query).unwrap();
+
+        assert_eq!(four_gram.word4, "bar");
+    }
+
+    #[test]
+    fn test_creating_four_gram_input_fail() {
+        let mut query = HashMap::new();
+
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+
+        let four_gram = FourGramInput::from(&query);
+
+        assert!(four_gram.is_err());
+    }
+
+    #[test]
+    fn test_four_gram_to_vec() {
+        let four_gram = four_gram();
+
+        let vec = four_gram.to_vec();
+
+        assert_eq!(vec, vec!["hello", "world", "foo", "bar"]);
+    }
+
+    #[test]
+    fn test_four_gram_get_query() {
+        let four_gram = four_gram();
+
+        assert_eq!(
+            four_gram.get_query(Some(1)).unwrap(),
+            GET_BY_SECOND_THIRD_AND_FOURTH_4
+        );
+        assert_eq!(
+            four_gram.get_query(Some(4)).unwrap(),
+            GET_BY_FIRST_SECOND_AND_THIRD_4
+        );
+    }
+
+    #[test]
+    fn test_four_gram_get_query_freq() {
+        let four_gram = four_gram();
+
+        let query = four_gram.get_query(None).unwrap();
+
+        assert_eq!(query, GET_FREQ_4);
+    }
+
+    #[test]
+    fn test_four_gram_get_input() {
+        let four_gram = four_gram();
+
+        let input = four_gram.get_input(3).unwrap();
+
+        assert_eq!(input, vec![&"hello", &"world", &"bar"]);
+    }
+
+    #[test]
+    fn test_four_gram_get_word() {
+        let four_gram = four_gram();
+
+        let word = four_gram.get_word(4).unwrap();
+
+        assert_eq!(word, &"bar");
+    }
+
+    #[test]
+    fn test_four_gram_print() {
+        let print = four_gram().print();
+
+        assert_eq!(print, "hello world foo bar");
+    }
+
+    #[test]
+    fn test_validate() {
+        let indexes = vec![1, 2, 3, 4];
+
+        let result = validate(&indexes);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_fail_index_out_of_bounds() {
+        let indexes = vec![1, 5];
+
+        let result = validate(&indexes);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_fail_index_duplicate() {
+        let indexes = vec![1, 4, 4];
+
+        let result = validate(&indexes);
+
+        assert!(result.is_err());
+    }
+}
@@ -1,17 +1,352 @@
 use super::{
+    five_grams::model::{validate as validate_indexes_5, FiveGramInput},
+    four_grams::model::{validate as validate_indexes_4, FourGramInput},
+    normalizer::{DiacriticsFoldingNormalizer, Normalizer},
+    one_grams::model::{validate as validate_indexes_1, OneGramInput},
     three_grams::model::{validate as validate_indexes_3, ThreeGramInput},
     two_grams::model::{validate as validate_indexes_2, TwoGramInput},
-    vary_n_gram::VaryingQueryResult,
-    word_freq_pair::DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
+    vary_n_gram::{VaryingOptions, VaryingQueryResult},
+    word_freq_pair::{WordFreqPair, DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS},
     Printable, Queryable,
 };
-use crate::{error_handler::HttpError, parse_amount, parse_varying_indexes};
+use crate::{
+    db::NgramStore, error_handler::HttpError, parse_amount, parse_min_freq, parse_offset,
+    parse_sort_order, parse_varying_indexes, SortOrder, CSV_CONTENT_TYPE,
+};
 use actix_web::HttpResponse;
-use scylla::Session;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
 
 /// supported n-grams
-pub static SUPPORTED_N_GRAMS: [i32; 2] = [2, 3];
+pub static SUPPORTED_N_GRAMS: [i32; 5] = [1, 2, 3, 4, 5];
+
+/// the upper bound of the n-grams of one bulk lookup
+pub static MAX_BULK_NGRAMS: usize = 1000;
+
+/// An n-gram of the bulk lookup
+///
+/// # Fields
+///
+/// * `n` - the order of the n-gram
+/// * `words` - the words of the n-gram, exactly `n` of them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BulkNgram {
+    pub n: i32,
+    pub words: Vec<String>,
+}
+
+/// The result of an n-gram of the bulk lookup
+///
+/// # Fields
+///
+/// * `ngram` - the n-gram, its words joined by spaces
+/// * `frequency` - the frequency of the n-gram, `None` if the lookup failed
+/// * `error` - the error of the lookup, if it failed
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct BulkNgramResult {
+    pub ngram: String,
+    pub frequency: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The frequency of a compared n-gram
+///
+/// # Fields
+///
+/// * `ngram` - the n-gram, its words joined by spaces
+/// * `frequency` - the frequency of the n-gram, zero if it is not stored
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct NgramFrequency {
+    pub ngram: String,
+    pub frequency: i32,
+}
+
+/// The comparison of two n-grams
+///
+/// # Fields
+///
+/// * `a` - the first n-gram
+/// * `b` - the second n-gram
+/// * `ratio` - the frequency of `a` divided by the frequency of `b`, `None` if `b` has zero frequency
+/// * `more_frequent` - `a` or `b`, `None` if the frequencies are equal
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NgramComparison {
+    pub a: NgramFrequency,
+    pub b: NgramFrequency,
+    pub ratio: Option<f64>,
+    pub more_frequent: Option<String>,
+}
+
+/// A frequency range of the distribution and the number of collocates in it
+///
+/// # Fields
+///
+/// * `min` - the lowest frequency of the range
+/// * `max` - the highest frequency of the range, `None` for the last, open range
+/// * `count` - the number of collocates with a frequency in the range
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct FrequencyBucket {
+    pub min: i32,
+    pub max: Option<i32>,
+    pub count: usize,
+}
+
+/// The distribution of the collocate frequencies of a varying index
+///
+/// # Fields
+///
+/// * `ngram` - the queried n-gram
+/// * `varying_index` - the varying index
+/// * `collocates` - the number of distinct collocates
+/// * `buckets` - the collocates counted by their frequency range
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrequencyDistribution {
+    pub ngram: String,
+    pub varying_index: i32,
+    pub collocates: usize,
+    pub buckets: Vec<FrequencyBucket>,
+}
+
+/// Get the default bucket boundaries, the powers of ten up to the largest frequency
+///
+/// # Returns
+///
+/// * `Vec<i32>` - `1, 10, 100, ..., 1000000000`
+pub fn default_bucket_boundaries() -> Vec<i32> {
+    (0..10).map(|exponent| 10_i32.pow(exponent)).collect()
+}
+
+/// Parse the bucket boundaries, the highest frequency of every range
+///
+/// # Arguments
+///
+/// * `buckets` - the comma separated boundaries, e.g. `1,10,100`
+///
+/// # Returns
+///
+/// * `Result<Vec<i32>, String>` - the boundaries if they are positive and increasing, otherwise the error message
+pub fn parse_bucket_boundaries(buckets: &str) -> Result<Vec<i32>, String> {
+    let boundaries = buckets
+        .split(',')
+        .map(|boundary| match boundary.trim().parse::<i32>() {
+            Ok(boundary) if boundary > 0 => Ok(boundary),
+            _ => Err(format!(
+                "Invalid bucket boundary: {}, expected a positive integer",
+                boundary
+            )),
+        })
+        .collect::<Result<Vec<i32>, String>>()?;
+
+    if boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("The bucket boundaries must be increasing".to_string());
+    }
+
+    Ok(boundaries)
+}
+
+/// Count the pairs by the frequency range
+///
+/// Every boundary closes a range, `1,10,100` gives the ranges 1, 2-10, 11-100 and above 100.
+///
+/// # Arguments
+///
+/// * `pairs` - the word frequency pairs
+/// * `boundaries` - the increasing bucket boundaries
+///
+/// # Returns
+///
+/// * `Vec<FrequencyBucket>` - one bucket per boundary and the open bucket above the last one
+pub fn bucket_frequencies(pairs: &[WordFreqPair], boundaries: &[i32]) -> Vec<FrequencyBucket> {
+    let mut buckets: Vec<FrequencyBucket> = boundaries
+        .iter()
+        .enumerate()
+        .map(|(i, max)| FrequencyBucket {
+            min: i.checked_sub(1).map_or(1, |prev| boundaries[prev] + 1),
+            max: Some(*max),
+            count: 0,
+        })
+        .collect();
+    buckets.push(FrequencyBucket {
+        min: boundaries.last().map_or(1, |last| last.saturating_add(1)),
+        max: None,
+        count: 0,
+    });
+
+    for pair in pairs {
+        buckets[boundaries.partition_point(|max| *max < pair.frequency)].count += 1;
+    }
+
+    buckets
+}
+
+/// Parse an n-gram spec, its words separated by whitespace
+///
+/// # Arguments
+///
+/// * `spec` - the n-gram spec, e.g. `sam gledao film`
+///
+/// # Returns
+///
+/// * `BulkNgram` - the n-gram, with the order of its number of words
+pub fn parse_ngram_spec(spec: &str) -> BulkNgram {
+    let words: Vec<String> = spec
+        .split_whitespace()
+        .map(|word| word.to_string())
+        .collect();
+
+    BulkNgram {
+        n: words.len() as i32,
+        words,
+    }
+}
+
+/// Compare the frequencies of two n-grams
+///
+/// # Arguments
+///
+/// * `a` - the first n-gram
+/// * `b` - the second n-gram
+/// * `store` - the n-gram store
+/// * `normalizer` - normalizes the words before querying
+///
+/// # Returns
+///
+/// * `Result<NgramComparison, String>` - the comparison, otherwise the error message of the
+///   n-gram that could not be looked up
+pub async fn compare(
+    a: &BulkNgram,
+    b: &BulkNgram,
+    store: &dyn NgramStore,
+    normalizer: &dyn Normalizer,
+) -> Result<NgramComparison, String> {
+    let (a, b) = futures::try_join!(
+        lookup_one(a, store, normalizer),
+        lookup_one(b, store, normalizer)
+    )?;
+
+    let ratio = match b.provided_n_gram_frequency {
+        0 => None,
+        b_frequency => Some(a.provided_n_gram_frequency as f64 / b_frequency as f64),
+    };
+
+    let more_frequent = match a
+        .provided_n_gram_frequency
+        .cmp(&b.provided_n_gram_frequency)
+    {
+        std::cmp::Ordering::Greater => Some("a".to_string()),
+        std::cmp::Ordering::Less => Some("b".to_string()),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    Ok(NgramComparison {
+        a: NgramFrequency {
+            ngram: a.provided_n_gram,
+            frequency: a.provided_n_gram_frequency,
+        },
+        b: NgramFrequency {
+            ngram: b.provided_n_gram,
+            frequency: b.provided_n_gram_frequency,
+        },
+        ratio,
+        more_frequent,
+    })
+}
+
+/// Look up the frequency of one n-gram of the bulk lookup
+///
+/// The words are passed as `word1` to `wordN`, so they are sanitized like the ones of `/n-gram`.
+///
+/// # Arguments
+///
+/// * `n_gram` - the n-gram
+/// * `store` - the n-gram store
+/// * `normalizer` - normalizes the words before querying
+///
+/// # Returns
+///
+/// * `Result<VaryingQueryResult, String>` - the result of the n-gram, otherwise the error message
+async fn lookup_one(
+    n_gram: &BulkNgram,
+    store: &dyn NgramStore,
+    normalizer: &dyn Normalizer,
+) -> Result<VaryingQueryResult, String> {
+    if !SUPPORTED_N_GRAMS.contains(&n_gram.n) {
+        return Err(format!("{}-grams are not supported", n_gram.n));
+    }
+
+    if n_gram.words.len() != n_gram.n as usize {
+        return Err(format!(
+            "Expected {} words, got {}",
+            n_gram.n,
+            n_gram.words.len()
+        ));
+    }
+
+    let query: HashMap<String, String> = n_gram
+        .words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| (format!("word{}", i + 1), word.clone()))
+        .collect();
+
+    match n_gram.n {
+        1 => VaryingQueryResult::get_one(store, OneGramInput::from(&query)?, normalizer).await,
+        2 => VaryingQueryResult::get_one(store, TwoGramInput::from(&query)?, normalizer).await,
+        3 => VaryingQueryResult::get_one(store, ThreeGramInput::from(&query)?, normalizer).await,
+        4 => VaryingQueryResult::get_one(store, FourGramInput::from(&query)?, normalizer).await,
+        5 => VaryingQueryResult::get_one(store, FiveGramInput::from(&query)?, normalizer).await,
+        _ => unreachable!("The n-gram is not supported"),
+    }
+}
+
+/// Look up the frequencies of the n-grams
+///
+/// The lookups run concurrently, at most `max_concurrency` at once, and a failed lookup only
+/// sets the error of its own result.
+///
+/// # Arguments
+///
+/// * `n_grams` - the n-grams
+/// * `store` - the n-gram store
+/// * `normalizer` - normalizes the words before querying
+/// * `max_concurrency` - the upper bound of the lookups running at once
+///
+/// # Returns
+///
+/// * `Vec<BulkNgramResult>` - the results, in the order of the n-grams
+pub async fn bulk_lookup(
+    n_grams: Vec<BulkNgram>,
+    store: Arc<dyn NgramStore>,
+    normalizer: Arc<dyn Normalizer>,
+    max_concurrency: usize,
+) -> Vec<BulkNgramResult> {
+    stream::iter(n_grams)
+        .map(|n_gram| {
+            let store = Arc::clone(&store);
+            let normalizer = Arc::clone(&normalizer);
+            async move {
+                match lookup_one(&n_gram, &*store, &*normalizer).await {
+                    Ok(result) => BulkNgramResult {
+                        ngram: result.provided_n_gram,
+                        frequency: Some(result.provided_n_gram_frequency),
+                        error: None,
+                    },
+                    Err(err) => BulkNgramResult {
+                        ngram: n_gram.words.join(" "),
+                        frequency: None,
+                        error: Some(err),
+                    },
+                }
+            }
+        })
+        .buffered(max_concurrency.max(1))
+        .collect()
+        .await
+}
 
 /// Query parameters for n-gram queries
 ///
@@ -20,10 +355,21 @@ pub static SUPPORTED_N_GRAMS: [i32; 2] = [2, 3];
 /// * `n_gram` - the n-gram to query
 /// * `varying_indexes` - the indexes to vary
 /// * `amount` - the amount of word frequency pairs to return
+/// * `offset` - the number of word frequency pairs to skip before the amount is applied
+/// * `min_freq` - the minimum frequency of the word frequency pairs to return
+/// * `order` - the order of the word frequency pairs by the frequency
+/// * `strict` - whether a failed varying index fails the whole query
+/// * `fold_diacritics` - whether the diacritics of the words are folded to ASCII before querying,
+///   best-effort as the corpus keeps the diacritics
 pub struct NgramQueryParams<T: Queryable> {
     pub n_gram: T,
     pub varying_indexes: Option<Vec<i32>>,
     pub amount: i32,
+    pub offset: usize,
+    pub min_freq: i32,
+    pub order: SortOrder,
+    pub strict: bool,
+    pub fold_diacritics: bool,
 }
 
 impl<T> NgramQueryParams<T>
@@ -35,51 +381,136 @@ where
     /// # Arguments
     ///
     /// * `input` - the query parameters
-    /// * `session` - the scylla session
+    /// * `store` - the n-gram store
+    /// * `number_of_ngrams` - the number of n-grams, used for the corpus total
+    /// * `normalizer` - normalizes the words before querying
+    /// * `csv` - whether the result is returned as CSV instead of JSON
     ///
     /// # Returns
     ///
     /// * `HttpResponse` - the response
     pub async fn execute(
         input: NgramQueryParams<T>,
-        session: Arc<Session>,
+        store: Arc<dyn NgramStore>,
+        number_of_ngrams: &HashMap<i32, i64>,
+        normalizer: Arc<dyn Normalizer>,
+        csv: bool,
     ) -> Result<HttpResponse, HttpError> {
+        let normalizer: Arc<dyn Normalizer> = match input.fold_diacritics {
+            true => Arc::new(DiacriticsFoldingNormalizer { inner: normalizer }),
+            false => normalizer,
+        };
+
         match input.varying_indexes {
             Some(indexes) => {
-                let s = Arc::clone(&session);
-
-                let result =
-                    VaryingQueryResult::get_varying(s, input.n_gram, indexes, input.amount).await;
+                let result = VaryingQueryResult::get_varying(
+                    store,
+                    input.n_gram,
+                    indexes,
+                    VaryingOptions {
+                        amount: input.amount,
+                        offset: input.offset,
+                        min_freq: input.min_freq,
+                        order: input.order,
+                    },
+                    normalizer,
+                    input.strict,
+                )
+                .await;
 
                 let result = match result {
-                    Ok(result) => Ok(result),
+                    Ok(result) => Ok(result.with_corpus_total(number_of_ngrams)),
                     Err(e) => Err(e.to_string()),
                 };
 
                 match result {
-                    Ok(result) => Ok(HttpResponse::Ok().json(result)),
+                    Ok(result) => Ok(varying_response(&result, csv)),
                     Err(e) => {
-                        eprintln!("{}", e);
-                        Ok(HttpResponse::BadRequest().json(e))
+                        tracing::error!(error = %e, "varying n-gram query failed");
+                        Err(HttpError::from_query(e))
                     }
                 }
             }
             None => {
-                let s = Arc::clone(&session);
-                let three_gram = VaryingQueryResult::get_one(s, input.n_gram).await;
+                let three_gram =
+                    VaryingQueryResult::get_one(&*store, input.n_gram, &*normalizer).await;
 
                 let three_gram = match three_gram {
                     Ok(three_gram) => three_gram,
                     Err(e) => {
-                        eprintln!("{}", e);
-                        return Ok(HttpResponse::BadRequest().json(e));
+                        tracing::error!(error = %e, "n-gram query failed");
+                        return Err(HttpError::from_query(e));
                     }
                 };
 
-                Ok(HttpResponse::Ok().json(three_gram))
+                Ok(varying_response(
+                    &three_gram.with_corpus_total(number_of_ngrams),
+                    csv,
+                ))
             }
         }
     }
+
+    /// Get the distribution of the collocate frequencies of the varying index
+    ///
+    /// The collocates are the word frequency pairs of `WordFreqPair::from`, so a very common
+    /// context is counted from the first `MAX_ROWS_SCANNED` rows only.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - the query parameters, with exactly one varying index
+    /// * `store` - the n-gram store
+    /// * `normalizer` - normalizes the words before querying
+    /// * `boundaries` - the increasing bucket boundaries
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FrequencyDistribution, String>` - the distribution, otherwise the error message
+    pub async fn distribution(
+        input: NgramQueryParams<T>,
+        store: &dyn NgramStore,
+        normalizer: Arc<dyn Normalizer>,
+        boundaries: &[i32],
+    ) -> Result<FrequencyDistribution, String> {
+        let varying_index = match input.varying_indexes.as_deref() {
+            Some([index]) => *index,
+            Some(_) => return Err("Exactly one varying index is supported".to_string()),
+            None => return Err("vary is required".to_string()),
+        };
+
+        let normalizer: Arc<dyn Normalizer> = match input.fold_diacritics {
+            true => Arc::new(DiacriticsFoldingNormalizer { inner: normalizer }),
+            false => normalizer,
+        };
+
+        let pairs = WordFreqPair::from(store, &varying_index, &input.n_gram, &*normalizer).await?;
+
+        Ok(FrequencyDistribution {
+            ngram: input.n_gram.print(),
+            varying_index,
+            collocates: pairs.len(),
+            buckets: bucket_frequencies(&pairs, boundaries),
+        })
+    }
+}
+
+/// Serialize the query result
+///
+/// # Arguments
+///
+/// * `result` - the query result
+/// * `csv` - whether the result is returned as CSV instead of JSON
+///
+/// # Returns
+///
+/// * `HttpResponse` - the response
+fn varying_response(result: &VaryingQueryResult, csv: bool) -> HttpResponse {
+    match csv {
+        true => HttpResponse::Ok()
+            .content_type(CSV_CONTENT_TYPE)
+            .body(result.to_csv()),
+        false => HttpResponse::Ok().json(result),
+    }
 }
 
 /// Trait for creating query parameters from a hashmap
@@ -102,71 +533,94 @@ pub trait FromQueryParams {
         Self: Sized;
 }
 
-impl FromQueryParams for NgramQueryParams<ThreeGramInput> {
-    fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<ThreeGramInput>, String> {
-        let varying_indexes = match query.get("vary") {
-            Some(vary) => match parse_varying_indexes(vary, validate_indexes_3) {
-                Ok(indexes) => Some(indexes),
-                Err(e) => return Err(e),
-            },
-            None => None,
-        };
-        let amount = match query.get("amount") {
-            Some(amount) => match parse_amount(amount) {
-                Ok(amount) => amount,
-                Err(e) => return Err(e),
-            },
-            None => DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
-        };
-        let three_gram = ThreeGramInput::from(&query);
+/// Create the query parameters from a hashmap, shared by all the n-gram inputs
+///
+/// # Arguments
+///
+/// * `query` - the query parameters
+/// * `validate` - validates the varying indexes for the order of the n-gram
+/// * `from` - creates the n-gram from the query parameters
+///
+/// # Returns
+///
+/// * `Result<NgramQueryParams<T>, String>` - the query parameters
+fn create_params<T: Queryable>(
+    query: HashMap<String, String>,
+    validate: fn(&Vec<i32>) -> Result<(), String>,
+    from: fn(&HashMap<String, String>) -> Result<T, String>,
+) -> Result<NgramQueryParams<T>, String> {
+    let varying_indexes = match query.get("vary") {
+        Some(vary) => Some(parse_varying_indexes(vary, validate)?),
+        None => None,
+    };
+    let amount = match query.get("amount") {
+        Some(amount) => parse_amount(amount)?,
+        None => DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
+    };
+    let offset = match query.get("offset") {
+        Some(offset) => parse_offset(offset)?,
+        None => 0,
+    };
+    let min_freq = match query.get("min_freq") {
+        Some(min_freq) => parse_min_freq(min_freq)?,
+        None => 0,
+    };
+    let order = match query.get("order") {
+        Some(order) => parse_sort_order(order)?,
+        None => SortOrder::Desc,
+    };
+    let strict = query.get("strict").is_some_and(|strict| strict == "true");
+    let fold_diacritics = query
+        .get("fold_diacritics")
+        .is_some_and(|fold_diacritics| fold_diacritics == "true");
+    let n_gram = from(&query)?;
 
-        let three_gram = match three_gram {
-            Ok(three_gram) => three_gram,
-            Err(e) => return Err(e),
-        };
+    Ok(NgramQueryParams {
+        n_gram,
+        varying_indexes,
+        amount,
+        offset,
+        min_freq,
+        order,
+        strict,
+        fold_diacritics,
+    })
+}
 
-        Ok(NgramQueryParams {
-            n_gram: three_gram,
-            varying_indexes,
-            amount,
-        })
+impl FromQueryParams for NgramQueryParams<FiveGramInput> {
+    fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<FiveGramInput>, String> {
+        create_params(query, validate_indexes_5, FiveGramInput::from)
+    }
+}
+
+impl FromQueryParams for NgramQueryParams<FourGramInput> {
+    fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<FourGramInput>, String> {
+        create_params(query, validate_indexes_4, FourGramInput::from)
+    }
+}
+
+impl FromQueryParams for NgramQueryParams<ThreeGramInput> {
+    fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<ThreeGramInput>, String> {
+        create_params(query, validate_indexes_3, ThreeGramInput::from)
     }
 }
 
 impl FromQueryParams for NgramQueryParams<TwoGramInput> {
     fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<TwoGramInput>, String> {
-        let varying_indexes = match query.get("vary") {
-            Some(vary) => match parse_varying_indexes(vary, validate_indexes_2) {
-                Ok(indexes) => Some(indexes),
-                Err(e) => return Err(e),
-            },
-            None => None,
-        };
-        let amount = match query.get("amount") {
-            Some(amount) => match parse_amount(amount) {
-                Ok(amount) => amount,
-                Err(e) => return Err(e),
-            },
-            None => DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS,
-        };
-        let two_gram = TwoGramInput::from(&query);
-
-        let two_gram = match two_gram {
-            Ok(two_gram) => two_gram,
-            Err(e) => return Err(e),
-        };
+        create_params(query, validate_indexes_2, TwoGramInput::from)
+    }
+}
 
-        Ok(NgramQueryParams {
-            n_gram: two_gram,
-            varying_indexes,
-            amount,
-        })
+impl FromQueryParams for NgramQueryParams<OneGramInput> {
+    fn create(query: HashMap<String, String>) -> Result<NgramQueryParams<OneGramInput>, String> {
+        create_params(query, validate_indexes_1, OneGramInput::from)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{db::InMemoryStore, n_grams::normalizer::IdentityNormalizer};
 
     #[test]
     fn test_creating_three_gram_query_params() {
@@ -177,9 +631,18 @@ mod tests {
         query.insert("vary".to_string(), "1,2".to_string());
         query.insert("amount".to_string(), "10".to_string());
 
-        let result = NgramQueryParams::<ThreeGramInput>::create(query);
+        let result = NgramQueryParams::<ThreeGramInput>::create(query.clone());
 
         assert!(result.is_ok());
+        assert!(!result.unwrap().strict);
+
+        query.insert("strict".to_string(), "true".to_string());
+
+        assert!(
+            NgramQueryParams::<ThreeGramInput>::create(query)
+                .unwrap()
+                .strict
+        );
     }
 
     #[test]
@@ -220,6 +683,104 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_creating_four_gram_query_params() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+        query.insert("word4".to_string(), "bar".to_string());
+        query.insert("vary".to_string(), "1,4".to_string());
+
+        let result = NgramQueryParams::<FourGramInput>::create(query);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_creating_four_gram_query_params_invalid_index() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+        query.insert("word4".to_string(), "bar".to_string());
+        query.insert("vary".to_string(), "5".to_string());
+
+        let result = NgramQueryParams::<FourGramInput>::create(query);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_creating_five_gram_query_params() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+        query.insert("word4".to_string(), "bar".to_string());
+        query.insert("word5".to_string(), "baz".to_string());
+        query.insert("vary".to_string(), "5".to_string());
+        query.insert("amount".to_string(), "10".to_string());
+
+        let result = NgramQueryParams::<FiveGramInput>::create(query);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_creating_five_gram_query_params_fail() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("word3".to_string(), "foo".to_string());
+        query.insert("word4".to_string(), "bar".to_string());
+
+        let result = NgramQueryParams::<FiveGramInput>::create(query);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_creating_one_gram_query_params() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "sam".to_string());
+
+        let result = NgramQueryParams::<OneGramInput>::create(query);
+
+        assert!(result.is_ok());
+        assert!(result.unwrap().varying_indexes.is_none());
+    }
+
+    #[test]
+    fn test_creating_one_gram_query_params_with_vary_fail() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "sam".to_string());
+        query.insert("vary".to_string(), "1".to_string());
+
+        let result = NgramQueryParams::<OneGramInput>::create(query);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_creating_two_gram_query_params_min_freq() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("vary".to_string(), "2".to_string());
+        query.insert("min_freq".to_string(), "5".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query.clone());
+
+        assert_eq!(result.unwrap().min_freq, 5);
+
+        query.insert("min_freq".to_string(), "-1".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query);
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_creating_three_gram_query_params_without_amount() {
         let mut query = HashMap::new();
@@ -233,4 +794,239 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap().amount == DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS);
     }
+
+    #[test]
+    fn test_creating_two_gram_query_params_fold_diacritics() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "Krleža".to_string());
+        query.insert("word2".to_string(), "piše".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query.clone()).unwrap();
+
+        assert!(!result.fold_diacritics);
+
+        query.insert("fold_diacritics".to_string(), "true".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query).unwrap();
+
+        assert!(result.fold_diacritics);
+    }
+
+    #[test]
+    fn test_creating_two_gram_query_params_offset() {
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "hello".to_string());
+        query.insert("word2".to_string(), "world".to_string());
+        query.insert("vary".to_string(), "1".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query.clone()).unwrap();
+
+        assert_eq!(result.offset, 0);
+
+        query.insert("offset".to_string(), "20".to_string());
+
+        let result = NgramQueryParams::<TwoGramInput>::create(query.clone()).unwrap();
+
+        assert_eq!(result.offset, 20);
+
+        query.insert("offset".to_string(), "-1".to_string());
+
+        assert!(NgramQueryParams::<TwoGramInput>::create(query).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_lookup() {
+        let store = Arc::new(InMemoryStore::new(HashMap::from([
+            ("od mene".to_string(), 12),
+            ("od mene zahtjeva".to_string(), 3),
+        ])));
+
+        let n_grams = vec![
+            BulkNgram {
+                n: 2,
+                words: vec!["od".to_string(), "mene".to_string()],
+            },
+            BulkNgram {
+                n: 3,
+                words: vec!["od".to_string(), "mene".to_string()],
+            },
+            BulkNgram {
+                n: 6,
+                words: vec!["od".to_string()],
+            },
+            BulkNgram {
+                n: 3,
+                words: vec!["od".to_string(), "mene".to_string(), "zahtjeva".to_string()],
+            },
+            BulkNgram {
+                n: 1,
+                words: vec!["nema".to_string()],
+            },
+        ];
+
+        let results = bulk_lookup(n_grams, store, Arc::new(IdentityNormalizer), 2).await;
+
+        assert_eq!(
+            results,
+            vec![
+                BulkNgramResult {
+                    ngram: "od mene".to_string(),
+                    frequency: Some(12),
+                    error: None,
+                },
+                BulkNgramResult {
+                    ngram: "od mene".to_string(),
+                    frequency: None,
+                    error: Some("Expected 3 words, got 2".to_string()),
+                },
+                BulkNgramResult {
+                    ngram: "od".to_string(),
+                    frequency: None,
+                    error: Some("6-grams are not supported".to_string()),
+                },
+                BulkNgramResult {
+                    ngram: "od mene zahtjeva".to_string(),
+                    frequency: Some(3),
+                    error: None,
+                },
+                BulkNgramResult {
+                    ngram: "nema".to_string(),
+                    frequency: Some(0),
+                    error: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_bucket_boundaries() {
+        assert_eq!(parse_bucket_boundaries("1, 10,100"), Ok(vec![1, 10, 100]));
+        assert_eq!(default_bucket_boundaries()[9], 1_000_000_000);
+        assert!(parse_bucket_boundaries("1,0").is_err());
+        assert!(parse_bucket_boundaries("10,1").is_err());
+        assert!(parse_bucket_boundaries("1,,10").is_err());
+    }
+
+    #[test]
+    fn test_bucket_frequencies() {
+        let pairs: Vec<WordFreqPair> = [1, 2, 10, 11, 100, 101, 5000]
+            .iter()
+            .map(|frequency| WordFreqPair::new(frequency.to_string(), *frequency))
+            .collect();
+
+        let buckets = bucket_frequencies(&pairs, &[1, 10, 100]);
+
+        assert_eq!(
+            buckets,
+            vec![
+                FrequencyBucket {
+                    min: 1,
+                    max: Some(1),
+                    count: 1
+                },
+                FrequencyBucket {
+                    min: 2,
+                    max: Some(10),
+                    count: 2
+                },
+                FrequencyBucket {
+                    min: 11,
+                    max: Some(100),
+                    count: 2
+                },
+                FrequencyBucket {
+                    min: 101,
+                    max: None,
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_distribution() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("ja sam gledao".to_string(), 120),
+            ("on sam gledao".to_string(), 3),
+            ("ti sam gledao".to_string(), 7),
+            ("mi sam gledao".to_string(), 1),
+        ]));
+
+        let mut query = HashMap::new();
+        query.insert("word1".to_string(), "ja".to_string());
+        query.insert("word2".to_string(), "sam".to_string());
+        query.insert("word3".to_string(), "gledao".to_string());
+        query.insert("vary".to_string(), "1".to_string());
+
+        let input = NgramQueryParams::<ThreeGramInput>::create(query.clone()).unwrap();
+        let result = NgramQueryParams::distribution(
+            input,
+            &store,
+            Arc::new(IdentityNormalizer),
+            &default_bucket_boundaries(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.collocates, 4);
+        assert_eq!(result.buckets.len(), 11);
+        let counts: Vec<usize> = result.buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts[..4], [1, 2, 0, 1]);
+
+        query.insert("vary".to_string(), "1,3".to_string());
+
+        let input = NgramQueryParams::<ThreeGramInput>::create(query).unwrap();
+
+        assert!(NgramQueryParams::distribution(
+            input,
+            &store,
+            Arc::new(IdentityNormalizer),
+            &default_bucket_boundaries(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compare() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("sam gledao film".to_string(), 30),
+            ("sam gledala film".to_string(), 10),
+        ]));
+
+        let gledao = parse_ngram_spec(" sam  gledao film");
+        let gledala = parse_ngram_spec("sam gledala film");
+        let gledalo = parse_ngram_spec("sam gledalo film");
+
+        assert_eq!(gledao.n, 3);
+
+        let comparison = compare(&gledao, &gledala, &store, &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        assert_eq!(comparison.a.ngram, "sam gledao film");
+        assert_eq!(comparison.b.frequency, 10);
+        assert_eq!(comparison.ratio, Some(3.0));
+        assert_eq!(comparison.more_frequent.as_deref(), Some("a"));
+
+        let comparison = compare(&gledao, &gledalo, &store, &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        assert_eq!(comparison.ratio, None);
+        assert_eq!(comparison.more_frequent.as_deref(), Some("a"));
+
+        let comparison = compare(&gledalo, &gledalo, &store, &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        assert_eq!(comparison.ratio, None);
+        assert_eq!(comparison.more_frequent, None);
+
+        assert!(
+            compare(&parse_ngram_spec(""), &gledao, &store, &IdentityNormalizer)
+                .await
+                .is_err()
+        );
+    }
 }
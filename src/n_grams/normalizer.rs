@@ -0,0 +1,118 @@
+use crate::normalize_diacritics;
+use std::sync::Arc;
+
+/// Reduces a word to the form the corpus was stored in.
+///
+/// # Methods
+///
+/// * `normalize` - Normalizes the word.
+pub trait Normalizer: Send + Sync {
+    /// Normalizes the word.
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - The word.
+    ///
+    /// # Returns
+    ///
+    /// The normalized word.
+    fn normalize(&self, word: &str) -> String;
+}
+
+/// Leaves the words as they are, used when the corpus is not normalized.
+pub struct IdentityNormalizer;
+
+impl Normalizer for IdentityNormalizer {
+    fn normalize(&self, word: &str) -> String {
+        word.to_string()
+    }
+}
+
+/// Lowercases the words and trims the surrounding whitespace.
+pub struct LowercaseNormalizer;
+
+impl Normalizer for LowercaseNormalizer {
+    fn normalize(&self, word: &str) -> String {
+        word.trim().to_lowercase()
+    }
+}
+
+/// Folds the diacritics of the words after normalizing them with the wrapped normalizer.
+///
+/// # Fields
+///
+/// * `inner` - The wrapped normalizer.
+pub struct DiacriticsFoldingNormalizer {
+    pub inner: Arc<dyn Normalizer>,
+}
+
+impl Normalizer for DiacriticsFoldingNormalizer {
+    fn normalize(&self, word: &str) -> String {
+        normalize_diacritics(&self.inner.normalize(word))
+    }
+}
+
+/// Gets the normalizer for the given mode.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether the corpus is normalized.
+///
+/// # Returns
+///
+/// The `LowercaseNormalizer` if enabled, otherwise the `IdentityNormalizer`.
+pub fn get_normalizer(enabled: bool) -> Arc<dyn Normalizer> {
+    match enabled {
+        true => Arc::new(LowercaseNormalizer),
+        false => Arc::new(IdentityNormalizer),
+    }
+}
+
+/// Normalizes all the words.
+///
+/// # Arguments
+///
+/// * `normalizer` - The normalizer.
+/// * `words` - The words.
+///
+/// # Returns
+///
+/// A `Vec<String>` containing the normalized words.
+pub fn normalize_all(normalizer: &dyn Normalizer, words: &[&str]) -> Vec<String> {
+    words
+        .iter()
+        .map(|word| normalizer.normalize(word))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_identity_normalizer() {
+        assert_eq!(IdentityNormalizer.normalize(" Sam"), " Sam");
+    }
+
+    #[test]
+    fn test_diacritics_folding_normalizer() {
+        let normalizer = DiacriticsFoldingNormalizer {
+            inner: get_normalizer(true),
+        };
+
+        assert_eq!(normalizer.normalize(" Krleža"), "krleza");
+    }
+
+    #[test]
+    fn test_normalized_query_hits_normalized_corpus() {
+        let corpus = HashMap::from([("ja sam".to_string(), 10)]);
+        let words = ["Ja", "SAM "];
+
+        let raw = normalize_all(&*get_normalizer(false), &words).join(" ");
+        let normalized = normalize_all(&*get_normalizer(true), &words).join(" ");
+
+        assert!(!corpus.contains_key(&raw));
+        assert_eq!(corpus.get(&normalized), Some(&10));
+    }
+}
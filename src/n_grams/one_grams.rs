@@ -0,0 +1,6 @@
+/// This module contains the one grams of the application
+///
+/// # Modules
+///
+/// * `model` - Contains the model of the one grams.
+pub mod model;
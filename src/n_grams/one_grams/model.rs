@@ -0,0 +1,149 @@
+use crate::{
+    db::GET_FREQ_1,
+    n_grams::{Printable, Queryable},
+    sanitize_word,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Represents the one gram that is given as input.
+///
+/// # Fields
+///
+/// * `word` - The word.
+///
+/// # Implements
+///
+/// * `Queryable` - Provides methods to query the database.
+/// * `Printable` - Provides method for printing.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OneGramInput {
+    pub word: String,
+}
+
+impl OneGramInput {
+    /// Creates a new `OneGramInput` from the given query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query that contains the word as `word1`.
+    ///
+    /// The word is sanitized, see `sanitize_word`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `OneGramInput` if the query is valid, otherwise a `String` with the error message.
+    pub fn from(query: &HashMap<String, String>) -> Result<OneGramInput, String> {
+        let word = match query.get("word1") {
+            Some(word) => word,
+            None => return Err("word1 is required".to_string()),
+        };
+
+        Ok(OneGramInput {
+            word: sanitize_word(word),
+        })
+    }
+}
+
+impl Queryable for OneGramInput {
+    fn to_vec(&self) -> Vec<&str> {
+        vec![&self.word]
+    }
+
+    fn get_query(&self, index: Option<i32>) -> Result<&str, String> {
+        match index {
+            Some(_) => Err("Invalid index".to_string()),
+            None => Ok(GET_FREQ_1),
+        }
+    }
+
+    fn get_input(&self, _index: i32) -> Result<Vec<&String>, String> {
+        Err("Invalid index".to_string())
+    }
+
+    fn get_word(&self, index: i32) -> Result<&String, String> {
+        match index {
+            1 => Ok(&self.word),
+            _ => Err("Invalid index".to_string()),
+        }
+    }
+}
+
+impl Printable for OneGramInput {
+    fn print(&self) -> String {
+        self.word.to_string()
+    }
+}
+
+/// Validates the indexes.
+///
+/// A single word has no context to vary against, so no indexes are valid.
+///
+/// # Arguments
+///
+/// * `indexes` - The indexes to validate.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the indexes are valid, otherwise a `String` with the error message.
+#[allow(clippy::ptr_arg)]
+pub fn validate(indexes: &Vec<i32>) -> Result<(), String> {
+    match indexes.is_empty() {
+        true => Ok(()),
+        false => Err("Varying is not supported for 1-grams".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_creating_one_gram_input() {
+        let mut query = HashMap::new();
+
+        query.insert("word1".to_string(), "sam".to_string());
+
+        let one_gram = OneGramInput::from(&query).unwrap();
+
+        assert_eq!(one_gram.word, "sam");
+    }
+
+    #[test]
+    fn test_creating_one_gram_input_fail() {
+        let query = HashMap::new();
+
+        let one_gram = OneGramInput::from(&query);
+
+        assert!(one_gram.is_err());
+    }
+
+    #[test]
+    fn test_one_gram_get_query() {
+        let one_gram = OneGramInput {
+            word: "sam".to_string(),
+        };
+
+        assert_eq!(one_gram.get_query(None).unwrap(), GET_FREQ_1);
+        assert!(one_gram.get_query(Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_one_gram_to_vec_and_print() {
+        let one_gram = OneGramInput {
+            word: "sam".to_string(),
+        };
+
+        assert_eq!(one_gram.to_vec(), vec!["sam"]);
+        assert_eq!(one_gram.print(), "sam");
+    }
+
+    #[test]
+    fn test_validate_fail() {
+        let indexes = vec![1];
+
+        let result = validate(&indexes);
+
+        assert!(result.is_err());
+    }
+}
@@ -1,26 +1,70 @@
 use crate::{
     error_handler::HttpError,
     n_grams::{
-        model::{FromQueryParams, NgramQueryParams, SUPPORTED_N_GRAMS},
+        five_grams, four_grams,
+        model::{
+            bulk_lookup, compare, default_bucket_boundaries, parse_bucket_boundaries,
+            parse_ngram_spec, BulkNgram, FrequencyDistribution, FromQueryParams, NgramQueryParams,
+            MAX_BULK_NGRAMS, SUPPORTED_N_GRAMS,
+        },
+        one_grams,
         solver::{
-            model::{execute_queries, SolverWithConfusionSet},
-            predictor::{predict, MaxPredictor, PowerSumPredictor, SumPredictor},
+            correction::correct,
+            ensemble::vote,
+            model::{
+                max_concurrency, sentence_boundaries, ExecuteOptions, TimedSentenceResults,
+                DEFAULT_WINDOW,
+            },
+            pipeline::{Pipeline, StoreExecutor},
+            predictor::{
+                get_predictor, predict, MaxPredictor, PowerSumPredictor, Predict,
+                PredictionResults, SumPredictor, DEFAULT_POWER, DEFAULT_PREDICTOR,
+            },
+            shares::shares,
+            AnalyzeOptions,
         },
-        three_grams, two_grams,
+        three_grams, two_grams, Printable, Queryable,
     },
-    parse_n, AppData, FormData,
+    parse_n, parse_score_scale, parse_top_k, select_groups, validate_distinct_counts,
+    validate_request_confusion_set, AppData, FormData, LoadedData, CSV_CONTENT_TYPE,
+    NDJSON_CONTENT_TYPE,
 };
 use actix_web::{
-    get, post,
-    web::{self, Form},
-    Error, HttpResponse,
+    get,
+    http::header,
+    post,
+    web::{self, Form, Json},
+    Either, HttpRequest, HttpResponse,
 };
-use std::{collections::HashMap, sync::Arc};
+use futures::StreamExt;
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+#[cfg(feature = "protobuf")]
+use crate::n_grams::solver::proto;
+
+/// Checks whether the client accepts CSV.
+///
+/// # Arguments
+///
+/// * `req` - The request.
+///
+/// # Returns
+///
+/// `true` if the `Accept` header contains `text/csv`.
+fn accepts_csv(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(CSV_CONTENT_TYPE))
+}
 
 /// Handles the n-gram query.
 ///
+/// With `Accept: text/csv` the solutions are returned as `index,word,frequency` rows instead of JSON.
+///
 /// # Arguments
 ///
+/// * `req` - The request.
 /// * `query` - The query parameters.
 /// * `data` - The application data.
 ///
@@ -28,56 +72,274 @@ use std::{collections::HashMap, sync::Arc};
 ///
 /// * `HttpResponse` - The response.
 #[get("/n-gram")]
+#[tracing::instrument(name = "get_n_gram", skip(req, data), fields(route = "/n-gram"))]
 async fn get_n_gram(
+    req: HttpRequest,
     query: web::Query<HashMap<String, String>>,
     data: web::Data<AppData>,
 ) -> Result<HttpResponse, HttpError> {
-    let session = Arc::clone(&data.scy_session);
-
     let query = query.into_inner().clone();
+    let csv = accepts_csv(&req);
+    let loaded = data.loaded();
 
-    let n = match parse_n(&query) {
-        Ok(n) => n,
-        Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
-    };
+    let n = parse_n(&query)?;
 
     if !SUPPORTED_N_GRAMS.contains(&n) {
-        return Ok(HttpResponse::BadRequest().json(format!("{}-grams are not supported", n)));
+        return Err(HttpError::from(format!("{}-grams are not supported", n)));
     }
 
     match n {
-        2 => {
-            let query_params =
-                match NgramQueryParams::<two_grams::model::TwoGramInput>::create(query) {
-                    Ok(query_params) => query_params,
-                    Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
-                };
+        1 => lookup::<one_grams::model::OneGramInput>(query, &data, &loaded, csv).await,
+        2 => lookup::<two_grams::model::TwoGramInput>(query, &data, &loaded, csv).await,
+        3 => lookup::<three_grams::model::ThreeGramInput>(query, &data, &loaded, csv).await,
+        4 => lookup::<four_grams::model::FourGramInput>(query, &data, &loaded, csv).await,
+        5 => lookup::<five_grams::model::FiveGramInput>(query, &data, &loaded, csv).await,
+        _ => {
+            unreachable!("The n-gram is not supported");
+        }
+    }
+}
+
+/// Runs the n-gram query of the order of `T`.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+/// * `loaded` - The loaded n-gram counts.
+/// * `csv` - Whether the solutions are returned as CSV instead of JSON.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+async fn lookup<T>(
+    query: HashMap<String, String>,
+    data: &AppData,
+    loaded: &LoadedData,
+    csv: bool,
+) -> Result<HttpResponse, HttpError>
+where
+    T: Queryable + Printable + Clone + Send + Sync + 'static,
+    NgramQueryParams<T>: FromQueryParams,
+{
+    let query_params = NgramQueryParams::<T>::create(query)?;
 
-            let result = NgramQueryParams::execute(query_params, session).await;
+    NgramQueryParams::execute(
+        query_params,
+        Arc::clone(&data.lookup_store),
+        &loaded.number_of_ngrams,
+        Arc::clone(&data.normalizer),
+        csv,
+    )
+    .await
+}
 
-            result
-        }
-        3 => {
-            let query_params =
-                match NgramQueryParams::<three_grams::model::ThreeGramInput>::create(query) {
-                    Ok(query_params) => query_params,
-                    Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
-                };
+/// Handles the bulk n-gram lookup.
+///
+/// The body is a JSON array of n-grams, e.g. `[{"n": 2, "words": ["od", "mene"]}]`, and the
+/// response holds their `{ngram, frequency}` results in the same order. The lookups run
+/// concurrently, bounded by `SOLVER_MAX_CONCURRENCY`, and an n-gram that can not be looked up
+/// reports its `error` without failing the others.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+/// * `n_grams` - The n-grams.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+///
+/// # Errors
+///
+/// If there are more than `MAX_BULK_NGRAMS` n-grams, a `HttpResponse` with the error message will be returned.
+#[post("/n-gram/bulk")]
+#[tracing::instrument(
+    name = "get_n_grams_bulk",
+    skip(data, n_grams),
+    fields(route = "/n-gram/bulk")
+)]
+async fn get_n_grams_bulk(
+    data: web::Data<AppData>,
+    n_grams: Json<Vec<BulkNgram>>,
+) -> Result<HttpResponse, HttpError> {
+    let n_grams = n_grams.into_inner();
 
-            let result = NgramQueryParams::execute(query_params, session).await;
+    if n_grams.len() > MAX_BULK_NGRAMS {
+        return Err(HttpError::from(format!(
+            "At most {} n-grams can be looked up at once",
+            MAX_BULK_NGRAMS
+        )));
+    }
 
-            result
-        }
+    let results = bulk_lookup(
+        n_grams,
+        Arc::clone(&data.lookup_store),
+        Arc::clone(&data.normalizer),
+        max_concurrency(),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Handles the comparison of two n-grams.
+///
+/// The `a` and `b` query parameters hold the words of the n-grams separated by spaces, e.g.
+/// `a=sam gledao film&b=sam gledala film`. The response has the frequency of both, the `ratio` of
+/// `a` to `b`, `null` if `b` has zero frequency, and which of them is `more_frequent`, `null` on a tie.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+///
+/// # Errors
+///
+/// If an n-gram is missing or unsupported, a `HttpResponse` with the error message will be returned.
+/// If a lookup fails, a `HttpResponse` with the error message will be returned.
+#[get("/n-gram/compare")]
+#[tracing::instrument(
+    name = "compare_n_grams",
+    skip(data),
+    fields(route = "/n-gram/compare")
+)]
+async fn compare_n_grams(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse, HttpError> {
+    let a = match query.get("a") {
+        Some(a) => parse_ngram_spec(a),
+        None => return Err(HttpError::from("a is required".to_string())),
+    };
+
+    let b = match query.get("b") {
+        Some(b) => parse_ngram_spec(b),
+        None => return Err(HttpError::from("b is required".to_string())),
+    };
+
+    let res = compare(&a, &b, &*data.lookup_store, &*data.normalizer)
+        .await
+        .inspect_err(|err| tracing::error!(error = %err, "n-gram comparison failed"))
+        .map_err(HttpError::from_query)?;
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Handles the distribution of the collocate frequencies.
+///
+/// Takes the parameters of `/n-gram` with exactly one varying index in `vary`, and counts the
+/// collocates of that index by their frequency. The optional `buckets` query parameter holds the
+/// comma separated, increasing boundaries of the ranges, e.g. `1,10,100` counts the collocates
+/// with the frequency 1, 2-10, 11-100 and above 100. The powers of ten are used by default.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+///
+/// # Errors
+///
+/// If the n-gram, the varying index or the buckets are invalid, a `HttpResponse` with the error message will be returned.
+/// If the query can not be executed, a `HttpResponse` with the error message will be returned.
+#[get("/n-gram/distribution")]
+#[tracing::instrument(
+    name = "get_n_gram_distribution",
+    skip(data),
+    fields(route = "/n-gram/distribution")
+)]
+async fn get_n_gram_distribution(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse, HttpError> {
+    let query = query.into_inner();
+
+    let n = parse_n(&query)?;
+
+    if !SUPPORTED_N_GRAMS.contains(&n) {
+        return Err(HttpError::from(format!("{}-grams are not supported", n)));
+    }
+
+    let boundaries = match query.get("buckets") {
+        Some(buckets) => parse_bucket_boundaries(buckets)?,
+        None => default_bucket_boundaries(),
+    };
+
+    let result = match n {
+        1 => distribution::<one_grams::model::OneGramInput>(query, &data, &boundaries).await,
+        2 => distribution::<two_grams::model::TwoGramInput>(query, &data, &boundaries).await,
+        3 => distribution::<three_grams::model::ThreeGramInput>(query, &data, &boundaries).await,
+        4 => distribution::<four_grams::model::FourGramInput>(query, &data, &boundaries).await,
+        5 => distribution::<five_grams::model::FiveGramInput>(query, &data, &boundaries).await,
         _ => {
             unreachable!("The n-gram is not supported");
         }
-    }
+    };
+
+    let result = result
+        .inspect_err(|err| tracing::error!(error = %err, "distribution query failed"))
+        .map_err(HttpError::from_query)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Gets the distribution of the collocate frequencies of the n-gram of the query.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+/// * `boundaries` - The bucket boundaries.
+///
+/// # Returns
+///
+/// A `Result` containing the distribution, otherwise a `String` with the error message.
+async fn distribution<T>(
+    query: HashMap<String, String>,
+    data: &AppData,
+    boundaries: &[i32],
+) -> Result<FrequencyDistribution, String>
+where
+    T: Queryable + Printable + Clone + Send + Sync + 'static,
+    NgramQueryParams<T>: FromQueryParams,
+{
+    let input = NgramQueryParams::<T>::create(query)?;
+
+    NgramQueryParams::distribution(
+        input,
+        &*data.lookup_store,
+        Arc::clone(&data.normalizer),
+        boundaries,
+    )
+    .await
 }
 
 /// Handles the text check.
 ///
+/// The payload can be sent either as JSON or as a form, nested fields like `distinct_counts` are only available with JSON.
+/// The optional `top_k` query parameter limits every context to its best scoring candidates.
+/// The optional `score_scale` query parameter returns the scores multiplied by the scale and truncated to integers.
+/// The optional `groups` query parameter limits the analysis to the comma separated confusion group labels.
+/// With the JSON `confusion_set` field the request is analyzed with its own confusion groups instead
+/// of the loaded ones, it can not be combined with `groups` and is not counted in the metrics.
+/// With `verbose=true` every context also reports how many n-grams Scylla returned for it.
+/// With `explain=true` every candidate also reports the terms it was scored from, for the `max`, `sum` and `lidstone` predictors.
+/// The predictor is chosen with the `predictor` field, `max` by default.
+/// With the `protobuf` feature, `Accept: application/x-protobuf` returns the protobuf encoding.
+/// With `Accept: text/csv` the scores are returned as `context,word_examined,candidate,score` rows.
+///
 /// # Arguments
 ///
+/// * `req` - The request.
+/// * `query` - The query parameters.
 /// * `form` - The form data.
 /// * `data` - The application data.
 ///
@@ -88,65 +350,558 @@ async fn get_n_gram(
 /// # Errors
 ///
 /// If the payload can not be read, a `HttpResponse` with the error message will be returned.
-/// If the queries can not be executed, a `HttpResponse` with the error message will be returned.
+/// If the predictor is unknown, a `HttpResponse` with the error message will be returned.
+/// If the custom confusion set is invalid, a `HttpResponse` with the error message will be returned.
+/// If the queries can not be executed, a `HttpResponse` with the error message will be returned,
+/// with the status code 504 if they timed out.
 #[post("/check")]
-async fn check_text(data: web::Data<AppData>, form: Form<FormData>) -> Result<HttpResponse, Error> {
-    let form = form.into_inner();
+#[tracing::instrument(name = "check_text", skip(req, data, form), fields(route = "/check"))]
+async fn check_text(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
 
-    let obj = match SolverWithConfusionSet::new(form.text, &data.confusion_set) {
-        Ok(obj) => obj,
-        Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
-    };
+    let top_k = query
+        .get("top_k")
+        .map(|top_k| parse_top_k(top_k))
+        .transpose()?;
 
-    let queries = obj.find_queries();
+    let score_scale = query
+        .get("score_scale")
+        .map(|score_scale| parse_score_scale(score_scale))
+        .transpose()?;
 
-    let session = Arc::clone(&data.scy_session);
+    let verbose = query
+        .get("verbose")
+        .is_some_and(|verbose| verbose == "true");
 
-    let result = execute_queries(queries, session).await;
+    let explain = query
+        .get("explain")
+        .is_some_and(|explain| explain == "true");
 
-    let i = 0;
+    let custom = form.confusion_set.is_some();
 
-    let res = match i {
-        0 => {
-            let predictor = MaxPredictor {};
+    let confusion_set =
+        request_confusion_set(&loaded, form.confusion_set.take(), query.get("groups"))?;
 
-            Ok(predict(
-                predictor,
-                result,
-                data.confusion_set.clone(),
-                data.number_of_ngrams.clone(),
-                data.number_of_distinct_ngrams.clone(),
-            ))
-        }
-        1 => {
-            let predictor = SumPredictor {};
+    let predictor = get_predictor(
+        form.predictor.as_deref().unwrap_or(DEFAULT_PREDICTOR),
+        form.power,
+        form.k,
+        form.log_base,
+    )?;
+
+    let pipeline = build_pipeline(&data, &loaded, &mut form, &confusion_set, predictor)?;
 
-            Ok(predict(
-                predictor,
+    let mut res = pipeline
+        .run(form.text)
+        .await
+        .inspect_err(|err| tracing::warn!(error = %err, "check failed"))
+        .map_err(HttpError::from_query)?;
+
+    // the metrics count the loaded groups, a custom set has its own
+    if !custom {
+        loaded.metrics.record(&loaded.confusion_set, &res);
+    }
+    if let Some(top_k) = top_k {
+        res.limit_top_k(top_k);
+    }
+    if !verbose {
+        res.hide_rows_fetched();
+    }
+    if !explain {
+        res.hide_explanation();
+    }
+
+    match score_scale {
+        Some(score_scale) => Ok(HttpResponse::Ok().json(res.scaled(score_scale))),
+        None if accepts_csv(&req) => Ok(HttpResponse::Ok()
+            .content_type(CSV_CONTENT_TYPE)
+            .body(res.to_csv())),
+        None => Ok(prediction_response(&req, &res)),
+    }
+}
+
+/// Serializes the predictions, as protobuf if the client accepts it.
+///
+/// # Arguments
+///
+/// * `req` - The request.
+/// * `res` - The prediction results.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+#[cfg(feature = "protobuf")]
+fn prediction_response(req: &HttpRequest, res: &PredictionResults) -> HttpResponse {
+    let accepts_protobuf = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(proto::PROTOBUF_CONTENT_TYPE));
+
+    match accepts_protobuf {
+        true => HttpResponse::Ok()
+            .content_type(proto::PROTOBUF_CONTENT_TYPE)
+            .body(proto::encode(res)),
+        false => HttpResponse::Ok().json(res),
+    }
+}
+
+/// Serializes the predictions as JSON.
+///
+/// # Arguments
+///
+/// * `_req` - The request.
+/// * `res` - The prediction results.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+#[cfg(not(feature = "protobuf"))]
+fn prediction_response(_req: &HttpRequest, res: &PredictionResults) -> HttpResponse {
+    HttpResponse::Ok().json(res)
+}
+
+/// Handles the text check with all the predictors.
+///
+/// Every predictor scores the same query results, and for each context the candidate picked by
+/// the majority of them is reported as the consensus. The confusion set of the form replaces the
+/// loaded one, like in `/check`.
+///
+/// # Arguments
+///
+/// * `form` - The form data.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+#[post("/check/ensemble")]
+async fn check_text_ensemble(
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
+
+    let confusion_set = request_confusion_set(&loaded, form.confusion_set.take(), None)?;
+
+    let (result, number_of_distinct_ngrams) = solve(&data, &loaded, form, &confusion_set)
+        .await
+        .map_err(HttpError::from_query)?;
+
+    let predictions = vec![
+        (
+            "max".to_string(),
+            predict(
+                &MaxPredictor::default(),
+                result.clone(),
+                &confusion_set,
+                &loaded.number_of_ngrams,
+                &number_of_distinct_ngrams,
+            ),
+        ),
+        (
+            "sum".to_string(),
+            predict(
+                &SumPredictor::default(),
+                result.clone(),
+                &confusion_set,
+                &loaded.number_of_ngrams,
+                &number_of_distinct_ngrams,
+            ),
+        ),
+        (
+            "power_sum".to_string(),
+            predict(
+                &PowerSumPredictor {
+                    power: DEFAULT_POWER,
+                },
                 result,
-                data.confusion_set.clone(),
-                data.number_of_ngrams.clone(),
-                data.number_of_distinct_ngrams.clone(),
-            ))
+                &confusion_set,
+                &loaded.number_of_ngrams,
+                &number_of_distinct_ngrams,
+            ),
+        ),
+    ];
+
+    let res = vote(predictions);
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Handles the text check with the shares of the candidates.
+///
+/// Every candidate's context frequency is returned as its share of the total over the
+/// candidates of the context, without smoothing and unigram factors. The confusion set of the
+/// form replaces the loaded one, like in `/check`.
+///
+/// # Arguments
+///
+/// * `form` - The form data.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+#[post("/check/shares")]
+async fn check_text_shares(
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
+
+    let confusion_set = request_confusion_set(&loaded, form.confusion_set.take(), None)?;
+
+    let (result, _) = solve(&data, &loaded, form, &confusion_set)
+        .await
+        .map_err(HttpError::from_query)?;
+
+    Ok(HttpResponse::Ok().json(shares(&result, &confusion_set)))
+}
+
+/// Handles the text check with the corrected text.
+///
+/// For every examined word the candidate with the lowest score is substituted into the text,
+/// keeping the capitalization of the original word. The confusion set of the form replaces the
+/// loaded one, like in `/check`.
+///
+/// # Arguments
+///
+/// * `form` - The form data.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response.
+#[post("/check/correct")]
+async fn check_text_correct(
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
+
+    let confusion_set = request_confusion_set(&loaded, form.confusion_set.take(), None)?;
+
+    let predictor = get_predictor(
+        form.predictor.as_deref().unwrap_or(DEFAULT_PREDICTOR),
+        form.power,
+        form.k,
+        form.log_base,
+    )?;
+
+    let pipeline = build_pipeline(&data, &loaded, &mut form, &confusion_set, predictor)?;
+
+    let (solver, res) = pipeline
+        .run_with_solver(form.text)
+        .await
+        .map_err(HttpError::from_query)?;
+
+    Ok(HttpResponse::Ok().json(correct(&solver.text, &solver.find_occurrences(), &res)))
+}
+
+/// Handles the dry run of the text check, the queries are planned but not executed.
+///
+/// The confusion set of the form replaces the loaded one, like in `/check`.
+///
+/// # Arguments
+///
+/// * `form` - The form data.
+/// * `data` - The application data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The response, the planned queries of every examined word in its context.
+#[post("/check/plan")]
+async fn check_text_plan(
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
+
+    let confusion_set = request_confusion_set(&loaded, form.confusion_set.take(), None)?;
+
+    // the queries are not executed, the predictor of the pipeline is not used
+    let pipeline = build_pipeline(
+        &data,
+        &loaded,
+        &mut form,
+        &confusion_set,
+        Box::new(MaxPredictor::default()),
+    )?;
+
+    Ok(HttpResponse::Ok().json(pipeline.plan(form.text)?))
+}
+
+/// Handles the streamed text check.
+///
+/// The queries of the whole text are run at once, and every prediction result is sent as a JSON
+/// line as soon as the queries of its context are done. A query that fails is sent as an
+/// `{"error": ...}` line, its contexts are scored without it. The fields of the form and the
+/// `groups`, `top_k`, `score_scale`, `verbose` and `explain` query parameters are the ones of `/check`.
+///
+/// # Arguments
+///
+/// * `query` - The query parameters.
+/// * `data` - The application data.
+/// * `form` - The form data.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The `application/x-ndjson` response with one prediction result per line.
+///
+/// # Errors
+///
+/// If the predictor is unknown, a `HttpResponse` with the error message will be returned.
+/// If the custom confusion set or the groups are invalid, a `HttpResponse` with the error message will be returned.
+/// If the distinct n-gram counts are invalid, a `HttpResponse` with the error message will be returned.
+#[post("/check/stream")]
+async fn check_text_stream(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppData>,
+    form: Either<Json<FormData>, Form<FormData>>,
+) -> Result<HttpResponse, HttpError> {
+    let mut form = form.into_inner();
+    let loaded = data.loaded();
+
+    let top_k = query
+        .get("top_k")
+        .map(|top_k| parse_top_k(top_k))
+        .transpose()?;
+
+    let score_scale = query
+        .get("score_scale")
+        .map(|score_scale| parse_score_scale(score_scale))
+        .transpose()?;
+
+    let verbose = query
+        .get("verbose")
+        .is_some_and(|verbose| verbose == "true");
+
+    let explain = query
+        .get("explain")
+        .is_some_and(|explain| explain == "true");
+
+    // the metrics count the loaded groups, a custom set has its own
+    let metrics = match form.confusion_set.is_some() {
+        true => None,
+        false => Some(Arc::clone(&loaded)),
+    };
+
+    let confusion_set =
+        request_confusion_set(&loaded, form.confusion_set.take(), query.get("groups"))?;
+
+    let predictor = get_predictor(
+        form.predictor.as_deref().unwrap_or(DEFAULT_PREDICTOR),
+        form.power,
+        form.k,
+        form.log_base,
+    )?;
+
+    let pipeline = build_pipeline(&data, &loaded, &mut form, &confusion_set, predictor)?;
+
+    // the response outlives the handler, so the stream keeps its own copy of the loaded data
+    let results = pipeline.into_owned().stream(form.text)?;
+
+    let lines = results.map(move |result| {
+        let mut line = match result {
+            Ok(mut result) => {
+                if let Some(loaded) = &metrics {
+                    loaded.metrics.record_result(&loaded.confusion_set, &result);
+                }
+                if let Some(top_k) = top_k {
+                    result.limit_top_k(top_k);
+                }
+                if !verbose {
+                    result.rows_fetched = None;
+                }
+                if !explain {
+                    result.explanation = None;
+                }
+                match score_scale {
+                    Some(score_scale) => serde_json::to_vec(&result.scaled(score_scale))?,
+                    None => serde_json::to_vec(&result)?,
+                }
+            }
+            Err(err) => serde_json::to_vec(&serde_json::json!({ "error": err }))?,
+        };
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(web::Bytes::from(line))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(NDJSON_CONTENT_TYPE)
+        .streaming(lines))
+}
+
+/// Builds the `/check` pipeline for the form, with the default stages.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+/// * `loaded` - The loaded confusion set and n-gram counts.
+/// * `form` - The form data, its distinct n-gram counts are moved into the pipeline.
+/// * `confusion_set` - The confusion groups to analyze.
+/// * `predictor` - The predictor.
+///
+/// # Returns
+///
+/// A `Result` containing the pipeline if the form is valid, otherwise a `String` with the error message.
+fn build_pipeline<'a>(
+    data: &'a AppData,
+    loaded: &'a LoadedData,
+    form: &mut FormData,
+    confusion_set: &'a [Vec<String>],
+    predictor: Box<dyn Predict>,
+) -> Result<Pipeline<'a, StoreExecutor>, String> {
+    let number_of_distinct_ngrams = distinct_counts(data, loaded, form.distinct_counts.take())?;
+    let options = analyze_options(loaded, form)?;
+
+    Ok(Pipeline::from_options(
+        Arc::clone(&data.store),
+        predictor,
+        Cow::Borrowed(confusion_set),
+        Cow::Borrowed(&loaded.number_of_ngrams),
+        number_of_distinct_ngrams,
+        options,
+    ))
+}
+
+/// Gets the confusion set of the request, the loaded one if not overridden.
+///
+/// # Arguments
+///
+/// * `loaded` - The loaded confusion set and n-gram counts.
+/// * `confusion_set` - The custom confusion set of the form, if any.
+/// * `groups` - The comma separated labels of the loaded groups to select, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the confusion set to analyze, otherwise a `String` with the error message
+/// if the custom confusion set is invalid, the groups are unknown or both are given.
+fn request_confusion_set<'a>(
+    loaded: &'a LoadedData,
+    confusion_set: Option<Vec<Vec<String>>>,
+    groups: Option<&String>,
+) -> Result<Cow<'a, [Vec<String>]>, String> {
+    match (confusion_set, groups) {
+        (Some(_), Some(_)) => {
+            Err("The groups can not be selected with a custom confusion set".to_string())
+        }
+        (Some(confusion_set), None) => {
+            validate_request_confusion_set(&confusion_set)?;
+            Ok(Cow::Owned(confusion_set))
         }
-        2 => {
-            let predictor = PowerSumPredictor { power: 0.5 };
+        (None, Some(groups)) => Ok(Cow::Owned(select_groups(
+            &loaded.confusion_set,
+            &loaded.group_labels,
+            groups,
+        )?)),
+        (None, None) => Ok(Cow::Borrowed(&loaded.confusion_set)),
+    }
+}
 
-            Ok(predict(
-                predictor,
-                result,
-                data.confusion_set.clone(),
-                data.number_of_ngrams.clone(),
-                data.number_of_distinct_ngrams.clone(),
-            ))
+/// Gets the number of distinct n-grams of the request, the configured ones if not overridden.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+/// * `loaded` - The loaded confusion set and n-gram counts.
+/// * `distinct_counts` - The distinct n-gram counts of the form, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the number of distinct n-grams if they are valid, otherwise a `String` with the error message.
+fn distinct_counts<'a>(
+    data: &'a AppData,
+    loaded: &LoadedData,
+    distinct_counts: Option<HashMap<i32, i64>>,
+) -> Result<Cow<'a, HashMap<i32, i64>>, String> {
+    match distinct_counts {
+        Some(distinct_counts) => {
+            validate_distinct_counts(&distinct_counts, &loaded.number_of_ngrams)?;
+            Ok(Cow::Owned(distinct_counts))
         }
-        _ => Err(HttpError::new(500, "Internal server error".to_string())),
+        None => Ok(Cow::Borrowed(&data.number_of_distinct_ngrams)),
+    }
+}
+
+/// Gets the options of the analysis of the form.
+///
+/// # Arguments
+///
+/// * `loaded` - The loaded confusion set and n-gram counts.
+/// * `form` - The form data.
+///
+/// # Returns
+///
+/// A `Result` containing the `AnalyzeOptions` of the form, otherwise a `String` with the error
+/// message if the edit candidates are asked for without a loaded vocabulary.
+fn analyze_options(loaded: &LoadedData, form: &FormData) -> Result<AnalyzeOptions, String> {
+    let vocabulary = match form.edit_candidates.unwrap_or(false) {
+        true => match &loaded.vocabulary {
+            Some(vocabulary) => Some(Arc::clone(vocabulary)),
+            None => {
+                return Err(
+                    "No vocabulary is loaded, set VOCABULARY_FILE to use edit_candidates"
+                        .to_string(),
+                )
+            }
+        },
+        false => None,
     };
 
-    match res {
-        Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::BadRequest().json(err)),
-    }
+    Ok(AnalyzeOptions {
+        execute: ExecuteOptions {
+            early_stop: form.early_stop,
+            skip_backfill: form.skip_backfill.unwrap_or(false),
+        },
+        canonical_words: loaded.canonical_words.clone(),
+        window: form.window.unwrap_or(DEFAULT_WINDOW),
+        skip_grams: form.skip_grams.unwrap_or(false),
+        sentence_boundaries: sentence_boundaries(),
+        vocabulary,
+    })
+}
+
+/// Runs the solver queries for the text of the form.
+///
+/// # Arguments
+///
+/// * `data` - The application data.
+/// * `loaded` - The loaded confusion set and n-gram counts.
+/// * `form` - The form data.
+/// * `confusion_set` - The confusion groups to analyze.
+///
+/// # Returns
+///
+/// A `Result` containing the timed sentence results and the distinct n-gram counts to smooth with, otherwise a `String` with the error message.
+async fn solve<'a>(
+    data: &'a AppData,
+    loaded: &'a LoadedData,
+    mut form: FormData,
+    confusion_set: &'a [Vec<String>],
+) -> Result<(TimedSentenceResults, Cow<'a, HashMap<i32, i64>>), String> {
+    // the callers score the results themselves, the predictor of the pipeline is not used
+    let pipeline = build_pipeline(
+        data,
+        loaded,
+        &mut form,
+        confusion_set,
+        Box::new(MaxPredictor::default()),
+    )?;
+
+    let result = pipeline.solve(form.text).await?;
+
+    Ok((result, pipeline.number_of_distinct_ngrams))
 }
 
 /// Initializes the routes for the n-grams.
@@ -156,5 +911,13 @@ async fn check_text(data: web::Data<AppData>, form: Form<FormData>) -> Result<Ht
 /// * `cfg` - The configuration of the service.
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_n_gram);
+    cfg.service(get_n_grams_bulk);
+    cfg.service(compare_n_grams);
+    cfg.service(get_n_gram_distribution);
     cfg.service(check_text);
+    cfg.service(check_text_ensemble);
+    cfg.service(check_text_shares);
+    cfg.service(check_text_correct);
+    cfg.service(check_text_plan);
+    cfg.service(check_text_stream);
 }
@@ -12,38 +12,195 @@ pub mod model;
 /// * `predictor` - Contains the predictor of the n-grams solver.
 pub mod predictor;
 
-/// Parses the text into sentences.
+/// This module contains the implementation of the ensemble of predictors.
+///
+/// # Modules
+///
+/// * `ensemble` - Contains the voting over the predictors.
+pub mod ensemble;
+
+/// This module contains the shares of the candidates in the context frequencies.
+///
+/// # Modules
+///
+/// * `shares` - Contains the normalization of the candidate frequencies.
+pub mod shares;
+
+/// This module contains the corrected text assembled from the predictions.
+///
+/// # Modules
+///
+/// * `correction` - Contains the substitution of the best candidates into the text.
+pub mod correction;
+
+/// This module contains the `/check` pipeline with swappable stages.
+///
+/// # Modules
+///
+/// * `pipeline` - Contains the stage traits and their default implementations.
+pub mod pipeline;
+
+/// This module contains the replay of the recorded requests.
+///
+/// # Modules
+///
+/// * `replay` - Contains the recorded cases and their replay against recorded frequencies.
+pub mod replay;
+
+/// This module contains the protobuf encoding of the predictions.
+///
+/// # Modules
+///
+/// * `proto` - Contains the protobuf types, only built with the `protobuf` feature.
+#[cfg(feature = "protobuf")]
+pub mod proto;
+
+use crate::db::NgramStore;
+use futures::stream::Stream;
+use model::{ExecuteOptions, DEFAULT_WINDOW};
+use pipeline::Pipeline;
+use predictor::{Predict, PredictionResult, PredictionResults};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// Represents the options of the text analysis.
+///
+/// # Fields
+///
+/// * `execute` - The options of the query execution.
+/// * `canonical_words` - The words that are kept as they are when suggested.
+/// * `window` - The number of words on each side of the examined word.
+/// * `skip_grams` - Whether the skip-bigrams around the examined word are queried as well.
+/// * `sentence_boundaries` - Whether the sentence boundaries are queried.
+/// * `vocabulary` - The vocabulary of the edit candidates, `None` to examine only the confusion set.
+#[derive(Clone)]
+pub struct AnalyzeOptions {
+    pub execute: ExecuteOptions,
+    pub canonical_words: HashSet<String>,
+    pub window: usize,
+    pub skip_grams: bool,
+    pub sentence_boundaries: bool,
+    pub vocabulary: Option<Arc<HashSet<String>>>,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        AnalyzeOptions {
+            execute: ExecuteOptions::default(),
+            canonical_words: HashSet::new(),
+            window: DEFAULT_WINDOW,
+            skip_grams: false,
+            sentence_boundaries: false,
+            vocabulary: None,
+        }
+    }
+}
+
+/// Analyzes the text, without going through the HTTP handlers.
+///
+/// Tokenizes the text, finds the queries of the examined words, runs them against the store
+/// and scores the candidates with the predictor, see `Pipeline::run`.
 ///
 /// # Arguments
 ///
 /// * `text` - The text.
+/// * `confusion_set` - The confusion groups to analyze.
+/// * `store` - The n-gram store.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+/// * `predictor` - Scores the candidates.
+/// * `options` - The options of the analysis.
 ///
 /// # Returns
 ///
-/// A `Vec<String>` containing the sentences.
-pub fn parse_text_to_sentences(text: &str) -> Vec<String> {
-    let mut text: Vec<String> = text.split(". ").map(|s| s.to_string()).collect();
-    let mut result: Vec<String> = Vec::new();
+/// A `Result` containing the prediction results if the confusion set is not empty, otherwise a `String` with the error message.
+pub async fn analyze_text(
+    text: String,
+    confusion_set: &[Vec<String>],
+    store: Arc<dyn NgramStore>,
+    number_of_ngrams: &HashMap<i32, i64>,
+    number_of_distinct_ngrams: &HashMap<i32, i64>,
+    predictor: Box<dyn Predict>,
+    options: AnalyzeOptions,
+) -> Result<PredictionResults, String> {
+    Pipeline::from_options(
+        store,
+        predictor,
+        Cow::Borrowed(confusion_set),
+        Cow::Borrowed(number_of_ngrams),
+        Cow::Borrowed(number_of_distinct_ngrams),
+        options,
+    )
+    .run(text)
+    .await
+}
 
-    for sentence in text.iter_mut() {
-        let res: Vec<String> = sentence.split(", ").map(|s| s.to_string()).collect();
-        for s in res {
-            result.push(s);
-        }
-    }
+/// Analyzes the text like `analyze_text`, streaming the prediction of every context as soon as its
+/// queries are done, see `Pipeline::stream`.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `confusion_set` - The confusion groups to analyze.
+/// * `store` - The n-gram store.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+/// * `predictor` - Scores the candidates.
+/// * `options` - The options of the analysis.
+///
+/// # Returns
+///
+/// A `Result` containing the stream of the prediction results in the order they are done, and of the
+/// errors of the queries that failed, if the confusion set is not empty, otherwise a `String` with the error message.
+pub fn analyze_text_stream<'a>(
+    text: String,
+    confusion_set: &'a [Vec<String>],
+    store: Arc<dyn NgramStore>,
+    number_of_ngrams: &'a HashMap<i32, i64>,
+    number_of_distinct_ngrams: &'a HashMap<i32, i64>,
+    predictor: Box<dyn Predict>,
+    options: AnalyzeOptions,
+) -> Result<impl Stream<Item = Result<PredictionResult, String>> + 'a, String> {
+    Pipeline::from_options(
+        store,
+        predictor,
+        Cow::Borrowed(confusion_set),
+        Cow::Borrowed(number_of_ngrams),
+        Cow::Borrowed(number_of_distinct_ngrams),
+        options,
+    )
+    .stream(text)
+}
 
-    for sentence in result.iter_mut() {
-        if sentence.ends_with('.') {
-            sentence.pop();
-        }
-    }
+/// The characters the text is split into sentences on.
+pub static SENTENCE_DELIMITERS: [char; 6] = ['.', '?', '!', ';', ',', '\n'];
 
-    result
+/// Parses the text into sentences.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+///
+/// # Returns
+///
+/// A `Vec<String>` containing the trimmed sentences, the empty ones are left out.
+pub fn parse_text_to_sentences(text: &str) -> Vec<String> {
+    text.split(SENTENCE_DELIMITERS)
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .map(|sentence| sentence.to_string())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::InMemoryStore;
+    use futures::TryStreamExt;
+    use predictor::MaxPredictor;
 
     #[test]
     fn test_parse_text_to_sentences() {
@@ -59,5 +216,143 @@ mod tests {
             "uspio je dobiti posao",
         ];
         assert_eq!(result, expected);
+
+        assert_eq!(
+            parse_text_to_sentences("Jesi li? Da!"),
+            vec!["Jesi li", "Da"]
+        );
+        assert_eq!(
+            parse_text_to_sentences("Ona zahtijeva; on ne.\nSutra dolazim.Ana zna"),
+            vec!["Ona zahtijeva", "on ne", "Sutra dolazim", "Ana zna"]
+        );
+        assert!(parse_text_to_sentences(" ... \n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_text() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("zahtijeva".to_string(), 412870),
+            ("zahtjeva".to_string(), 96512),
+            ("mene zahtijeva".to_string(), 1204),
+            ("mene zahtjeva".to_string(), 87),
+        ]));
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+
+        let result = analyze_text(
+            "Ana sve zna. On od mene zahtjeva da dođem.".to_string(),
+            &confusion_set,
+            Arc::new(store),
+            &HashMap::from([(1, 14038305488), (2, 11860749005), (3, 10042562275)]),
+            &HashMap::from([(1, 3275612), (2, 443937190), (3, 1744646259)]),
+            Box::new(MaxPredictor::default()),
+            AnalyzeOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.sentences, 2);
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].context, "od mene zahtjeva da dođem");
+        assert!(result.results[0].results["zahtijeva"] < result.results[0].results["zahtjeva"]);
+
+        assert!(analyze_text(
+            "Ana sve zna.".to_string(),
+            &[],
+            Arc::new(InMemoryStore::new(HashMap::new())),
+            &HashMap::new(),
+            &HashMap::new(),
+            Box::new(MaxPredictor::default()),
+            AnalyzeOptions::default(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_text_with_a_custom_confusion_set() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("zahtijeva".to_string(), 412870),
+            ("zahtjeva".to_string(), 96512),
+            ("dijelom".to_string(), 5120),
+            ("djelom".to_string(), 20480),
+            ("svojim djelom".to_string(), 312),
+            ("svojim dijelom".to_string(), 41),
+        ]));
+        let confusion_set = vec![vec!["dijelom".to_string(), "djelom".to_string()]];
+
+        let result = analyze_text(
+            "On od mene zahtjeva da dođem. Krleža sve oduševio svojim dijelom.".to_string(),
+            &confusion_set,
+            Arc::new(store),
+            &HashMap::from([(1, 14038305488), (2, 11860749005), (3, 10042562275)]),
+            &HashMap::from([(1, 3275612), (2, 443937190), (3, 1744646259)]),
+            Box::new(MaxPredictor::default()),
+            AnalyzeOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].word_examined, "dijelom");
+        assert!(result.results[0].results["djelom"] < result.results[0].results["dijelom"]);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_text_stream() {
+        let counts = HashMap::from([
+            ("zahtijeva".to_string(), 412870),
+            ("zahtjeva".to_string(), 96512),
+            ("mene zahtijeva".to_string(), 1204),
+            ("mene zahtjeva".to_string(), 87),
+            ("Ana zahtjeva".to_string(), 3),
+        ]);
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let number_of_ngrams = HashMap::from([(1, 14038305488), (2, 11860749005)]);
+        let number_of_distinct_ngrams = HashMap::from([(1, 3275612), (2, 443937190)]);
+        let text = "Ana zahtjeva sve. Ana sve zna. On od mene zahtjeva da dođem.";
+
+        let expected = analyze_text(
+            text.to_string(),
+            &confusion_set,
+            Arc::new(InMemoryStore::new(counts.clone())),
+            &number_of_ngrams,
+            &number_of_distinct_ngrams,
+            Box::new(MaxPredictor::default()),
+            AnalyzeOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let streamed: Vec<PredictionResult> = analyze_text_stream(
+            text.to_string(),
+            &confusion_set,
+            Arc::new(InMemoryStore::new(counts)),
+            &number_of_ngrams,
+            &number_of_distinct_ngrams,
+            Box::new(MaxPredictor::default()),
+            AnalyzeOptions::default(),
+        )
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+        let mut contexts: Vec<&str> = streamed
+            .iter()
+            .map(|result| result.context.as_str())
+            .collect();
+        contexts.sort();
+        assert_eq!(
+            contexts,
+            vec!["Ana zahtjeva sve", "od mene zahtjeva da dođem"]
+        );
+        for result in &streamed {
+            let expected = expected
+                .results
+                .iter()
+                .find(|expected| expected.context == result.context)
+                .unwrap();
+            assert_eq!(result.results, expected.results);
+        }
     }
 }
@@ -0,0 +1,267 @@
+use crate::n_grams::solver::{
+    model::Occurrence,
+    predictor::{PredictionResult, PredictionResults},
+};
+use serde::{Deserialize, Serialize};
+
+/// Represents a substitution in the text.
+///
+/// # Fields
+///
+/// * `position` - The character offset of the replaced word in the original text.
+/// * `from` - The original word.
+/// * `to` - The suggested word, with the capitalization of the original.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Change {
+    pub position: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// Represents the corrected text.
+///
+/// # Fields
+///
+/// * `time_elapsed` - The time elapsed.
+/// * `time_elapsed_ms` - The time elapsed in milliseconds.
+/// * `text` - The text with the suggested substitutions applied.
+/// * `changes` - The substitutions, in the order of the text.
+/// * `sentences` - The number of analyzable sentences.
+/// * `errors` - The errors of the queries that failed.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionResults {
+    pub time_elapsed: String,
+    #[serde(default)]
+    pub time_elapsed_ms: u128,
+    pub text: String,
+    pub changes: Vec<Change>,
+    pub sentences: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Picks the candidate with the lowest score, the most probable one.
+///
+/// On a tie the examined word is kept.
+///
+/// # Arguments
+///
+/// * `result` - The prediction result of a context.
+///
+/// # Returns
+///
+/// The best candidate, `None` if no candidate was scored.
+pub fn best_candidate(result: &PredictionResult) -> Option<&String> {
+    let mut candidates: Vec<(&String, &f64)> = result.results.iter().collect();
+    candidates.sort_by(|(a, a_score), (b, b_score)| {
+        a_score
+            .total_cmp(b_score)
+            .then((*b == &result.word_examined).cmp(&(*a == &result.word_examined)))
+            .then(a.cmp(b))
+    });
+
+    candidates.first().map(|(candidate, _)| *candidate)
+}
+
+/// Copies the capitalization of the original word to the replacement.
+///
+/// # Arguments
+///
+/// * `original` - The original word.
+/// * `replacement` - The replacement.
+///
+/// # Returns
+///
+/// The replacement, with the first letter uppercased if the original starts with an uppercase letter.
+fn match_case(original: &str, replacement: &str) -> String {
+    if !original.chars().next().is_some_and(char::is_uppercase) {
+        return replacement.to_string();
+    }
+
+    let mut chars = replacement.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Applies the best candidate of every context to the text.
+///
+/// # Arguments
+///
+/// * `text` - The original text.
+/// * `occurrences` - The occurrences of the examined words, see `SolverWithConfusionSet::find_occurrences`.
+/// * `predictions` - The prediction results.
+///
+/// # Returns
+///
+/// The corrected text with the list of the substitutions.
+pub fn correct(
+    text: &str,
+    occurrences: &[Occurrence],
+    predictions: &PredictionResults,
+) -> CorrectionResults {
+    let mut corrected = String::with_capacity(text.len());
+    let mut changes: Vec<Change> = vec![];
+    let mut last = 0;
+
+    for occurrence in occurrences {
+        if occurrence.start < last {
+            continue;
+        }
+
+        let best = predictions
+            .results
+            .iter()
+            .find(|result| {
+                result.context == occurrence.key.context
+                    && result.word_examined == occurrence.key.word
+            })
+            .and_then(best_candidate);
+
+        let original = &text[occurrence.start..occurrence.end];
+
+        let best = match best {
+            Some(best) if best.to_lowercase() != original.to_lowercase() => best,
+            _ => continue,
+        };
+
+        let replacement = match_case(original, best);
+
+        corrected.push_str(&text[last..occurrence.start]);
+        corrected.push_str(&replacement);
+        last = occurrence.end;
+
+        changes.push(Change {
+            position: text[..occurrence.start].chars().count(),
+            from: original.to_string(),
+            to: replacement,
+        });
+    }
+
+    corrected.push_str(&text[last..]);
+
+    CorrectionResults {
+        time_elapsed: predictions.time_elapsed.clone(),
+        time_elapsed_ms: predictions.time_elapsed_ms,
+        text: corrected,
+        changes,
+        sentences: predictions.sentences,
+        errors: predictions.errors.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_grams::solver::model::SolverWithConfusionSet;
+    use std::collections::HashMap;
+
+    fn prediction(context: &str, word: &str, scores: &[(&str, f64)]) -> PredictionResult {
+        PredictionResult {
+            context: context.to_string(),
+            word_examined: word.to_string(),
+            results: scores
+                .iter()
+                .map(|(candidate, score)| (candidate.to_string(), *score))
+                .collect::<HashMap<String, f64>>(),
+            normalized: HashMap::new(),
+            unigram_only: vec![],
+            max_matched_order: HashMap::new(),
+            rows_fetched: None,
+            explanation: None,
+        }
+    }
+
+    fn predictions(results: Vec<PredictionResult>) -> PredictionResults {
+        PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results,
+            sentences: 2,
+            errors: vec![],
+            perplexity: vec![],
+        }
+    }
+
+    #[test]
+    fn test_best_candidate() {
+        let result = prediction(
+            "od mene zahtjeva da dođem",
+            "zahtjeva",
+            &[("zahtijeva", 9.8566), ("zahtjeva", 11.6398)],
+        );
+
+        assert_eq!(best_candidate(&result).unwrap(), "zahtijeva");
+
+        let tie = prediction("a", "zahtjeva", &[("zahtijeva", 1.0), ("zahtjeva", 1.0)]);
+
+        assert_eq!(best_candidate(&tie).unwrap(), "zahtjeva");
+        assert!(best_candidate(&prediction("a", "zahtjeva", &[])).is_none());
+    }
+
+    #[test]
+    fn test_match_case() {
+        assert_eq!(match_case("Zahtjeva", "zahtijeva"), "Zahtijeva");
+        assert_eq!(match_case("zahtjeva", "zahtijeva"), "zahtijeva");
+        assert_eq!(match_case("Što", "sto"), "Sto");
+    }
+
+    #[test]
+    fn test_correct() {
+        let text = "Zahtjeva da dođem. On od mene zahtjeva, da dođem.";
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(text.to_string(), &confusion_set).unwrap();
+        let occurrences = solver.find_occurrences();
+
+        assert_eq!(occurrences.len(), 2);
+
+        let predictions = predictions(vec![
+            prediction(
+                "Zahtjeva da dođem",
+                "zahtjeva",
+                &[("zahtijeva", 9.0), ("zahtjeva", 11.0)],
+            ),
+            prediction(
+                "od mene zahtjeva",
+                "zahtjeva",
+                &[("zahtijeva", 12.0), ("zahtjeva", 11.0)],
+            ),
+        ]);
+
+        let result = correct(text, &occurrences, &predictions);
+
+        assert_eq!(
+            result.text,
+            "Zahtijeva da dođem. On od mene zahtjeva, da dođem."
+        );
+        assert_eq!(
+            result.changes,
+            vec![Change {
+                position: 0,
+                from: "Zahtjeva".to_string(),
+                to: "Zahtijeva".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_correct_keeps_attached_punctuation() {
+        let text = "Čak i Ana zahtjeva!";
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(text.to_string(), &confusion_set).unwrap();
+        let occurrences = solver.find_occurrences();
+
+        let predictions = predictions(vec![prediction(
+            &occurrences[0].key.context,
+            "zahtjeva",
+            &[("zahtijeva", 9.0), ("zahtjeva", 11.0)],
+        )]);
+
+        let result = correct(text, &occurrences, &predictions);
+
+        assert_eq!(result.text, "Čak i Ana zahtijeva!");
+        assert_eq!(result.changes[0].position, 10);
+    }
+}
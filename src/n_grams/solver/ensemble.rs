@@ -0,0 +1,190 @@
+use crate::n_grams::solver::{correction::best_candidate, predictor::PredictionResults};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Represents the pick of a single predictor.
+///
+/// # Fields
+///
+/// * `predictor` - The name of the predictor.
+/// * `pick` - The best scoring candidate, `None` if the predictor had no candidates.
+#[derive(Deserialize, Serialize)]
+pub struct PredictorPick {
+    pub predictor: String,
+    pub pick: Option<String>,
+}
+
+/// Represents the ensemble result for one context.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `word_examined` - The word examined.
+/// * `picks` - The pick of every predictor.
+/// * `consensus` - The candidate picked by the majority of the predictors, if there is one.
+/// * `disagreement` - Whether the predictors picked different candidates.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleResult {
+    pub context: String,
+    pub word_examined: String,
+    pub picks: Vec<PredictorPick>,
+    pub consensus: Option<String>,
+    pub disagreement: bool,
+}
+
+/// Represents the ensemble results.
+///
+/// # Fields
+///
+/// * `time_elapsed` - The time elapsed.
+/// * `time_elapsed_ms` - The time elapsed in milliseconds.
+/// * `results` - The results.
+/// * `sentences` - The number of analyzable sentences.
+/// * `errors` - The errors of the queries that failed.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleResults {
+    pub time_elapsed: String,
+    #[serde(default)]
+    pub time_elapsed_ms: u128,
+    pub results: Vec<EnsembleResult>,
+    pub sentences: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Votes over the predictions of several predictors.
+///
+/// All predictions must come from the same `TimedSentenceResults`, so their results are in the same order.
+///
+/// # Arguments
+///
+/// * `predictions` - The name of each predictor and its prediction results.
+///
+/// # Returns
+///
+/// The ensemble results, with one entry per context.
+pub fn vote(predictions: Vec<(String, PredictionResults)>) -> EnsembleResults {
+    let (time_elapsed, time_elapsed_ms, sentences, contexts, errors) = match predictions.first() {
+        Some((_, first)) => (
+            first.time_elapsed.clone(),
+            first.time_elapsed_ms,
+            first.sentences,
+            first.results.len(),
+            first.errors.clone(),
+        ),
+        None => ("0 ms".to_string(), 0, 0, 0, vec![]),
+    };
+
+    let mut results: Vec<EnsembleResult> = vec![];
+
+    for i in 0..contexts {
+        let mut picks: Vec<PredictorPick> = vec![];
+
+        for (name, prediction) in predictions.iter() {
+            picks.push(PredictorPick {
+                predictor: name.clone(),
+                pick: best_candidate(&prediction.results[i]).cloned(),
+            });
+        }
+
+        let mut votes: HashMap<&String, usize> = HashMap::new();
+        for pick in picks.iter().filter_map(|p| p.pick.as_ref()) {
+            *votes.entry(pick).or_insert(0) += 1;
+        }
+
+        let consensus = votes
+            .iter()
+            .find(|(_, count)| **count * 2 > picks.len())
+            .map(|(candidate, _)| candidate.to_string());
+
+        let disagreement = picks.windows(2).any(|pair| pair[0].pick != pair[1].pick);
+
+        let first = &predictions[0].1.results[i];
+
+        results.push(EnsembleResult {
+            context: first.context.clone(),
+            word_examined: first.word_examined.clone(),
+            picks,
+            consensus,
+            disagreement,
+        });
+    }
+
+    EnsembleResults {
+        time_elapsed,
+        time_elapsed_ms,
+        results,
+        sentences,
+        errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_grams::solver::predictor::PredictionResult;
+
+    fn prediction(zahtijeva: f64, zahtjeva: f64) -> PredictionResults {
+        PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results: vec![PredictionResult {
+                context: "On od mene zahtjeva da".to_string(),
+                word_examined: "zahtjeva".to_string(),
+                results: HashMap::from([
+                    ("zahtijeva".to_string(), zahtijeva),
+                    ("zahtjeva".to_string(), zahtjeva),
+                ]),
+                normalized: HashMap::new(),
+                unigram_only: vec![],
+                max_matched_order: HashMap::new(),
+                rows_fetched: None,
+                explanation: None,
+            }],
+            sentences: 1,
+            errors: vec![],
+            perplexity: vec![],
+        }
+    }
+
+    #[test]
+    fn test_vote_tie_keeps_the_examined_word() {
+        let result = vote(vec![("max".to_string(), prediction(2.0, 2.0))]);
+
+        assert_eq!(
+            result.results[0].picks[0].pick,
+            Some("zahtjeva".to_string())
+        );
+    }
+
+    #[test]
+    fn test_vote_two_agree_one_disagrees() {
+        let predictions = vec![
+            ("max".to_string(), prediction(3.0, 2.0)),
+            ("sum".to_string(), prediction(2.0, 3.0)),
+            ("power_sum".to_string(), prediction(4.0, 1.0)),
+        ];
+
+        let result = vote(predictions);
+        let result = &result.results[0];
+
+        assert_eq!(result.consensus, Some("zahtjeva".to_string()));
+        assert!(result.disagreement);
+        assert_eq!(result.picks[1].pick, Some("zahtijeva".to_string()));
+    }
+
+    #[test]
+    fn test_vote_unanimous() {
+        let predictions = vec![
+            ("max".to_string(), prediction(3.0, 2.0)),
+            ("sum".to_string(), prediction(3.0, 2.0)),
+        ];
+
+        let result = vote(predictions);
+
+        assert_eq!(result.results[0].consensus, Some("zahtjeva".to_string()));
+        assert!(!result.results[0].disagreement);
+    }
+}
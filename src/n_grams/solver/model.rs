@@ -1,16 +1,23 @@
 use crate::{
-    db::{
-        get_n_gram_string, QueryError, QueryFactory, GET_ALL_VARYING_1, GET_ALL_VARYING_2_1,
-        GET_ALL_VARYING_2_2, GET_ALL_VARYING_3_1, GET_ALL_VARYING_3_3,
+    db::{get_n_gram_string, NgramStore, QueryShape, VaryingQuery},
+    n_grams::{
+        solver::parse_text_to_sentences,
+        word_freq_pair::{duplicate_policy, DuplicatePolicy},
     },
-    n_grams::solver::parse_text_to_sentences,
+    sanitize_word,
+};
+use futures::{
+    stream::{self, FuturesUnordered, Stream, StreamExt},
+    FutureExt,
 };
-use futures::stream::StreamExt;
-use scylla::{statement::Consistency, Session};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    sync::{mpsc, Arc},
+    collections::{HashMap, HashSet},
+    sync::{mpsc, Arc, OnceLock},
+};
+use tokio::{
+    sync::Semaphore,
+    task::{JoinError, JoinHandle},
 };
 
 /// Represents a query builder.
@@ -18,30 +25,116 @@ use std::{
 /// # Fields
 ///
 /// * `query` - The query.
+/// * `shape` - The shape of the query, which word of the n-gram varies.
 /// * `static_params` - The static parameters.
 /// * `varying_params` - The varying parameters.
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
 pub struct QueryBuilder {
     pub query: String,
+    pub shape: QueryShape,
     pub static_params: Vec<String>,
     pub varying_params: Vec<String>,
 }
 
+/// The default number of words on each side of the examined word.
+pub const DEFAULT_WINDOW: usize = 2;
+
+/// The largest edit distance of the candidates of a word outside the confusion set.
+pub const MAX_EDIT_DISTANCE: usize = 2;
+
+/// The upper bound of the candidates of a word outside the confusion set, the word itself not included.
+pub const MAX_EDIT_CANDIDATES: usize = 10;
+
+/// The default upper bound of the solver queries running at the same time.
+pub static DEFAULT_MAX_CONCURRENCY: usize = 64;
+
+/// The upper bound of the solver queries running at the same time, read once from `SOLVER_MAX_CONCURRENCY`.
+static MAX_CONCURRENCY: OnceLock<usize> = OnceLock::new();
+
+/// The token that marks the start of a sentence in the boundary-marked n-grams.
+pub static SENTENCE_START: &str = "<s>";
+
+/// The token that marks the end of a sentence in the boundary-marked n-grams.
+pub static SENTENCE_END: &str = "</s>";
+
+/// Whether the n-grams were built with the sentence boundaries, read once from `SENTENCE_BOUNDARIES`.
+static SENTENCE_BOUNDARIES: OnceLock<bool> = OnceLock::new();
+
+/// Gets the upper bound of the solver queries running at the same time.
+///
+/// # Returns
+///
+/// The value of `SOLVER_MAX_CONCURRENCY`, or `DEFAULT_MAX_CONCURRENCY` if it is not set or not positive.
+pub fn max_concurrency() -> usize {
+    *MAX_CONCURRENCY.get_or_init(|| {
+        std::env::var("SOLVER_MAX_CONCURRENCY")
+            .ok()
+            .and_then(|max| max.parse::<usize>().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+    })
+}
+
+/// Gets whether the n-grams were built with the sentence boundaries.
+///
+/// # Returns
+///
+/// `true` if `SENTENCE_BOUNDARIES` is `true`, otherwise `false`.
+pub fn sentence_boundaries() -> bool {
+    *SENTENCE_BOUNDARIES.get_or_init(|| {
+        std::env::var("SENTENCE_BOUNDARIES").is_ok_and(|boundaries| boundaries == "true")
+    })
+}
+
+/// Gets the default window, used by serde.
+fn default_window() -> usize {
+    DEFAULT_WINDOW
+}
+
 /// Represents a solver with a confusion set.
 ///
 /// # Fields
 ///
 /// * `confusion_set` - The confusion set.
 /// * `text` - The text.
+/// * `canonical_words` - The preferred words of the directional confusion groups, these are never flagged.
+/// * `window` - The number of words on each side of the examined word, defaults to `DEFAULT_WINDOW`.
+/// * `skip_grams` - Whether the skip-bigrams around the examined word are queried as well, off by default.
+/// * `sentence_boundaries` - Whether the sentences are wrapped in `SENTENCE_START` and `SENTENCE_END`
+///   for the queries, off by default.
+/// * `vocabulary` - The lowercased unigram vocabulary, the words outside it and the confusion set are
+///   examined against their edit candidates. `None` by default and not serialized.
+/// * `word_index` - The lowercased words of the confusion set, mapped to the index of their first group.
+///   It is built by `new` and not serialized.
 ///
 /// # Methods
 ///
 /// * `new` - Creates a new `SolverWithConfusionSet`.
+/// * `with_canonical_words` - Sets the canonical words.
+/// * `with_window` - Sets the window.
+/// * `with_skip_grams` - Sets whether the skip-bigrams are queried.
+/// * `with_sentence_boundaries` - Sets whether the sentence boundaries are queried.
+/// * `with_vocabulary` - Sets the vocabulary of the edit candidates.
 /// * `find_queries` - Finds the queries.
+/// * `find_queries_in` - Finds the queries in already tokenized sentences.
+/// * `find_occurrences` - Finds where the examined words are in the text.
 #[derive(Deserialize, Serialize)]
 pub struct SolverWithConfusionSet {
     pub confusion_set: Vec<Vec<String>>,
     pub text: String,
+    #[serde(default)]
+    pub canonical_words: HashSet<String>,
+    #[serde(default = "default_window")]
+    pub window: usize,
+    #[serde(default)]
+    pub skip_grams: bool,
+    #[serde(default)]
+    pub sentence_boundaries: bool,
+    #[serde(skip)]
+    pub vocabulary: Option<Arc<HashSet<String>>>,
+    #[serde(skip)]
+    word_index: HashMap<String, usize>,
 }
 
 /// Represents a text extractor.
@@ -54,15 +147,68 @@ pub struct TextExtractor {
     pub text: String,
 }
 
+/// Represents the key of the queries, the examined word in its context.
+///
+/// Two confusion words can share the same context, so the context alone is not unique.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `word` - The examined word.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ContextKey {
+    pub context: String,
+    pub word: String,
+}
+
+impl ContextKey {
+    /// Creates a new `ContextKey`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context.
+    /// * `word` - The examined word.
+    ///
+    /// # Returns
+    ///
+    /// A `ContextKey`.
+    pub fn new(context: &str, word: &str) -> Self {
+        ContextKey {
+            context: context.to_string(),
+            word: word.to_string(),
+        }
+    }
+}
+
+/// Represents an occurrence of an examined word in the text.
+///
+/// # Fields
+///
+/// * `key` - The examined word in its context.
+/// * `start` - The byte offset of the word in the text, without the attached punctuation.
+/// * `end` - The byte offset of the end of the word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occurrence {
+    pub key: ContextKey,
+    pub start: usize,
+    pub end: usize,
+}
+
 /// Represents the queries.
 ///
 /// # Fields
 ///
 /// * `queries` - The queries.
 /// * `word` - The word.
+/// * `edit_candidates` - The candidates of a word outside the confusion set, the word included,
+///   empty for the words of the confusion set.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Queries {
     pub queries: Vec<QueryBuilder>,
     pub word: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub edit_candidates: Vec<String>,
 }
 
 impl SolverWithConfusionSet {
@@ -76,168 +222,574 @@ impl SolverWithConfusionSet {
     /// # Returns
     ///
     /// A `Result` containing the `SolverWithConfusionSet` if the confusion set is not empty, otherwise a `String` with the error message.
-    pub fn new(text: String, confusion_set: &Vec<Vec<String>>) -> Result<Self, String> {
+    pub fn new(text: String, confusion_set: &[Vec<String>]) -> Result<Self, String> {
         if confusion_set.is_empty() {
             return Err("Confusion set is empty".to_string());
         }
 
+        let mut word_index: HashMap<String, usize> = HashMap::new();
+        for (index, group) in confusion_set.iter().enumerate() {
+            for word in group {
+                word_index.entry(word.to_lowercase()).or_insert(index);
+            }
+        }
+
         Ok(Self {
-            confusion_set: confusion_set.clone(),
+            confusion_set: confusion_set.to_vec(),
             text,
+            canonical_words: HashSet::new(),
+            window: DEFAULT_WINDOW,
+            skip_grams: false,
+            sentence_boundaries: false,
+            vocabulary: None,
+            word_index,
         })
     }
 
+    /// Sets the canonical words.
+    ///
+    /// Canonical words are the preferred members of directional confusion groups, so they are
+    /// only suggested as replacements and never examined themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `canonical_words` - The canonical words.
+    ///
+    /// # Returns
+    ///
+    /// The `SolverWithConfusionSet` with the canonical words set.
+    pub fn with_canonical_words(mut self, canonical_words: HashSet<String>) -> Self {
+        self.canonical_words = canonical_words;
+        self
+    }
+
+    /// Sets the window.
+    ///
+    /// The window is the number of words taken on each side of the examined word for the context.
+    /// The bigram queries need a window of at least one and the trigram queries a window of at
+    /// least two, a wider window only widens the context.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The window.
+    ///
+    /// # Returns
+    ///
+    /// The `SolverWithConfusionSet` with the window.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets whether the skip-bigrams are queried.
+    ///
+    /// With a window of at least two, the examined word is also paired with the word two positions
+    /// before and after it, skipping the word in between. Every skip-bigram reads the whole
+    /// `three_grams_1_3_pk` partition of its words, one row per skipped word, so it costs Scylla
+    /// more than a trigram query and doubles the queries of every context with two words on a side.
+    ///
+    /// # Arguments
+    ///
+    /// * `skip_grams` - Whether the skip-bigrams are queried.
+    ///
+    /// # Returns
+    ///
+    /// The `SolverWithConfusionSet` with the skip-bigrams set.
+    pub fn with_skip_grams(mut self, skip_grams: bool) -> Self {
+        self.skip_grams = skip_grams;
+        self
+    }
+
+    /// Sets whether the sentence boundaries are queried.
+    ///
+    /// Every sentence is wrapped in `SENTENCE_START` and `SENTENCE_END`, so a word at the start or
+    /// the end of a sentence gets the bigram and trigram queries of the boundary as well. Only turn
+    /// it on if the n-grams were built with the same markers, otherwise those queries find nothing.
+    /// The markers are not part of the context.
+    ///
+    /// # Arguments
+    ///
+    /// * `sentence_boundaries` - Whether the sentence boundaries are queried.
+    ///
+    /// # Returns
+    ///
+    /// The `SolverWithConfusionSet` with the sentence boundaries set.
+    pub fn with_sentence_boundaries(mut self, sentence_boundaries: bool) -> Self {
+        self.sentence_boundaries = sentence_boundaries;
+        self
+    }
+
+    /// Sets the vocabulary of the edit candidates.
+    ///
+    /// With a vocabulary, a word that is neither in the confusion set nor in the vocabulary is
+    /// taken as a misspelling and examined against the words of the vocabulary within
+    /// `MAX_EDIT_DISTANCE`, at most `MAX_EDIT_CANDIDATES` of them. Every such word is compared with
+    /// the whole vocabulary and adds its own queries, so it is off unless asked for.
+    ///
+    /// # Arguments
+    ///
+    /// * `vocabulary` - The lowercased vocabulary, `None` to examine only the confusion set.
+    ///
+    /// # Returns
+    ///
+    /// The `SolverWithConfusionSet` with the vocabulary set.
+    pub fn with_vocabulary(mut self, vocabulary: Option<Arc<HashSet<String>>>) -> Self {
+        self.vocabulary = vocabulary;
+        self
+    }
+
+    /// Counts the analyzable sentences, sentences made only of delimiters are not counted.
+    ///
+    /// # Returns
+    ///
+    /// The number of sentences that contain at least one word.
+    pub fn count_sentences(&self) -> usize {
+        parse_text_to_sentences(&self.text)
+            .iter()
+            .filter(|sentence| sentence.split_whitespace().next().is_some())
+            .count()
+    }
+
+    /// Finds the examined word of the confusion set that matches the token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token, without the attached punctuation.
+    ///
+    /// # Returns
+    ///
+    /// The word of the confusion set, `None` if the token is not in the confusion set or the word is canonical.
+    fn examined_word(&self, token: &str) -> Option<&String> {
+        let token = token.to_lowercase();
+        let index = *self.word_index.get(&token)?;
+
+        self.confusion_set[index]
+            .iter()
+            .find(|word| word.to_lowercase() == token)
+            .filter(|word| !self.canonical_words.contains(*word))
+    }
+
+    /// Gets the candidates of the word, the union of all the groups the word is in.
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - The word.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<String>` containing the candidates, in the order of the confusion set.
+    fn candidates(&self, word: &String) -> Vec<String> {
+        let mut candidates: Vec<String> = vec![];
+
+        for group in self
+            .confusion_set
+            .iter()
+            .filter(|group| group.contains(word))
+        {
+            for candidate in group {
+                if !candidates.contains(candidate) {
+                    candidates.push(candidate.clone());
+                }
+            }
+        }
+
+        candidates
+    }
+
+    /// Gets the edit candidates of a word outside the confusion set.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token.
+    ///
+    /// # Returns
+    ///
+    /// The lowercased token followed by the closest words of the vocabulary, by the edit distance
+    /// and then alphabetically. `None` if there is no vocabulary, the token is in the confusion set
+    /// or the vocabulary, it is not made of letters, or no word is close enough.
+    fn edit_candidates(&self, token: &str) -> Option<Vec<String>> {
+        let vocabulary = self.vocabulary.as_ref()?;
+        let token = token.to_lowercase();
+
+        if token.is_empty()
+            || !token.chars().all(char::is_alphabetic)
+            || self.word_index.contains_key(&token)
+            || vocabulary.contains(&token)
+        {
+            return None;
+        }
+
+        let length = token.chars().count();
+        let mut candidates: Vec<(usize, &String)> = vocabulary
+            .iter()
+            // the distance is at least the difference of the lengths
+            .filter(|word| word.chars().count().abs_diff(length) <= MAX_EDIT_DISTANCE)
+            .map(|word| (levenshtein(&token, word), word))
+            .filter(|(distance, _)| *distance <= MAX_EDIT_DISTANCE)
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        candidates.sort();
+        candidates.truncate(MAX_EDIT_CANDIDATES);
+
+        let mut result = vec![token.clone()];
+        result.extend(candidates.into_iter().map(|(_, word)| word.clone()));
+
+        Some(result)
+    }
+
     /// Finds the queries.
     ///
+    /// A word that is in more than one confusion group is queried against all of them.
+    /// With a vocabulary, the words outside it are queried against their edit candidates.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` containing the queries of every examined word in its context.
+    pub fn find_queries(&self) -> HashMap<ContextKey, Queries> {
+        self.find_queries_in(&tokenize(&self.text))
+    }
+
+    /// Finds the queries in already tokenized sentences.
+    ///
+    /// # Arguments
+    ///
+    /// * `sentences` - The words of every sentence.
+    ///
     /// # Returns
     ///
-    /// A `HashMap` containing the queries.
-    pub fn find_queries(&self) -> HashMap<String, Queries> {
-        let sentences = parse_text_to_sentences(&self.text);
+    /// A `HashMap` containing the queries of every examined word in its context.
+    pub fn find_queries_in(&self, sentences: &[Vec<String>]) -> HashMap<ContextKey, Queries> {
         let mut queries = HashMap::new();
 
-        for sentence in &sentences {
-            let words: Vec<&str> = sentence.split_whitespace().collect();
-            for confusion_set in &self.confusion_set {
-                for word in confusion_set {
-                    if sentence.to_lowercase().contains(word) {
-                        process_word_in_sentence(word, &words, confusion_set, &mut queries);
-                    }
+        let mut candidates: HashMap<&String, Vec<String>> = HashMap::new();
+        let mut edit_candidates: HashMap<&str, Option<Vec<String>>> = HashMap::new();
+
+        for sentence in sentences {
+            let mut words: Vec<&str> = sentence.iter().map(|word| word.as_str()).collect();
+            // the queries are built from the words without the attached punctuation
+            let mut cleaned: Vec<&str> = words.iter().map(|word| strip_punctuation(word)).collect();
+
+            let mut examined = 0..words.len();
+            if self.sentence_boundaries {
+                // the markers are added after the cleaning, it would strip them
+                for tokens in [&mut words, &mut cleaned] {
+                    tokens.insert(0, SENTENCE_START);
+                    tokens.push(SENTENCE_END);
+                }
+                examined = 1..words.len() - 1;
+            }
+
+            for j in examined {
+                if let Some(word) = self.examined_word(cleaned[j]) {
+                    let candidates = candidates
+                        .entry(word)
+                        .or_insert_with(|| self.candidates(word));
+                    self.process_word_in_sentence(
+                        word,
+                        j,
+                        &words,
+                        &cleaned,
+                        candidates,
+                        &mut queries,
+                    );
+                    continue;
+                }
+
+                let candidates = edit_candidates
+                    .entry(cleaned[j])
+                    .or_insert_with(|| self.edit_candidates(cleaned[j]));
+                if let Some(candidates) = candidates {
+                    self.process_word_in_sentence(
+                        &candidates[0],
+                        j,
+                        &words,
+                        &cleaned,
+                        candidates,
+                        &mut queries,
+                    );
                 }
             }
         }
 
         queries
     }
-}
 
-/// Processes the word in the sentence.
-///
-/// # Arguments
-///
-/// * `word` - The word.
-/// * `words` - The words.
-/// * `confusion_set` - The confusion set.
-/// * `queries` - The queries.
-fn process_word_in_sentence(
-    word: &str,
-    words: &[&str],
-    confusion_set: &[String],
-    queries: &mut HashMap<String, Queries>,
-) {
-    for (j, &w) in words.iter().enumerate() {
-        if w.to_lowercase() == word.to_lowercase() {
-            let context = extract_context(j, words);
+    /// Processes the word at the position in the sentence.
+    ///
+    /// The words keep their punctuation for the context, the queries are built from the cleaned words.
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - The examined word of the confusion set.
+    /// * `j` - The position of the word in the sentence.
+    /// * `words` - The words.
+    /// * `cleaned` - The words without the attached punctuation.
+    /// * `confusion_set` - The confusion set.
+    /// * `queries` - The queries.
+    ///
+    /// A confusion set with fewer than two words has nothing to confuse the word with, it is skipped.
+    fn process_word_in_sentence(
+        &self,
+        word: &str,
+        j: usize,
+        words: &[&str],
+        cleaned: &[&str],
+        confusion_set: &[String],
+        queries: &mut HashMap<ContextKey, Queries>,
+    ) {
+        if confusion_set.len() < 2 {
+            tracing::warn!(word, "skipping a confusion set with fewer than two words");
+            return;
+        }
 
-            if queries.get(&context).is_some() {
-                continue;
-            }
+        let window = self.window;
+        let context = match self.sentence_boundaries {
+            // the context is taken without the markers, as `find_occurrences` sees the text
+            true => extract_context(j - 1, &words[1..words.len() - 1], window),
+            false => extract_context(j, words, window),
+        };
+        let context = ContextKey::new(&context, word);
 
-            let mut q = Vec::new();
+        if queries.contains_key(&context) {
+            return;
+        }
 
-            let query_str = GET_ALL_VARYING_1.to_string()
-                + "("
-                + "?, ".repeat(confusion_set.len() - 1).as_str()
-                + "?)";
+        let mut q = Vec::new();
 
-            q.push(QueryBuilder {
-                query: query_str,
-                static_params: vec![],
-                varying_params: confusion_set.to_vec(),
-            });
+        add_to_query(QueryShape::OneVary, &[word], confusion_set, &mut q);
 
-            if j >= 1 {
-                if words[j - 1] != words[j - 1].to_lowercase() {
-                    let lowercase_word = words[j - 1].to_lowercase();
-                    add_to_query(
-                        GET_ALL_VARYING_2_2,
-                        &vec![&lowercase_word, words[j]],
-                        &confusion_set,
-                        &mut q,
-                        1,
-                    );
-                }
+        if window >= 1 && j >= 1 {
+            if cleaned[j - 1] != cleaned[j - 1].to_lowercase() {
+                let lowercase_word = cleaned[j - 1].to_lowercase();
                 add_to_query(
-                    GET_ALL_VARYING_2_2,
-                    &words[j - 1..=j],
-                    &confusion_set,
+                    QueryShape::TwoVary2,
+                    &[&lowercase_word, cleaned[j]],
+                    confusion_set,
                     &mut q,
-                    1,
                 );
             }
-            if j + 1 < words.len() {
-                if words[j + 1] != words[j + 1].to_lowercase() {
-                    let lowercase_word = words[j + 1].to_lowercase();
-                    add_to_query(
-                        GET_ALL_VARYING_2_1,
-                        &vec![words[j], &lowercase_word],
-                        &confusion_set,
-                        &mut q,
-                        0,
-                    );
-                }
+            add_to_query(
+                QueryShape::TwoVary2,
+                &cleaned[j - 1..=j],
+                confusion_set,
+                &mut q,
+            );
+        }
+        if window >= 1 && j + 1 < words.len() {
+            if cleaned[j + 1] != cleaned[j + 1].to_lowercase() {
+                let lowercase_word = cleaned[j + 1].to_lowercase();
                 add_to_query(
-                    GET_ALL_VARYING_2_1,
-                    &words[j..=j + 1],
-                    &confusion_set,
+                    QueryShape::TwoVary1,
+                    &[cleaned[j], &lowercase_word],
+                    confusion_set,
                     &mut q,
-                    0,
                 );
             }
+            add_to_query(
+                QueryShape::TwoVary1,
+                &cleaned[j..=j + 1],
+                confusion_set,
+                &mut q,
+            );
+        }
 
-            if j >= 2 {
-                if words[j - 2] != words[j - 2].to_lowercase()
-                    || words[j - 1] != words[j - 1].to_lowercase()
-                {
-                    let lowercase_word1 = words[j - 2].to_lowercase();
-                    let lowercase_word2 = words[j - 1].to_lowercase();
-                    add_to_query(
-                        GET_ALL_VARYING_3_3,
-                        &vec![&lowercase_word1, &lowercase_word2, words[j]],
-                        &confusion_set,
-                        &mut q,
-                        2,
-                    );
-                }
+        if window >= 2 && j >= 2 {
+            if cleaned[j - 2] != cleaned[j - 2].to_lowercase()
+                || cleaned[j - 1] != cleaned[j - 1].to_lowercase()
+            {
+                let lowercase_word1 = cleaned[j - 2].to_lowercase();
+                let lowercase_word2 = cleaned[j - 1].to_lowercase();
                 add_to_query(
-                    GET_ALL_VARYING_3_3,
-                    &words[j - 2..=j],
-                    &confusion_set,
+                    QueryShape::ThreeVary3,
+                    &[&lowercase_word1, &lowercase_word2, cleaned[j]],
+                    confusion_set,
                     &mut q,
-                    2,
                 );
             }
-            if j + 2 < words.len() {
-                if words[j + 1] != words[j + 1].to_lowercase()
-                    || words[j + 2] != words[j + 2].to_lowercase()
-                {
-                    let lowercase_word1 = words[j + 1].to_lowercase();
-                    let lowercase_word2 = words[j + 2].to_lowercase();
-                    add_to_query(
-                        GET_ALL_VARYING_3_1,
-                        &vec![words[j], &lowercase_word1, &lowercase_word2],
-                        &confusion_set,
-                        &mut q,
-                        0,
-                    );
-                }
+            add_to_query(
+                QueryShape::ThreeVary3,
+                &cleaned[j - 2..=j],
+                confusion_set,
+                &mut q,
+            );
+        }
+        if window >= 2 && j + 2 < words.len() {
+            if cleaned[j + 1] != cleaned[j + 1].to_lowercase()
+                || cleaned[j + 2] != cleaned[j + 2].to_lowercase()
+            {
+                let lowercase_word1 = cleaned[j + 1].to_lowercase();
+                let lowercase_word2 = cleaned[j + 2].to_lowercase();
                 add_to_query(
-                    GET_ALL_VARYING_3_1,
-                    &words[j..=j + 2],
-                    &confusion_set,
+                    QueryShape::ThreeVary1,
+                    &[cleaned[j], &lowercase_word1, &lowercase_word2],
+                    confusion_set,
                     &mut q,
-                    0,
                 );
             }
+            add_to_query(
+                QueryShape::ThreeVary1,
+                &cleaned[j..=j + 2],
+                confusion_set,
+                &mut q,
+            );
+        }
+
+        if self.skip_grams && window >= 2 && j >= 2 {
+            add_to_query(
+                QueryShape::SkipVary3,
+                &cleaned[j - 2..=j],
+                confusion_set,
+                &mut q,
+            );
+        }
+        if self.skip_grams && window >= 2 && j + 2 < words.len() {
+            add_to_query(
+                QueryShape::SkipVary1,
+                &cleaned[j..=j + 2],
+                confusion_set,
+                &mut q,
+            );
+        }
+
+        // the predictors find the candidates of a word outside the confusion set in its queries
+        let edit_candidates = match self.word_index.contains_key(&word.to_lowercase()) {
+            true => vec![],
+            false => confusion_set.to_vec(),
+        };
+
+        let result = Queries {
+            queries: q,
+            word: word.to_string(),
+            edit_candidates,
+        };
 
-            let result = Queries {
-                queries: q,
-                word: word.to_string(),
+        queries.insert(context, result);
+    }
+
+    /// Finds where the examined words are in the text.
+    ///
+    /// The words are matched the same way as in `find_queries`, but every occurrence is kept, also
+    /// the repeated ones that share a context.
+    ///
+    /// # Returns
+    ///
+    /// The occurrences, in the order of the text.
+    pub fn find_occurrences(&self) -> Vec<Occurrence> {
+        let mut occurrences: Vec<Occurrence> = vec![];
+        let mut cursor = 0;
+
+        for sentence in parse_text_to_sentences(&self.text) {
+            let offset = match self.text[cursor..].find(sentence.as_str()) {
+                Some(offset) => cursor + offset,
+                None => continue,
             };
+            cursor = offset + sentence.len();
+
+            let sentence = &self.text[offset..cursor];
+            let mut words: Vec<String> = vec![];
+            let mut tokens: Vec<&str> = vec![];
+
+            for token in sentence.split_whitespace() {
+                let word = sanitize_word(token);
+                if !word.is_empty() {
+                    words.push(word);
+                    tokens.push(token);
+                }
+            }
+
+            let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+
+            for (j, token) in tokens.iter().enumerate() {
+                let cleaned = strip_punctuation(words[j]);
+                let word = match self.examined_word(cleaned) {
+                    Some(word) => word.clone(),
+                    None => match self.edit_candidates(cleaned) {
+                        Some(mut candidates) => candidates.swap_remove(0),
+                        None => continue,
+                    },
+                };
+
+                let core = strip_punctuation(token);
+                let start = offset
+                    + (token.as_ptr() as usize - sentence.as_ptr() as usize)
+                    + (core.as_ptr() as usize - token.as_ptr() as usize);
+
+                occurrences.push(Occurrence {
+                    key: ContextKey::new(&extract_context(j, &words, self.window), &word),
+                    start,
+                    end: start + core.len(),
+                });
+            }
+        }
+
+        occurrences
+    }
+}
+
+/// Splits the text into sentences of sanitized words.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+///
+/// # Returns
+///
+/// The words of every sentence, the words that are empty after sanitizing are left out.
+pub fn tokenize(text: &str) -> Vec<Vec<String>> {
+    parse_text_to_sentences(text)
+        .iter()
+        .map(|sentence| {
+            sentence
+                .split_whitespace()
+                .map(sanitize_word)
+                .filter(|word| !word.is_empty())
+                .collect()
+        })
+        .collect()
+}
+
+/// Strips the punctuation attached to the start and the end of the token.
+///
+/// # Arguments
+///
+/// * `token` - The token.
+///
+/// # Returns
+///
+/// The token without the leading and trailing characters that are not alphanumeric.
+fn strip_punctuation(token: &str) -> &str {
+    token.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+/// Computes the Levenshtein distance of two words.
+///
+/// # Arguments
+///
+/// * `a` - The first word.
+/// * `b` - The second word.
+///
+/// # Returns
+///
+/// The least number of inserted, deleted or substituted characters that turns `a` into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
 
-            queries.insert(context, result);
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
         }
+        std::mem::swap(&mut previous, &mut current);
     }
+
+    previous[b.len()]
 }
 
 /// Extracts the context.
@@ -246,14 +798,15 @@ fn process_word_in_sentence(
 ///
 /// * `index` - The index.
 /// * `words` - The words.
+/// * `window` - The number of words on each side of the index.
 ///
 /// # Returns
 ///
 /// The context.
-fn extract_context(index: usize, words: &[&str]) -> String {
+fn extract_context(index: usize, words: &[&str], window: usize) -> String {
     let mut context = String::new();
-    for i in (index.saturating_sub(2))..=(index + 2).min(words.len() - 1) {
-        context.push_str(words[i]);
+    for word in &words[index.saturating_sub(window)..=(index + window).min(words.len() - 1)] {
+        context.push_str(word);
         context.push(' ');
     }
     context = context.trim().to_string();
@@ -264,20 +817,27 @@ fn extract_context(index: usize, words: &[&str]) -> String {
 ///
 /// # Arguments
 ///
-/// * `query_str` - The query string.
-/// * `window` - The window.
+/// * `shape` - The shape of the query.
+/// * `window` - The window, the words of the n-gram, the skipped one of the skip-grams included.
 /// * `confusion_set` - The confusion set.
 /// * `queries` - The queries.
-/// * `index` - The index.
+///
+/// A confusion set with fewer than two words is skipped, it has no alternative to query.
 fn add_to_query(
-    query_str: &str,
+    shape: QueryShape,
     window: &[&str],
     confusion_set: &[String],
     queries: &mut Vec<QueryBuilder>,
-    index: usize,
 ) {
+    if confusion_set.len() < 2 {
+        return;
+    }
+
     let mut static_params: Vec<String> = window.to_vec().iter().map(|s| s.to_string()).collect();
-    static_params.remove(index);
+    static_params.remove(shape.varying_index());
+    if let Some(skipped) = shape.skipped_index() {
+        static_params.remove(skipped - usize::from(skipped > shape.varying_index()));
+    }
 
     let mut varying_params = Vec::new();
 
@@ -285,10 +845,11 @@ fn add_to_query(
         varying_params.push(word.clone());
     }
 
-    let q = query_str.to_string() + "(" + "?, ".repeat(confusion_set.len() - 1).as_str() + "?)";
+    let q = shape.query().to_string() + "(" + "?, ".repeat(confusion_set.len() - 1).as_str() + "?)";
 
     queries.push(QueryBuilder {
         query: q,
+        shape,
         static_params,
         varying_params,
     });
@@ -301,7 +862,8 @@ fn add_to_query(
 /// * `input` - The input.
 /// * `frequency` - The frequency.
 /// * `length` - The length.
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct QueryResult {
     pub input: String,
     pub frequency: i32,
@@ -315,204 +877,1775 @@ pub struct QueryResult {
 /// * `sentence` - The sentence.
 /// * `word` - The word.
 /// * `results` - The results.
-#[derive(Deserialize, Serialize)]
+/// * `rows_fetched` - The number of results returned by Scylla, the other results are zero-filled.
+/// * `edit_candidates` - The candidates of a word outside the confusion set, empty for the words of
+///   the confusion set.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SentenceResult {
     pub sentence: String,
     pub word: String,
     pub results: Vec<QueryResult>,
+    #[serde(default)]
+    pub rows_fetched: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edit_candidates: Vec<String>,
 }
 
 /// Represents the timed sentence results.
 ///
 /// # Fields
 ///
-/// * `time_taken` - The time taken.
+/// * `time_taken` - The time taken, formatted as `"123 ms"`.
+/// * `time_taken_ms` - The time taken in milliseconds.
 /// * `results` - The results.
-#[derive(Deserialize, Serialize)]
+/// * `sentences` - The number of analyzable sentences in the text.
+/// * `errors` - The errors of the queries that failed, their contexts are scored without them.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TimedSentenceResults {
     pub time_taken: String,
+    #[serde(default)]
+    pub time_taken_ms: u128,
     pub results: Vec<SentenceResult>,
+    pub sentences: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Represents the options of the query execution.
+///
+/// # Fields
+///
+/// * `early_stop` - The margin, in log10 units of the bigram frequencies, above which a context
+///   that already has a correction skips its trigram queries. `None` runs all the queries.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out instead of
+///   being sent with zero frequency.
+#[derive(Default, Clone, Deserialize, Serialize)]
+pub struct ExecuteOptions {
+    pub early_stop: Option<f64>,
+    #[serde(default)]
+    pub skip_backfill: bool,
 }
 
 /// Executes the queries.
 ///
+/// With `early_stop` set the unigram and bigram queries run first, and the trigram queries only
+/// run for the contexts without a confident bigram correction.
+///
+/// A failing query does not fail the execution, its error is collected into `errors`.
+///
 /// # Arguments
 ///
 /// * `queries` - The queries.
-/// * `session` - The session.
+/// * `store` - The n-gram store.
+/// * `options` - The execution options.
 ///
 /// # Returns
 ///
-/// The timed sentence results..
+/// The timed sentence results, `sentences` is left for the caller to fill in.
 pub async fn execute_queries(
-    queries: HashMap<String, Queries>,
-    session: Arc<Session>,
+    queries: HashMap<ContextKey, Queries>,
+    store: Arc<dyn NgramStore>,
+    options: &ExecuteOptions,
 ) -> TimedSentenceResults {
-    let mut sentence_results: Vec<SentenceResult> = vec![];
-    let (tx, rx) = mpsc::channel();
-    let mut handlers = vec![];
-
     let start = std::time::Instant::now();
 
-    for (key, value) in queries {
-        sentence_results.push(SentenceResult {
-            sentence: key.clone(),
-            results: vec![],
-            word: value.word,
-        });
-        for v in value.queries {
-            let key = key.clone();
-            let query = v.query.clone();
-            let values = v.varying_params.clone();
-            let static_params = v.static_params.clone();
+    let margin = match options.early_stop {
+        Some(margin) => margin,
+        None => {
+            let (sentence_results, errors) =
+                run_queries(queries, &store, options.skip_backfill, max_concurrency()).await;
 
-            let s = Arc::clone(&session);
+            let elapsed = start.elapsed().as_millis();
+            return TimedSentenceResults {
+                time_taken: format!("{} ms", elapsed),
+                time_taken_ms: elapsed,
+                results: sentence_results,
+                sentences: 0,
+                errors,
+            };
+        }
+    };
 
-            let tx_clone = tx.clone();
+    let (queries, trigram_queries) = split_trigram_queries(queries);
 
-            let handle = tokio::spawn(async move {
-                process(
-                    key,
-                    s,
-                    query.as_str(),
-                    static_params.iter().map(|s| s.as_str()).collect(),
-                    values.iter().map(|s| s.as_str()).collect(),
-                    tx_clone,
-                )
-                .await
-                .unwrap();
-            });
+    let (mut sentence_results, mut errors) =
+        run_queries(queries, &store, options.skip_backfill, max_concurrency()).await;
+
+    let trigram_queries = skip_confident_contexts(trigram_queries, &sentence_results, margin);
+    let indexes = index_results(&sentence_results);
 
-            handlers.push(handle);
+    let (trigram_results, trigram_errors) = run_queries(
+        trigram_queries,
+        &store,
+        options.skip_backfill,
+        max_concurrency(),
+    )
+    .await;
+    errors.extend(trigram_errors);
+
+    for result in trigram_results {
+        let key = ContextKey {
+            context: result.sentence,
+            word: result.word,
+        };
+        if let Some(&index) = indexes.get(&key) {
+            sentence_results[index].results.extend(result.results);
+            sentence_results[index].rows_fetched += result.rows_fetched;
         }
     }
 
-    for handle in handlers {
-        handle.await.unwrap();
+    let elapsed = start.elapsed().as_millis();
+    TimedSentenceResults {
+        time_taken: format!("{} ms", elapsed),
+        time_taken_ms: elapsed,
+        results: sentence_results,
+        sentences: 0,
+        errors,
     }
+}
 
-    drop(tx);
+/// Executes the queries like `execute_queries`, streaming the results of every context as soon
+/// as all of its queries are done.
+///
+/// Every distinct query of the text still runs once, see `group_queries`. With `early_stop` set
+/// the unigram and bigram queries of all the contexts run first, and the trigram queries are streamed.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+/// * `store` - The n-gram store.
+/// * `options` - The execution options.
+///
+/// # Returns
+///
+/// The stream of the results of every context in the order they are done, and of the errors of
+/// the queries that failed as soon as they fail.
+pub fn execute_queries_streamed(
+    queries: HashMap<ContextKey, Queries>,
+    store: Arc<dyn NgramStore>,
+    options: &ExecuteOptions,
+) -> impl Stream<Item = Result<SentenceResult, String>> {
+    let options = options.clone();
+
+    stream::once(async move {
+        let (queries, sentence_results, errors) = match options.early_stop {
+            None => {
+                let sentence_results = empty_results(&queries);
+                (queries, sentence_results, vec![])
+            }
+            Some(margin) => {
+                let (queries, trigram_queries) = split_trigram_queries(queries);
+
+                let (sentence_results, errors) =
+                    run_queries(queries, &store, options.skip_backfill, max_concurrency()).await;
+
+                let trigram_queries =
+                    skip_confident_contexts(trigram_queries, &sentence_results, margin);
+
+                (trigram_queries, sentence_results, errors)
+            }
+        };
+
+        stream::iter(errors.into_iter().map(Err)).chain(stream_queries(
+            queries,
+            sentence_results,
+            &store,
+            options.skip_backfill,
+            max_concurrency(),
+        ))
+    })
+    .flatten()
+}
+
+/// Groups the identical queries of the contexts.
+///
+/// Contexts often share queries, e.g. every context of the same word has the same unigram query.
+/// Scylla batches only accept modifications, so instead of batching the reads every distinct query
+/// is run once and its results are sent to all the contexts that asked for it.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+///
+/// # Returns
+///
+/// The distinct queries with the keys of the contexts that asked for them, a key is repeated
+/// if its context asked for the query more than once.
+pub fn group_queries(
+    queries: &HashMap<ContextKey, Queries>,
+) -> Vec<(&QueryBuilder, Vec<ContextKey>)> {
+    let mut grouped: Vec<(&QueryBuilder, Vec<ContextKey>)> = vec![];
+    let mut indexes: HashMap<&QueryBuilder, usize> = HashMap::new();
 
-    for result in rx {
-        for sentence_result in &mut sentence_results {
-            if sentence_result.sentence == result.0 {
-                sentence_result.results.push(result.1);
-                break;
+    for (key, value) in queries {
+        for query in &value.queries {
+            match indexes.get(query) {
+                Some(&index) => grouped[index].1.push(key.clone()),
+                None => {
+                    indexes.insert(query, grouped.len());
+                    grouped.push((query, vec![key.clone()]));
+                }
             }
         }
     }
 
-    let elapsed = start.elapsed().as_millis();
+    grouped
+}
 
-    TimedSentenceResults {
-        time_taken: format!("{} ms", elapsed),
-        results: sentence_results,
+/// Runs the queries concurrently, every distinct query once.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+/// * `store` - The n-gram store.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+/// * `max_concurrency` - The upper bound of the queries running at the same time.
+///
+/// # Returns
+///
+/// The results of every context, and the sorted distinct errors of the queries that failed.
+async fn run_queries(
+    queries: HashMap<ContextKey, Queries>,
+    store: &Arc<dyn NgramStore>,
+    skip_backfill: bool,
+    max_concurrency: usize,
+) -> (Vec<SentenceResult>, Vec<String>) {
+    let mut errors: Vec<String> = vec![];
+    let (tx, rx) = mpsc::channel();
+
+    let handlers = spawn_queries(&queries, store, skip_backfill, max_concurrency, &tx);
+
+    let mut sentence_results = empty_results(&queries);
+
+    for (_, handle) in handlers {
+        if let Some(err) = task_error(handle.await) {
+            errors.push(err);
+        }
     }
+
+    drop(tx);
+
+    merge_results(&mut sentence_results, rx);
+
+    errors.sort();
+    errors.dedup();
+
+    (sentence_results, errors)
 }
 
-/// Processes the query.
+/// Runs the queries like `run_queries`, streaming the results of every context as soon as all of
+/// its queries are done.
 ///
 /// # Arguments
 ///
-/// * `key` - The key.
-/// * `session` - The session.
-/// * `query` - The query.
-/// * `static_values` - The static values.
-/// * `varying_values` - The varying values.
-/// * `tx` - The sender.
+/// * `queries` - The queries.
+/// * `sentence_results` - The results of every context so far, the contexts without queries are done.
+/// * `store` - The n-gram store.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+/// * `max_concurrency` - The upper bound of the queries running at the same time.
 ///
 /// # Returns
 ///
-/// A `Result` containing `()` if the query is successful, otherwise a `std::io::Error`.
-async fn process(
-    key: String,
-    session: Arc<Session>,
-    query: &str,
-    static_values: Vec<&str>,
-    varying_values: Vec<&str>,
-    tx: mpsc::Sender<(String, QueryResult)>,
-) -> Result<(), std::io::Error> {
-    let s = Arc::clone(&session);
-
-    let factory = match QueryFactory::build(s, query, Consistency::One).await {
-        Ok(factory) => factory,
-        Err(err) => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                err.to_string(),
-            ))
+/// The stream of the results of every context, and of the errors of the queries that failed.
+fn stream_queries(
+    queries: HashMap<ContextKey, Queries>,
+    sentence_results: Vec<SentenceResult>,
+    store: &Arc<dyn NgramStore>,
+    skip_backfill: bool,
+    max_concurrency: usize,
+) -> impl Stream<Item = Result<SentenceResult, String>> {
+    let (tx, rx) = mpsc::channel();
+    let mut pending: HashMap<ContextKey, usize> = HashMap::new();
+    let running = FuturesUnordered::new();
+
+    for (keys, handle) in spawn_queries(&queries, store, skip_backfill, max_concurrency, &tx) {
+        for key in &keys {
+            *pending.entry(key.clone()).or_insert(0) += 1;
         }
-    };
+        running.push(handle.map(move |joined| (keys, joined)));
+    }
 
-    let s = Arc::clone(&session);
+    drop(tx);
 
-    let all_values = static_values
-        .iter()
-        .chain(varying_values.iter())
-        .collect::<Vec<&&str>>();
-
-    let mut row_stream = match factory.execute_one(s, all_values).await {
-        Ok(rows) => rows.into_typed::<(String, i32)>(),
-        Err(err) => match err {
-            QueryError::ScyllaError => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Can not execute query",
-                ))
+    let (waiting, done): (Vec<SentenceResult>, Vec<SentenceResult>) =
+        sentence_results.into_iter().partition(|sentence_result| {
+            pending.contains_key(&ContextKey::new(
+                &sentence_result.sentence,
+                &sentence_result.word,
+            ))
+        });
+
+    let waiting: HashMap<ContextKey, SentenceResult> = waiting
+        .into_iter()
+        .map(|sentence_result| {
+            (
+                ContextKey::new(&sentence_result.sentence, &sentence_result.word),
+                sentence_result,
+            )
+        })
+        .collect();
+
+    let streamed = stream::unfold(
+        (running, rx, pending, waiting),
+        |(mut running, rx, mut pending, mut waiting)| async move {
+            let (keys, joined) = running.next().await?;
+
+            // the task sent all its results before it was done, they are already in the channel
+            for (key, result, fetched) in rx.try_iter() {
+                if let Some(sentence_result) = waiting.get_mut(&key) {
+                    merge_result(sentence_result, result, fetched);
+                }
+            }
+
+            let mut items = vec![];
+
+            if let Some(err) = task_error(joined) {
+                items.push(Err(err));
             }
-            QueryError::NotFound => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Word not found",
-                ))
+
+            for key in keys {
+                if let Some(count) = pending.get_mut(&key) {
+                    *count -= 1;
+                    if *count == 0 {
+                        pending.remove(&key);
+                        if let Some(sentence_result) = waiting.remove(&key) {
+                            items.push(Ok(sentence_result));
+                        }
+                    }
+                }
             }
+
+            Some((stream::iter(items), (running, rx, pending, waiting)))
         },
+    )
+    .flatten();
+
+    stream::iter(done.into_iter().map(Ok)).chain(streamed)
+}
+
+/// The keys of the contexts that asked for a distinct query, and the task running it.
+type QueryTask = (Vec<ContextKey>, JoinHandle<Result<(), String>>);
+
+/// Spawns every distinct query once, see `group_queries`.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+/// * `store` - The n-gram store.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+/// * `max_concurrency` - The upper bound of the queries running at the same time.
+/// * `tx` - The sender of the results, see `process`.
+///
+/// # Returns
+///
+/// The keys of the contexts that asked for every query, and the task running it.
+fn spawn_queries(
+    queries: &HashMap<ContextKey, Queries>,
+    store: &Arc<dyn NgramStore>,
+    skip_backfill: bool,
+    max_concurrency: usize,
+    tx: &mpsc::Sender<(ContextKey, QueryResult, bool)>,
+) -> Vec<QueryTask> {
+    let semaphore = Arc::new(Semaphore::new(
+        max_concurrency.clamp(1, Semaphore::MAX_PERMITS),
+    ));
+
+    group_queries(queries)
+        .into_iter()
+        .map(|(query, keys)| {
+            let query = query.clone();
+
+            let store = Arc::clone(store);
+            let semaphore = Arc::clone(&semaphore);
+
+            let tx_clone = tx.clone();
+            let keys_clone = keys.clone();
+
+            let handle = tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                process(keys_clone, &*store, &query, skip_backfill, tx_clone).await
+            });
+
+            (keys, handle)
+        })
+        .collect()
+}
+
+/// Gets the error of a finished query task, and logs it.
+///
+/// # Arguments
+///
+/// * `joined` - The outcome of the task.
+///
+/// # Returns
+///
+/// An `Option` containing the error message if the query or its task failed.
+fn task_error(joined: Result<Result<(), String>, JoinError>) -> Option<String> {
+    let err = match joined {
+        Ok(Ok(())) => return None,
+        Ok(Err(err)) => err,
+        Err(err) => format!("Query task failed: {}", err),
     };
 
-    let mut words_received = vec![];
+    tracing::error!(error = %err, "solver query failed");
 
-    while let Some(rows) = row_stream.next().await {
-        let (word, freq) = rows.unwrap();
-        let input = get_n_gram_string(query, &static_values, word.as_str());
-        words_received.push(word.clone());
-        tx.send((
-            key.clone(),
-            QueryResult {
-                input: input.clone(),
-                frequency: freq,
-                length: input.split_whitespace().count() as i32,
-            },
-        ))
-        .unwrap();
+    Some(err)
+}
+
+/// Creates the empty results of every context of the queries.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+///
+/// # Returns
+///
+/// The results of every context, without query results.
+fn empty_results(queries: &HashMap<ContextKey, Queries>) -> Vec<SentenceResult> {
+    queries
+        .iter()
+        .map(|(key, value)| SentenceResult {
+            sentence: key.context.clone(),
+            results: vec![],
+            word: key.word.clone(),
+            rows_fetched: 0,
+            edit_candidates: value.edit_candidates.clone(),
+        })
+        .collect()
+}
+
+/// Runs the queries against recorded n-gram frequencies instead of Scylla.
+///
+/// Every distinct query is answered once, like `run_queries`, the n-grams missing from the
+/// recorded frequencies are treated as rows Scylla did not return.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+/// * `counts` - The recorded frequencies, keyed by the n-gram.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+///
+/// # Returns
+///
+/// The results of every context.
+pub fn replay_queries(
+    queries: HashMap<ContextKey, Queries>,
+    counts: &HashMap<String, i32>,
+    skip_backfill: bool,
+) -> Vec<SentenceResult> {
+    let mut received = vec![];
+
+    for (builder, keys) in group_queries(&queries) {
+        let static_values: Vec<&str> = builder.static_params.iter().map(|s| s.as_str()).collect();
+        let mut words_received = vec![];
+
+        for word in &builder.varying_params {
+            let input = get_n_gram_string(builder.shape, &static_values, word);
+            if let Some(frequency) = counts.get(&input) {
+                words_received.push(word.clone());
+                for key in &keys {
+                    received.push((
+                        key.clone(),
+                        QueryResult {
+                            length: input.split_whitespace().count() as i32,
+                            input: input.clone(),
+                            frequency: *frequency,
+                        },
+                        true,
+                    ));
+                }
+            }
+        }
+
+        for result in backfill(builder, &words_received, skip_backfill) {
+            for key in &keys {
+                received.push((key.clone(), result.clone(), false));
+            }
+        }
     }
 
-    for word in varying_values {
-        if !words_received.contains(&word.to_string()) {
-            let input = get_n_gram_string(query, &static_values, word);
-            tx.send((
-                key.clone(),
-                QueryResult {
-                    input: input.clone(),
-                    frequency: 0,
-                    length: input.split_whitespace().count() as i32,
-                },
+    let mut sentence_results = empty_results(&queries);
+
+    merge_results(&mut sentence_results, received);
+
+    sentence_results
+}
+
+/// Indexes the results by the key of their context.
+///
+/// # Arguments
+///
+/// * `sentence_results` - The results of every context.
+///
+/// # Returns
+///
+/// The key of every context mapped to the position of its results, the first one if repeated.
+fn index_results(sentence_results: &[SentenceResult]) -> HashMap<ContextKey, usize> {
+    let mut indexes = HashMap::with_capacity(sentence_results.len());
+
+    for (index, sentence_result) in sentence_results.iter().enumerate() {
+        indexes
+            .entry(ContextKey::new(
+                &sentence_result.sentence,
+                &sentence_result.word,
             ))
-            .unwrap();
+            .or_insert(index);
+    }
+
+    indexes
+}
+
+/// Merges the received results into the results of their contexts.
+///
+/// The received results of unknown contexts are dropped.
+///
+/// # Arguments
+///
+/// * `sentence_results` - The results of every context.
+/// * `received` - The context key, the result and whether it was returned by Scylla.
+fn merge_results(
+    sentence_results: &mut [SentenceResult],
+    received: impl IntoIterator<Item = (ContextKey, QueryResult, bool)>,
+) {
+    let indexes = index_results(sentence_results);
+
+    for (key, result, fetched) in received {
+        if let Some(&index) = indexes.get(&key) {
+            merge_result(&mut sentence_results[index], result, fetched);
         }
     }
+}
 
-    Ok(())
+/// Merges the received result into the results of its context.
+///
+/// # Arguments
+///
+/// * `sentence_result` - The results of the context.
+/// * `result` - The received result.
+/// * `fetched` - Whether the result was returned by Scylla.
+fn merge_result(sentence_result: &mut SentenceResult, result: QueryResult, fetched: bool) {
+    sentence_result.results.push(result);
+    if fetched {
+        sentence_result.rows_fetched += 1;
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Checks whether the query is a trigram query.
+///
+/// # Arguments
+///
+/// * `query` - The query.
+///
+/// # Returns
+///
+/// `true` if the query selects from the trigram tables.
+fn is_trigram_query(query: &QueryBuilder) -> bool {
+    query.shape.order() == 3
+}
 
-    #[test]
-    fn test_extract_context() {
-        let words = vec!["Krleža", "sve", "oduševio", "svojim", "dijelom"];
+/// Splits the trigram queries from the rest.
+///
+/// # Arguments
+///
+/// * `queries` - The queries.
+///
+/// # Returns
+///
+/// The queries without the trigram queries, and the trigram queries of every context.
+pub fn split_trigram_queries(
+    queries: HashMap<ContextKey, Queries>,
+) -> (HashMap<ContextKey, Queries>, HashMap<ContextKey, Queries>) {
+    let mut rest = HashMap::new();
+    let mut trigrams = HashMap::new();
 
-        let context = extract_context(4, &words);
-        assert_eq!(context, "oduševio svojim dijelom");
+    for (context, value) in queries {
+        let (trigram_queries, other_queries): (Vec<QueryBuilder>, Vec<QueryBuilder>) =
+            value.queries.into_iter().partition(is_trigram_query);
+
+        if !trigram_queries.is_empty() {
+            trigrams.insert(
+                context.clone(),
+                Queries {
+                    queries: trigram_queries,
+                    word: value.word.clone(),
+                    edit_candidates: value.edit_candidates.clone(),
+                },
+            );
+        }
+        rest.insert(
+            context,
+            Queries {
+                queries: other_queries,
+                word: value.word,
+                edit_candidates: value.edit_candidates,
+            },
+        );
+    }
+
+    (rest, trigrams)
+}
+
+/// Drops the queries of the contexts that already have a confident bigram correction.
+///
+/// A correction is confident when the bigram frequency of the best candidate, which is not the
+/// examined word, beats the runner up by at least the margin in log10 units.
+///
+/// # Arguments
+///
+/// * `queries` - The trigram queries of every context.
+/// * `results` - The results of the bigram queries.
+/// * `margin` - The margin.
+///
+/// # Returns
+///
+/// The queries of the contexts that still need them.
+pub fn skip_confident_contexts(
+    queries: HashMap<ContextKey, Queries>,
+    results: &[SentenceResult],
+    margin: f64,
+) -> HashMap<ContextKey, Queries> {
+    let indexes = index_results(results);
+
+    queries
+        .into_iter()
+        .filter(|(context, value)| {
+            let candidates = match value.queries.first() {
+                Some(query) => &query.varying_params,
+                None => return false,
+            };
+            match indexes.get(context) {
+                Some(&index) => !is_confident_correction(&results[index], candidates, margin),
+                None => true,
+            }
+        })
+        .collect()
+}
+
+/// Checks whether the bigram results confidently pick a candidate other than the examined word.
+///
+/// # Arguments
+///
+/// * `result` - The results of the context.
+/// * `candidates` - The candidates.
+/// * `margin` - The margin.
+///
+/// # Returns
+///
+/// `true` if the correction is confident.
+fn is_confident_correction(result: &SentenceResult, candidates: &[String], margin: f64) -> bool {
+    let mut frequencies: Vec<(&String, i64)> = candidates
+        .iter()
+        .map(|candidate| {
+            let frequency = result
+                .results
+                .iter()
+                .filter(|query_result| query_result.length == 2)
+                .filter(|query_result| {
+                    query_result
+                        .input
+                        .split_whitespace()
+                        .any(|word| word == candidate)
+                })
+                .map(|query_result| query_result.frequency as i64)
+                .sum();
+            (candidate, frequency)
+        })
+        .collect();
+
+    frequencies.sort_by_key(|(_, frequency)| std::cmp::Reverse(*frequency));
+
+    match frequencies.as_slice() {
+        [(best, best_frequency), (_, runner_up), ..] => {
+            **best != result.word
+                && ((*best_frequency + 1) as f64 / (*runner_up + 1) as f64).log10() >= margin
+        }
+        _ => false,
+    }
+}
+
+/// The error of a query whose results can no longer be received.
+static RECEIVER_DROPPED: &str = "The receiver of the query results was dropped";
+
+/// Processes the query.
+///
+/// # Arguments
+///
+/// * `keys` - The keys of the contexts the results are sent to.
+/// * `store` - The n-gram store.
+/// * `builder` - The query with its static and varying values.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+/// * `tx` - The sender, the flag tells whether the result was returned by Scylla or zero-filled.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the query is successful, otherwise a `String` with the error message.
+async fn process(
+    keys: Vec<ContextKey>,
+    store: &dyn NgramStore,
+    builder: &QueryBuilder,
+    skip_backfill: bool,
+    tx: mpsc::Sender<(ContextKey, QueryResult, bool)>,
+) -> Result<(), String> {
+    let static_values: Vec<&str> = builder.static_params.iter().map(|s| s.as_str()).collect();
+
+    let query = VaryingQuery {
+        query: builder.query.clone(),
+        static_params: builder.static_params.clone(),
+        index: builder.shape.varying_index(),
+        candidates: builder.varying_params.clone(),
+        max_rows: None,
+        skipped: builder.shape.skipped_index(),
+    };
+
+    let rows = store.varying(&query).await.map_err(|err| {
+        format!(
+            "Query for {} failed: {}",
+            get_n_gram_string(builder.shape, &static_values, "?"),
+            err
+        )
+    })?;
+
+    let mut words_received = vec![];
+
+    // every row of a skip-gram is a different skipped word, they all add up to the skip-gram
+    let policy = match builder.shape.skipped_index() {
+        Some(_) => DuplicatePolicy::Sum,
+        None => duplicate_policy(),
+    };
+
+    for (word, freq) in merge_duplicate_rows(rows, policy) {
+        let input = get_n_gram_string(builder.shape, &static_values, word.as_str());
+        words_received.push(word.clone());
+        for key in &keys {
+            tx.send((
+                key.clone(),
+                QueryResult {
+                    input: input.clone(),
+                    frequency: freq,
+                    length: input.split_whitespace().count() as i32,
+                },
+                true,
+            ))
+            .map_err(|_| RECEIVER_DROPPED.to_string())?;
+        }
+    }
+
+    for result in backfill(builder, &words_received, skip_backfill) {
+        for key in &keys {
+            tx.send((key.clone(), result.clone(), false))
+                .map_err(|_| RECEIVER_DROPPED.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the duplicate rows of the same word, keeping the order of the first occurrences.
+///
+/// # Arguments
+///
+/// * `rows` - The words and their frequencies.
+/// * `policy` - How the frequencies of the duplicates are merged.
+///
+/// # Returns
+///
+/// The rows with one entry per word.
+fn merge_duplicate_rows(rows: Vec<(String, i32)>, policy: DuplicatePolicy) -> Vec<(String, i32)> {
+    let mut merged: Vec<(String, i32)> = vec![];
+
+    for (word, freq) in rows {
+        match merged
+            .iter_mut()
+            .find(|(merged_word, _)| *merged_word == word)
+        {
+            Some((_, merged_freq)) => *merged_freq = policy.merge(*merged_freq, freq),
+            None => merged.push((word, freq)),
+        }
+    }
+
+    merged
+}
+
+/// Creates the zero frequency results of the candidates that were not returned.
+///
+/// # Arguments
+///
+/// * `builder` - The query with its static and varying values.
+/// * `words_received` - The candidates that were returned.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+///
+/// # Returns
+///
+/// The zero frequency results, empty if `skip_backfill` is set.
+fn backfill(
+    builder: &QueryBuilder,
+    words_received: &[String],
+    skip_backfill: bool,
+) -> Vec<QueryResult> {
+    if skip_backfill {
+        return vec![];
+    }
+
+    let static_values: Vec<&str> = builder.static_params.iter().map(|s| s.as_str()).collect();
+
+    builder
+        .varying_params
+        .iter()
+        .filter(|word| !words_received.contains(word))
+        .map(|word| {
+            let input = get_n_gram_string(builder.shape, &static_values, word);
+            QueryResult {
+                length: input.split_whitespace().count() as i32,
+                input,
+                frequency: 0,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::InMemoryStore;
+    use futures::{future::BoxFuture, TryStreamExt};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    /// Counts the queries, and the ones running at the same time, every query takes a few milliseconds.
+    struct ConcurrencyCountingStore {
+        store: InMemoryStore,
+        queries: AtomicUsize,
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl ConcurrencyCountingStore {
+        fn new(counts: HashMap<String, i32>) -> Self {
+            ConcurrencyCountingStore {
+                store: InMemoryStore::new(counts),
+                queries: AtomicUsize::new(0),
+                running: AtomicUsize::new(0),
+                max_running: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl NgramStore for ConcurrencyCountingStore {
+        fn frequency<'a>(
+            &'a self,
+            query: &'a str,
+            n_gram: Vec<String>,
+        ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+            self.store.frequency(query, n_gram)
+        }
+
+        fn varying<'a>(
+            &'a self,
+            query: &'a VaryingQuery,
+        ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+            Box::pin(async move {
+                self.queries.fetch_add(1, Ordering::SeqCst);
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_running.fetch_max(running, Ordering::SeqCst);
+
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                let result = self.store.varying(query).await;
+
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                result
+            })
+        }
+    }
+
+    /// Fails the unigram queries, like a Scylla node timing out on some of the queries.
+    struct UnigramFailingStore {
+        store: InMemoryStore,
+    }
+
+    impl NgramStore for UnigramFailingStore {
+        fn frequency<'a>(
+            &'a self,
+            query: &'a str,
+            n_gram: Vec<String>,
+        ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+            self.store.frequency(query, n_gram)
+        }
+
+        fn varying<'a>(
+            &'a self,
+            query: &'a VaryingQuery,
+        ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+            if query.static_params.is_empty() {
+                return Box::pin(async { Err("Operation timed out".to_string()) });
+            }
+            self.store.varying(query)
+        }
+    }
+
+    /// The context, the examined word and the inputs with their frequencies of a sentence result.
+    type ComparableResult = (String, String, Vec<(String, i32)>);
+
+    /// Sorts the sentence results and their query results, to compare them.
+    fn sorted_results(results: Vec<SentenceResult>) -> Vec<ComparableResult> {
+        let mut results: Vec<ComparableResult> = results
+            .into_iter()
+            .map(|result| {
+                let mut inputs: Vec<(String, i32)> = result
+                    .results
+                    .into_iter()
+                    .map(|result| (result.input, result.frequency))
+                    .collect();
+                inputs.sort();
+                (result.sentence, result.word, inputs)
+            })
+            .collect();
+        results.sort();
+        results
+    }
+
+    /// Finds the queries by scanning every word of the confusion set against every sentence,
+    /// the way they were found before the word index.
+    fn find_queries_by_scanning(
+        solver: &SolverWithConfusionSet,
+        sentences: &[Vec<String>],
+    ) -> HashMap<ContextKey, Queries> {
+        let mut queries = HashMap::new();
+
+        for sentence in sentences {
+            let words: Vec<&str> = sentence.iter().map(|word| word.as_str()).collect();
+            let cleaned: Vec<&str> = words.iter().map(|word| strip_punctuation(word)).collect();
+
+            for word in solver.confusion_set.iter().flatten() {
+                if solver.canonical_words.contains(word) {
+                    continue;
+                }
+                for j in 0..words.len() {
+                    if cleaned[j].to_lowercase() == word.to_lowercase() {
+                        let candidates = solver.candidates(word);
+                        solver.process_word_in_sentence(
+                            word,
+                            j,
+                            &words,
+                            &cleaned,
+                            &candidates,
+                            &mut queries,
+                        );
+                    }
+                }
+            }
+        }
+
+        queries
+    }
+
+    /// Sorts the queries by their key, to compare them.
+    fn sorted(queries: HashMap<ContextKey, Queries>) -> Vec<(String, String, Vec<QueryBuilder>)> {
+        let mut queries: Vec<(String, String, Vec<QueryBuilder>)> = queries
+            .into_iter()
+            .map(|(key, queries)| (key.context, key.word, queries.queries))
+            .collect();
+        queries.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        queries
+    }
+
+    #[test]
+    fn test_extract_context() {
+        let words = vec!["Krleža", "sve", "oduševio", "svojim", "dijelom"];
+
+        let context = extract_context(4, &words, DEFAULT_WINDOW);
+        assert_eq!(context, "oduševio svojim dijelom");
+    }
+
+    #[test]
+    fn test_extract_context_window() {
+        let words = vec!["Krleža", "sve", "oduševio", "svojim", "dijelom"];
+
+        assert_eq!(extract_context(2, &words, 1), "sve oduševio svojim");
+        assert_eq!(extract_context(0, &words, 1), "Krleža sve");
+        assert_eq!(
+            extract_context(1, &words, 3),
+            "Krleža sve oduševio svojim dijelom"
+        );
+        assert_eq!(extract_context(4, &words, 3), "sve oduševio svojim dijelom");
+    }
+
+    #[test]
+    fn test_find_queries_window_one() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &confusion_set,
+        )
+        .unwrap()
+        .with_window(1);
+
+        let queries = solver.find_queries();
+        let queries = &queries[&ContextKey::new("mene zahtjeva da", "zahtjeva")];
+
+        assert_eq!(queries.queries.len(), 3);
+        assert!(queries.queries.iter().all(|query| query.shape.order() <= 2));
+    }
+
+    #[test]
+    fn test_find_queries_window_three() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &confusion_set,
+        )
+        .unwrap()
+        .with_window(3);
+
+        let queries = solver.find_queries();
+        let queries = &queries[&ContextKey::new("On od mene zahtjeva da dođem", "zahtjeva")];
+
+        assert_eq!(queries.queries.len(), 5);
+        assert!(queries
+            .queries
+            .iter()
+            .any(|query| query.shape == QueryShape::ThreeVary3));
+    }
+
+    #[test]
+    fn test_find_queries_directional_confusion_set() {
+        let confusion_set = vec![vec!["bih".to_string(), "bi".to_string()]];
+        let solver = SolverWithConfusionSet::new(
+            "Ja bih to rekao. On bi to rekao".to_string(),
+            &confusion_set,
+        )
+        .unwrap()
+        .with_canonical_words(HashSet::from(["bih".to_string()]));
+
+        let queries = solver.find_queries();
+
+        assert!(!queries.is_empty());
+        assert!(queries.values().all(|q| q.word == "bi"));
+    }
+
+    #[test]
+    fn test_find_queries_sanitizes_words() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtj\u{200D}eva da dolazim".to_string(),
+            &confusion_set,
+        )
+        .unwrap();
+
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 1);
+        assert!(queries.contains_key(&ContextKey::new("od mene zahtjeva da dolazim", "zahtjeva")));
+    }
+
+    fn bigram_result(input: &str, frequency: i32) -> QueryResult {
+        QueryResult {
+            input: input.to_string(),
+            frequency,
+            length: 2,
+        }
+    }
+
+    #[test]
+    fn test_merge_results_counts_rows_fetched() {
+        let mut sentence_results = vec![SentenceResult {
+            sentence: "mene zahtjeva da".to_string(),
+            word: "zahtjeva".to_string(),
+            results: vec![],
+            rows_fetched: 0,
+            edit_candidates: vec![],
+        }];
+        let key = ContextKey::new("mene zahtjeva da", "zahtjeva");
+
+        merge_results(
+            &mut sentence_results,
+            vec![
+                (key.clone(), bigram_result("mene zahtijeva", 5), true),
+                (key.clone(), bigram_result("mene zahtjeva", 4), true),
+                (key.clone(), bigram_result("zahtijeva da", 0), false),
+                (key, bigram_result("zahtjeva da", 0), false),
+            ],
+        );
+
+        assert_eq!(sentence_results[0].results.len(), 4);
+        assert_eq!(sentence_results[0].rows_fetched, 2);
+    }
+
+    #[test]
+    fn test_merge_results_many_contexts() {
+        let mut sentence_results: Vec<SentenceResult> = (0..2000)
+            .map(|i| SentenceResult {
+                sentence: format!("kontekst {}", i),
+                word: "zahtjeva".to_string(),
+                results: vec![],
+                rows_fetched: 0,
+                edit_candidates: vec![],
+            })
+            .collect();
+        let received: Vec<(ContextKey, QueryResult, bool)> = (0..2000)
+            .rev()
+            .flat_map(|i| {
+                let key = ContextKey::new(&format!("kontekst {}", i), "zahtjeva");
+                vec![
+                    (key.clone(), bigram_result("mene zahtijeva", i), true),
+                    (key, bigram_result("mene zahtjeva", 0), false),
+                ]
+            })
+            .chain(std::iter::once((
+                ContextKey::new("kontekst 0", "zahtijeva"),
+                bigram_result("mene zahtijeva", 1),
+                true,
+            )))
+            .collect();
+
+        merge_results(&mut sentence_results, received);
+
+        for (i, sentence_result) in sentence_results.iter().enumerate() {
+            assert_eq!(sentence_result.sentence, format!("kontekst {}", i));
+            assert_eq!(sentence_result.results.len(), 2);
+            assert_eq!(sentence_result.results[0].frequency, i as i32);
+            assert_eq!(sentence_result.rows_fetched, 1);
+        }
+    }
+
+    #[test]
+    fn test_replay_queries() {
+        let obj = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &[vec!["zahtijeva".to_string(), "zahtjeva".to_string()]],
+        )
+        .unwrap();
+        let counts = HashMap::from([
+            ("zahtijeva".to_string(), 412870),
+            ("zahtijeva da".to_string(), 58213),
+        ]);
+
+        let results = replay_queries(obj.find_queries(), &counts, false);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].rows_fetched, 2);
+        assert_eq!(results[0].results.len(), 10);
+        assert!(results[0]
+            .results
+            .iter()
+            .any(|result| result.input == "zahtijeva da" && result.frequency == 58213));
+
+        let results = replay_queries(obj.find_queries(), &counts, true);
+
+        assert_eq!(results[0].results.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_duplicate_rows() {
+        let rows = vec![
+            ("zahtijeva".to_string(), 10),
+            ("zahtjeva".to_string(), 3),
+            ("zahtijeva".to_string(), 7),
+        ];
+
+        assert_eq!(
+            merge_duplicate_rows(rows.clone(), DuplicatePolicy::Max),
+            vec![("zahtijeva".to_string(), 10), ("zahtjeva".to_string(), 3)]
+        );
+        assert_eq!(
+            merge_duplicate_rows(rows, DuplicatePolicy::Sum),
+            vec![("zahtijeva".to_string(), 17), ("zahtjeva".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_backfill() {
+        let confusion_set = vec![
+            "zahtijeva".to_string(),
+            "zahtjeva".to_string(),
+            "zahtjevaju".to_string(),
+        ];
+        let mut queries = vec![];
+        add_to_query(
+            QueryShape::TwoVary2,
+            &["mene", "zahtjeva"],
+            &confusion_set,
+            &mut queries,
+        );
+        let received = vec!["zahtjeva".to_string()];
+
+        let results = backfill(&queries[0], &received, false);
+        let inputs: Vec<&str> = results.iter().map(|r| r.input.as_str()).collect();
+
+        assert_eq!(inputs, vec!["mene zahtijeva", "mene zahtjevaju"]);
+        assert!(results.iter().all(|r| r.frequency == 0 && r.length == 2));
+        assert!(backfill(&queries[0], &received, true).is_empty());
+    }
+
+    #[test]
+    fn test_add_to_query_skips_short_confusion_sets() {
+        let mut queries = vec![];
+
+        add_to_query(
+            QueryShape::TwoVary2,
+            &["mene", "zahtjeva"],
+            &["zahtjeva".to_string()],
+            &mut queries,
+        );
+        add_to_query(
+            QueryShape::TwoVary2,
+            &["mene", "zahtjeva"],
+            &[],
+            &mut queries,
+        );
+
+        assert!(queries.is_empty());
+    }
+
+    #[test]
+    fn test_find_queries_skips_short_confusion_sets() {
+        let text = "On od mene zahtjeva da dođem".to_string();
+
+        for confusion_set in [vec![vec!["zahtjeva".to_string()]], vec![vec![]]] {
+            let solver = SolverWithConfusionSet::new(text.clone(), &confusion_set).unwrap();
+
+            assert!(solver.find_queries().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_early_stop_skips_trigram_queries() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("On od mene zahtjeva da".to_string(), &confusion_set)
+                .unwrap();
+
+        let (queries, trigram_queries) = split_trigram_queries(solver.find_queries());
+
+        let context = ContextKey::new("od mene zahtjeva da", "zahtjeva");
+        assert!(queries[&context]
+            .queries
+            .iter()
+            .all(|q| !is_trigram_query(q)));
+        assert_eq!(trigram_queries[&context].queries.len(), 1);
+
+        let confident = vec![SentenceResult {
+            sentence: context.context.clone(),
+            word: "zahtjeva".to_string(),
+            results: vec![
+                bigram_result("mene zahtijeva", 500),
+                bigram_result("zahtijeva da", 300),
+                bigram_result("mene zahtjeva", 2),
+            ],
+            rows_fetched: 3,
+            edit_candidates: vec![],
+        }];
+        let unsure = vec![SentenceResult {
+            sentence: context.context.clone(),
+            word: "zahtjeva".to_string(),
+            results: vec![
+                bigram_result("mene zahtijeva", 5),
+                bigram_result("mene zahtjeva", 4),
+            ],
+            rows_fetched: 2,
+            edit_candidates: vec![],
+        }];
+
+        let (_, trigram_queries) = split_trigram_queries(solver.find_queries());
+        assert!(skip_confident_contexts(trigram_queries, &confident, 1.0).is_empty());
+
+        let (_, trigram_queries) = split_trigram_queries(solver.find_queries());
+        assert!(skip_confident_contexts(trigram_queries, &unsure, 1.0).contains_key(&context));
+    }
+
+    #[test]
+    fn test_find_queries_matches_whole_words() {
+        let confusion_set = vec![vec!["je".to_string(), "jest".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("Hrvatski jezik ima dvoje".to_string(), &confusion_set)
+                .unwrap();
+
+        assert!(solver.find_queries().is_empty());
+    }
+
+    #[test]
+    fn test_find_queries_ignores_attached_punctuation() {
+        let confusion_set = vec![vec!["je".to_string(), "jest".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("Tako mi je:".to_string(), &confusion_set).unwrap();
+
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[&ContextKey::new("Tako mi je:", "je")].word, "je");
+        assert_eq!(strip_punctuation("„je,"), "je");
+    }
+
+    #[test]
+    fn test_find_queries_strips_punctuation_from_query_words() {
+        let confusion_set = vec![vec!["je".to_string(), "jest".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("Tako „mi (je)“ rekao: dođi".to_string(), &confusion_set)
+                .unwrap();
+
+        let queries = solver.find_queries();
+        let key = ContextKey::new("Tako „mi (je)“ rekao: dođi", "je");
+
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[&key].word, "je");
+
+        let static_params: Vec<&String> = queries[&key]
+            .queries
+            .iter()
+            .flat_map(|query| query.static_params.iter())
+            .collect();
+
+        assert!(static_params.contains(&&"mi".to_string()));
+        assert!(static_params.contains(&&"rekao".to_string()));
+        assert!(static_params
+            .iter()
+            .all(|param| param.chars().all(char::is_alphanumeric)));
+    }
+
+    #[test]
+    fn test_find_queries_matches_scanning() {
+        let text = "Danas sam poslao dva zahtijeva prodekanu za nastavu. On od mene zahtjeva da dolazim na nastavu. Ona Zahtijeva, a ja bi išao. Što je, je.";
+        let confusion_set = vec![
+            vec!["zahtijeva".to_string(), "zahtjeva".to_string()],
+            vec!["je".to_string(), "jest".to_string()],
+            vec!["bi".to_string(), "bih".to_string()],
+            vec!["je".to_string(), "je li".to_string()],
+        ];
+        let solver = SolverWithConfusionSet::new(text.to_string(), &confusion_set)
+            .unwrap()
+            .with_canonical_words(HashSet::from(["bih".to_string()]));
+        let sentences = tokenize(text);
+
+        assert_eq!(
+            sorted(solver.find_queries_in(&sentences)),
+            sorted(find_queries_by_scanning(&solver, &sentences))
+        );
+    }
+
+    #[test]
+    fn test_find_queries_with_large_confusion_set() {
+        let mut confusion_set: Vec<Vec<String>> = (0..5000)
+            .map(|i| vec![format!("riječ{}a", i), format!("riječ{}b", i)])
+            .collect();
+        confusion_set.push(vec!["zahtijeva".to_string(), "zahtjeva".to_string()]);
+
+        let text = "On od mene zahtjeva da dolazim na nastavu. ".repeat(20);
+        let solver = SolverWithConfusionSet::new(text.clone(), &confusion_set).unwrap();
+        let sentences = tokenize(&text);
+
+        let start = Instant::now();
+        let indexed = solver.find_queries_in(&sentences);
+        let indexed_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        let scanned = find_queries_by_scanning(&solver, &sentences);
+        let scanned_elapsed = start.elapsed();
+
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(sorted(indexed), sorted(scanned));
+        assert!(indexed_elapsed < scanned_elapsed);
+        assert!(indexed_elapsed < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_run_queries_bounds_concurrency() {
+        let confusion_set = vec![vec!["je".to_string(), "jest".to_string()]];
+        let text = "Ana je rekla. Ivan je došao. Sunce je sjalo. Kiša je pala. Pas je lajao.";
+        let solver = SolverWithConfusionSet::new(text.to_string(), &confusion_set).unwrap();
+        let counts = HashMap::from([
+            ("je".to_string(), 1000),
+            ("Ana je".to_string(), 12),
+            ("je rekla".to_string(), 40),
+            ("Ivan je došao".to_string(), 3),
+        ]);
+
+        let bounded = Arc::new(ConcurrencyCountingStore::new(counts.clone()));
+        let store: Arc<dyn NgramStore> = bounded.clone();
+        let (results, errors) = run_queries(solver.find_queries(), &store, false, 2).await;
+
+        let unbounded = Arc::new(ConcurrencyCountingStore::new(counts));
+        let store: Arc<dyn NgramStore> = unbounded.clone();
+        let (expected, _) =
+            run_queries(solver.find_queries(), &store, false, Semaphore::MAX_PERMITS).await;
+
+        assert!(errors.is_empty());
+        assert!(bounded.max_running.load(Ordering::SeqCst) <= 2);
+        assert!(unbounded.max_running.load(Ordering::SeqCst) > 2);
+        assert_eq!(sorted_results(results), sorted_results(expected));
+    }
+
+    #[tokio::test]
+    async fn test_execute_queries_collects_errors() {
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &[vec!["zahtijeva".to_string(), "zahtjeva".to_string()]],
+        )
+        .unwrap();
+        let store: Arc<dyn NgramStore> = Arc::new(UnigramFailingStore {
+            store: InMemoryStore::new(HashMap::from([("mene zahtijeva".to_string(), 5)])),
+        });
+
+        let result =
+            execute_queries(solver.find_queries(), store, &ExecuteOptions::default()).await;
+
+        assert_eq!(
+            result.errors,
+            vec!["Query for ? failed: Operation timed out"]
+        );
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].rows_fetched, 1);
+        assert!(result.results[0]
+            .results
+            .iter()
+            .all(|query_result| query_result.length > 1));
+    }
+
+    #[tokio::test]
+    async fn test_execute_queries_streamed() {
+        let confusion_set = vec![vec!["je".to_string(), "jest".to_string()]];
+        let text = "Ana je rekla. Ivan je došao. Sunce je sjalo.";
+        let solver = SolverWithConfusionSet::new(text.to_string(), &confusion_set).unwrap();
+        let counts = HashMap::from([
+            ("je".to_string(), 1000),
+            ("Ana je".to_string(), 12),
+            ("je rekla".to_string(), 40),
+            ("Ivan je došao".to_string(), 3),
+        ]);
+        let queries = solver.find_queries();
+        let distinct = group_queries(&queries).len();
+
+        let counting = Arc::new(ConcurrencyCountingStore::new(counts.clone()));
+        let store: Arc<dyn NgramStore> = counting.clone();
+        let streamed: Vec<SentenceResult> =
+            execute_queries_streamed(queries, store, &ExecuteOptions::default())
+                .try_collect()
+                .await
+                .unwrap();
+
+        // the unigram query is shared by the sentences, it still runs once
+        assert_eq!(counting.queries.load(Ordering::SeqCst), distinct);
+        assert_eq!(streamed.len(), 3);
+
+        for options in [
+            ExecuteOptions::default(),
+            ExecuteOptions {
+                early_stop: Some(0.5),
+                skip_backfill: true,
+            },
+        ] {
+            let store: Arc<dyn NgramStore> = Arc::new(InMemoryStore::new(counts.clone()));
+            let streamed: Vec<SentenceResult> =
+                execute_queries_streamed(solver.find_queries(), store, &options)
+                    .try_collect()
+                    .await
+                    .unwrap();
+
+            let store: Arc<dyn NgramStore> = Arc::new(InMemoryStore::new(counts.clone()));
+            let expected = execute_queries(solver.find_queries(), store, &options).await;
+
+            assert_eq!(sorted_results(streamed), sorted_results(expected.results));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_queries_streamed_sends_errors() {
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &[vec!["zahtijeva".to_string(), "zahtjeva".to_string()]],
+        )
+        .unwrap();
+        let store: Arc<dyn NgramStore> = Arc::new(UnigramFailingStore {
+            store: InMemoryStore::new(HashMap::from([("mene zahtijeva".to_string(), 5)])),
+        });
+
+        let streamed: Vec<Result<SentenceResult, String>> =
+            execute_queries_streamed(solver.find_queries(), store, &ExecuteOptions::default())
+                .collect()
+                .await;
+
+        let errors: Vec<&String> = streamed
+            .iter()
+            .filter_map(|item| item.as_ref().err())
+            .collect();
+        let results: Vec<&SentenceResult> = streamed
+            .iter()
+            .filter_map(|item| item.as_ref().ok())
+            .collect();
+
+        assert_eq!(errors, vec!["Query for ? failed: Operation timed out"]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].rows_fetched, 1);
+    }
+
+    #[tokio::test]
+    async fn test_skip_grams() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver = SolverWithConfusionSet::new(
+            "On od mene zahtjeva da dođem.".to_string(),
+            &confusion_set,
+        )
+        .unwrap();
+
+        let key = ContextKey::new("od mene zahtjeva da dođem", "zahtjeva");
+
+        assert_eq!(solver.find_queries()[&key].queries.len(), 5);
+
+        let solver = solver.with_skip_grams(true);
+        let queries = solver.find_queries();
+        let skip_grams: Vec<(QueryShape, Vec<String>)> = queries[&key]
+            .queries
+            .iter()
+            .filter(|query| query.shape.skipped_index().is_some())
+            .map(|query| (query.shape, query.static_params.clone()))
+            .collect();
+
+        assert_eq!(queries[&key].queries.len(), 7);
+        assert_eq!(
+            skip_grams,
+            vec![
+                (QueryShape::SkipVary3, vec!["od".to_string()]),
+                (QueryShape::SkipVary1, vec!["dođem".to_string()]),
+            ]
+        );
+
+        let store: Arc<dyn NgramStore> = Arc::new(InMemoryStore::new(HashMap::from([
+            ("od mene zahtijeva".to_string(), 5),
+            ("od njih zahtijeva".to_string(), 2),
+            ("od tebe zahtjeva".to_string(), 1),
+            ("zahtijeva da dođem".to_string(), 4),
+        ])));
+
+        let result = execute_queries(queries, store, &ExecuteOptions::default()).await;
+        let frequency = |input: &str| {
+            result.results[0]
+                .results
+                .iter()
+                .find(|query_result| query_result.input == input)
+                .map(|query_result| (query_result.frequency, query_result.length))
+        };
+
+        assert_eq!(frequency("od * zahtijeva"), Some((7, 3)));
+        assert_eq!(frequency("od * zahtjeva"), Some((1, 3)));
+        assert_eq!(frequency("zahtjeva * dođem"), Some((0, 3)));
+        assert_eq!(frequency("zahtijeva da dođem"), Some((4, 3)));
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("zahtjeva", "zahtjeva"), 0);
+        assert_eq!(levenshtein("zahtjva", "zahtjeva"), 1);
+        assert_eq!(levenshtein("zahtjva", "zahtijeva"), 2);
+        assert_eq!(levenshtein("kuca", "kuća"), 1);
+        assert_eq!(levenshtein("", "da"), 2);
+        assert_eq!(levenshtein("mene", ""), 4);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_edit_candidates() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let vocabulary: HashSet<String> = ["on", "od", "mene", "zahtijeva", "zahtjeva", "da"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        let solver =
+            SolverWithConfusionSet::new("On od mene zahtjva da xyzqw.".to_string(), &confusion_set)
+                .unwrap();
+
+        assert!(solver.find_queries().is_empty());
+
+        let solver = solver.with_vocabulary(Some(Arc::new(vocabulary)));
+        let queries = solver.find_queries();
+        let key = ContextKey::new("od mene zahtjva da xyzqw", "zahtjva");
+
+        // the known words and the words without close candidates are not examined
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            queries[&key].edit_candidates,
+            vec!["zahtjva", "zahtjeva", "zahtijeva"]
+        );
+        assert!(queries[&key]
+            .queries
+            .iter()
+            .all(|query| query.varying_params == queries[&key].edit_candidates));
+        assert_eq!(solver.find_occurrences()[0].key, key);
+
+        let vocabulary: HashSet<String> = ('a'..='t').map(|c| format!("ku{}a", c)).collect();
+        let solver = solver.with_vocabulary(Some(Arc::new(vocabulary)));
+
+        assert_eq!(solver.edit_candidates("kua").unwrap().len(), 11);
+        assert!(solver.edit_candidates("zahtjeva").is_none());
+        assert!(solver.edit_candidates("k2a").is_none());
+    }
+
+    #[test]
+    fn test_sentence_boundaries_at_the_start() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("Zahtjeva da dođem.".to_string(), &confusion_set).unwrap();
+
+        let key = ContextKey::new("Zahtjeva da dođem", "zahtjeva");
+        let shapes = |queries: &Queries| -> Vec<(QueryShape, Vec<String>)> {
+            queries
+                .queries
+                .iter()
+                .map(|query| (query.shape, query.static_params.clone()))
+                .collect()
+        };
+
+        assert!(!shapes(&solver.find_queries()[&key])
+            .iter()
+            .any(|(_, static_params)| static_params.contains(&SENTENCE_START.to_string())));
+
+        let queries = solver.with_sentence_boundaries(true).find_queries();
+
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            shapes(&queries[&key]),
+            vec![
+                (QueryShape::OneVary, vec![]),
+                (QueryShape::TwoVary2, vec!["<s>".to_string()]),
+                (QueryShape::TwoVary1, vec!["da".to_string()]),
+                (
+                    QueryShape::ThreeVary1,
+                    vec!["da".to_string(), "dođem".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_boundaries_at_the_end() {
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("On od mene zahtjeva.".to_string(), &confusion_set)
+                .unwrap()
+                .with_sentence_boundaries(true);
+
+        let key = ContextKey::new("od mene zahtjeva", "zahtjeva");
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 1);
+        assert_eq!(solver.find_occurrences()[0].key, key);
+
+        let shapes: Vec<(QueryShape, Vec<String>)> = queries[&key]
+            .queries
+            .iter()
+            .map(|query| (query.shape, query.static_params.clone()))
+            .collect();
+
+        assert_eq!(
+            shapes,
+            vec![
+                (QueryShape::OneVary, vec![]),
+                (QueryShape::TwoVary2, vec!["mene".to_string()]),
+                (QueryShape::TwoVary1, vec!["</s>".to_string()]),
+                (
+                    QueryShape::ThreeVary3,
+                    vec!["od".to_string(), "mene".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_queries_runs_shared_queries_once() {
+        let confusion_set = vec![
+            vec!["je".to_string(), "jest".to_string()],
+            vec!["bi".to_string(), "bih".to_string()],
+        ];
+        let solver = SolverWithConfusionSet::new(
+            "Ja bi išao, ali on je rekao da bi ostao jer je kasno".to_string(),
+            &confusion_set,
+        )
+        .unwrap();
+        let queries = solver.find_queries();
+
+        let total: usize = queries.values().map(|value| value.queries.len()).sum();
+        let grouped = group_queries(&queries);
+
+        // both contexts of "je" and both contexts of "bi" share their unigram query
+        assert_eq!(total, 18);
+        assert_eq!(grouped.len(), 16);
+        assert_eq!(
+            grouped.iter().map(|(_, keys)| keys.len()).sum::<usize>(),
+            total
+        );
+    }
+
+    #[test]
+    fn test_find_queries_words_sharing_context() {
+        let confusion_set = vec![
+            vec!["bi".to_string(), "bih".to_string()],
+            vec!["je".to_string(), "jest".to_string()],
+        ];
+        let solver = SolverWithConfusionSet::new("On bi je".to_string(), &confusion_set).unwrap();
+
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[&ContextKey::new("On bi je", "bi")].word, "bi");
+        assert_eq!(queries[&ContextKey::new("On bi je", "je")].word, "je");
+    }
+
+    #[test]
+    fn test_find_queries_overlapping_groups() {
+        let confusion_set = vec![
+            vec!["bi".to_string(), "bih".to_string()],
+            vec!["bi".to_string(), "bii".to_string()],
+        ];
+        let solver =
+            SolverWithConfusionSet::new("Ja bi htio doći".to_string(), &confusion_set).unwrap();
+
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 1);
+        for query in &queries[&ContextKey::new("Ja bi htio doći", "bi")].queries {
+            assert_eq!(query.varying_params, vec!["bi", "bih", "bii"]);
+        }
+    }
+
+    #[test]
+    fn test_find_queries_selected_groups() {
+        let contents = "yat: zahtijeva zahtjeva\nsto što";
+        let confusion_set = crate::select_groups(
+            &crate::parse_confusion_set(contents.to_string()).unwrap(),
+            &crate::parse_group_labels(contents),
+            "yat",
+        )
+        .unwrap();
+        let solver =
+            SolverWithConfusionSet::new("Sto on od mene zahtjeva".to_string(), &confusion_set)
+                .unwrap();
+
+        let queries = solver.find_queries();
+
+        assert_eq!(queries.len(), 1);
+        assert!(queries.values().all(|q| q.word == "zahtjeva"));
+    }
+
+    #[test]
+    fn test_count_sentences_delimiters_only() {
+        let confusion_set = vec![vec!["sto".to_string(), "što".to_string()]];
+        let solver = SolverWithConfusionSet::new(". , . ".to_string(), &confusion_set).unwrap();
+
+        assert_eq!(solver.count_sentences(), 0);
+        assert!(solver.find_queries().is_empty());
+    }
+
+    #[test]
+    fn test_count_sentences() {
+        let confusion_set = vec![vec!["sto".to_string(), "što".to_string()]];
+        let solver =
+            SolverWithConfusionSet::new("Ja sam. Ti si, on je.".to_string(), &confusion_set)
+                .unwrap();
+
+        assert_eq!(solver.count_sentences(), 3);
     }
 }
@@ -0,0 +1,762 @@
+use crate::{
+    db::NgramStore,
+    n_grams::solver::{
+        model::{
+            execute_queries, execute_queries_streamed, replay_queries, tokenize, ContextKey,
+            ExecuteOptions, Queries, QueryBuilder, SolverWithConfusionSet, TimedSentenceResults,
+            DEFAULT_WINDOW,
+        },
+        predictor::{predict, Predict, PredictionResult, PredictionResults},
+        AnalyzeOptions,
+    },
+};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::Arc,
+};
+
+/// Represents the stage that splits the text into sentences of words.
+///
+/// # Methods
+///
+/// * `tokenize` - Tokenizes the text.
+pub trait Tokenizer {
+    /// Tokenizes the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// The words of every sentence.
+    fn tokenize(&self, text: &str) -> Vec<Vec<String>>;
+}
+
+/// Represents the stage that builds the queries of the examined words.
+///
+/// # Methods
+///
+/// * `plan` - Builds the queries.
+pub trait QueryPlanner {
+    /// Builds the queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `solver` - The solver, with the confusion set, the canonical words and the window.
+    /// * `sentences` - The words of every sentence.
+    ///
+    /// # Returns
+    ///
+    /// The queries of every examined word in its context.
+    fn plan(
+        &self,
+        solver: &SolverWithConfusionSet,
+        sentences: &[Vec<String>],
+    ) -> HashMap<ContextKey, Queries>;
+}
+
+/// Represents the stage that runs the queries.
+///
+/// # Methods
+///
+/// * `execute` - Runs the queries.
+pub trait Executor {
+    /// Runs the queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The queries.
+    ///
+    /// # Returns
+    ///
+    /// The timed sentence results, `sentences` is left for the pipeline to fill in.
+    fn execute(
+        &self,
+        queries: HashMap<ContextKey, Queries>,
+    ) -> impl Future<Output = TimedSentenceResults>;
+}
+
+/// Represents the planned queries of an examined word in its context.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `word` - The examined word.
+/// * `queries` - The queries, with their CQL, static and varying parameters.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedQueries {
+    pub context: String,
+    pub word: String,
+    pub queries: Vec<QueryBuilder>,
+}
+
+/// The default tokenizer, splits the sentences on `SENTENCE_DELIMITERS` and the words on whitespace.
+pub struct SentenceTokenizer;
+
+impl Tokenizer for SentenceTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Vec<String>> {
+        tokenize(text)
+    }
+}
+
+/// The default query planner, the unigram, bigram and trigram queries of every context.
+pub struct ContextQueryPlanner;
+
+impl QueryPlanner for ContextQueryPlanner {
+    fn plan(
+        &self,
+        solver: &SolverWithConfusionSet,
+        sentences: &[Vec<String>],
+    ) -> HashMap<ContextKey, Queries> {
+        solver.find_queries_in(sentences)
+    }
+}
+
+/// The default executor, runs the queries against an n-gram store.
+///
+/// # Fields
+///
+/// * `store` - The n-gram store.
+/// * `options` - The options of the query execution.
+pub struct StoreExecutor {
+    pub store: Arc<dyn NgramStore>,
+    pub options: ExecuteOptions,
+}
+
+impl Executor for StoreExecutor {
+    fn execute(
+        &self,
+        queries: HashMap<ContextKey, Queries>,
+    ) -> impl Future<Output = TimedSentenceResults> {
+        execute_queries(queries, Arc::clone(&self.store), &self.options)
+    }
+}
+
+/// An executor that answers the queries from recorded n-gram frequencies, without Scylla.
+///
+/// # Fields
+///
+/// * `counts` - The recorded frequencies, keyed by the n-gram.
+/// * `skip_backfill` - Whether the candidates that were not returned are left out.
+pub struct RecordedExecutor {
+    pub counts: HashMap<String, i32>,
+    pub skip_backfill: bool,
+}
+
+impl Executor for RecordedExecutor {
+    async fn execute(&self, queries: HashMap<ContextKey, Queries>) -> TimedSentenceResults {
+        let start = std::time::Instant::now();
+        let results = replay_queries(queries, &self.counts, self.skip_backfill);
+        let elapsed = start.elapsed().as_millis();
+
+        TimedSentenceResults {
+            time_taken: format!("{} ms", elapsed),
+            time_taken_ms: elapsed,
+            results,
+            sentences: 0,
+            errors: vec![],
+        }
+    }
+}
+
+/// Represents the `/check` pipeline, every stage can be swapped.
+///
+/// The confusion set and the n-gram counts are borrowed from the loaded data, unless the request
+/// brought its own.
+///
+/// # Fields
+///
+/// * `tokenizer` - Splits the text into sentences of words.
+/// * `planner` - Builds the queries of the examined words.
+/// * `executor` - Runs the queries.
+/// * `predictor` - Scores the candidates.
+/// * `confusion_set` - The confusion set.
+/// * `canonical_words` - The canonical words of the directional confusion groups.
+/// * `window` - The number of words on each side of the examined word.
+/// * `skip_grams` - Whether the skip-bigrams around the examined word are queried as well.
+/// * `sentence_boundaries` - Whether the sentence boundaries are queried.
+/// * `vocabulary` - The vocabulary of the edit candidates, `None` to examine only the confusion set.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+///
+/// # Methods
+///
+/// * `new` - Creates a new `Pipeline` with the default tokenizer and query planner.
+/// * `from_options` - Creates a new `Pipeline` over the n-gram store with the options of the analysis.
+/// * `stream` - Runs all the stages over the n-gram store, streaming the predictions.
+/// * `into_owned` - Copies the borrowed confusion set and n-gram counts into the `Pipeline`.
+/// * `with_tokenizer` - Replaces the tokenizer.
+/// * `with_planner` - Replaces the query planner.
+/// * `with_canonical_words` - Sets the canonical words.
+/// * `with_window` - Sets the window.
+/// * `with_skip_grams` - Sets whether the skip-bigrams are queried.
+/// * `with_sentence_boundaries` - Sets whether the sentence boundaries are queried.
+/// * `with_vocabulary` - Sets the vocabulary of the edit candidates.
+/// * `solver` - Creates the solver of the text.
+/// * `plan` - Runs the stages up to the query planning, without executing the queries.
+/// * `solve` - Runs the stages up to the execution.
+/// * `run` - Runs all the stages.
+/// * `run_with_solver` - Runs all the stages, keeping the solver of the text.
+pub struct Pipeline<'a, E, T = SentenceTokenizer, P = ContextQueryPlanner> {
+    pub tokenizer: T,
+    pub planner: P,
+    pub executor: E,
+    pub predictor: Box<dyn Predict>,
+    pub confusion_set: Cow<'a, [Vec<String>]>,
+    pub canonical_words: HashSet<String>,
+    pub window: usize,
+    pub skip_grams: bool,
+    pub sentence_boundaries: bool,
+    pub vocabulary: Option<Arc<HashSet<String>>>,
+    pub number_of_ngrams: Cow<'a, HashMap<i32, i64>>,
+    pub number_of_distinct_ngrams: Cow<'a, HashMap<i32, i64>>,
+}
+
+impl<'a, E: Executor> Pipeline<'a, E> {
+    /// Creates a new `Pipeline` with the default tokenizer and query planner.
+    ///
+    /// # Arguments
+    ///
+    /// * `executor` - Runs the queries.
+    /// * `predictor` - Scores the candidates.
+    /// * `confusion_set` - The confusion set.
+    /// * `number_of_ngrams` - The number of n-grams.
+    /// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+    ///
+    /// # Returns
+    ///
+    /// A `Pipeline`.
+    pub fn new(
+        executor: E,
+        predictor: Box<dyn Predict>,
+        confusion_set: Cow<'a, [Vec<String>]>,
+        number_of_ngrams: Cow<'a, HashMap<i32, i64>>,
+        number_of_distinct_ngrams: Cow<'a, HashMap<i32, i64>>,
+    ) -> Self {
+        Pipeline {
+            tokenizer: SentenceTokenizer,
+            planner: ContextQueryPlanner,
+            executor,
+            predictor,
+            confusion_set,
+            canonical_words: HashSet::new(),
+            window: DEFAULT_WINDOW,
+            skip_grams: false,
+            sentence_boundaries: false,
+            vocabulary: None,
+            number_of_ngrams,
+            number_of_distinct_ngrams,
+        }
+    }
+}
+
+impl<'a> Pipeline<'a, StoreExecutor> {
+    /// Creates a new `Pipeline` that runs the queries against the n-gram store, with the options of the analysis.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The n-gram store.
+    /// * `predictor` - Scores the candidates.
+    /// * `confusion_set` - The confusion set.
+    /// * `number_of_ngrams` - The number of n-grams.
+    /// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+    /// * `options` - The options of the analysis.
+    ///
+    /// # Returns
+    ///
+    /// A `Pipeline` with the `StoreExecutor`.
+    pub fn from_options(
+        store: Arc<dyn NgramStore>,
+        predictor: Box<dyn Predict>,
+        confusion_set: Cow<'a, [Vec<String>]>,
+        number_of_ngrams: Cow<'a, HashMap<i32, i64>>,
+        number_of_distinct_ngrams: Cow<'a, HashMap<i32, i64>>,
+        options: AnalyzeOptions,
+    ) -> Self {
+        let executor = StoreExecutor {
+            store,
+            options: options.execute,
+        };
+
+        Pipeline::new(
+            executor,
+            predictor,
+            confusion_set,
+            number_of_ngrams,
+            number_of_distinct_ngrams,
+        )
+        .with_canonical_words(options.canonical_words)
+        .with_window(options.window)
+        .with_skip_grams(options.skip_grams)
+        .with_sentence_boundaries(options.sentence_boundaries)
+        .with_vocabulary(options.vocabulary)
+    }
+
+    /// Runs all the stages, streaming the prediction of every context as soon as its queries are done.
+    ///
+    /// The queries of the whole text are planned and executed at once, so the queries shared by the
+    /// sentences still run once, see `execute_queries_streamed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the stream of the prediction results, and of the errors of the queries
+    /// that failed, otherwise a `String` with the error message.
+    pub fn stream(
+        self,
+        text: String,
+    ) -> Result<impl Stream<Item = Result<PredictionResult, String>> + 'a, String> {
+        let sentences = self.tokenizer.tokenize(&text);
+        let solver = self.solver(text)?;
+
+        let queries = self.planner.plan(&solver, &sentences);
+
+        let results = execute_queries_streamed(
+            queries,
+            Arc::clone(&self.executor.store),
+            &self.executor.options,
+        );
+
+        Ok(results.flat_map(move |result| {
+            let predictions = match result {
+                Ok(result) => predict(
+                    &*self.predictor,
+                    TimedSentenceResults {
+                        time_taken: String::new(),
+                        time_taken_ms: 0,
+                        results: vec![result],
+                        sentences: 0,
+                        errors: vec![],
+                    },
+                    &self.confusion_set,
+                    &self.number_of_ngrams,
+                    &self.number_of_distinct_ngrams,
+                )
+                .results
+                .into_iter()
+                .map(Ok)
+                .collect(),
+                Err(err) => vec![Err(err)],
+            };
+            stream::iter(predictions)
+        }))
+    }
+}
+
+impl<'a, E: Executor, T: Tokenizer, P: QueryPlanner> Pipeline<'a, E, T, P> {
+    /// Copies the borrowed confusion set and n-gram counts into the `Pipeline`, e.g. for a stream
+    /// that outlives the loaded data it was built from.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` that owns its confusion set and n-gram counts.
+    pub fn into_owned(self) -> Pipeline<'static, E, T, P> {
+        Pipeline {
+            tokenizer: self.tokenizer,
+            planner: self.planner,
+            executor: self.executor,
+            predictor: self.predictor,
+            confusion_set: Cow::Owned(self.confusion_set.into_owned()),
+            canonical_words: self.canonical_words,
+            window: self.window,
+            skip_grams: self.skip_grams,
+            sentence_boundaries: self.sentence_boundaries,
+            vocabulary: self.vocabulary,
+            number_of_ngrams: Cow::Owned(self.number_of_ngrams.into_owned()),
+            number_of_distinct_ngrams: Cow::Owned(self.number_of_distinct_ngrams.into_owned()),
+        }
+    }
+
+    /// Replaces the tokenizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokenizer` - The tokenizer.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the tokenizer.
+    pub fn with_tokenizer<U: Tokenizer>(self, tokenizer: U) -> Pipeline<'a, E, U, P> {
+        Pipeline {
+            tokenizer,
+            planner: self.planner,
+            executor: self.executor,
+            predictor: self.predictor,
+            confusion_set: self.confusion_set,
+            canonical_words: self.canonical_words,
+            window: self.window,
+            skip_grams: self.skip_grams,
+            sentence_boundaries: self.sentence_boundaries,
+            vocabulary: self.vocabulary,
+            number_of_ngrams: self.number_of_ngrams,
+            number_of_distinct_ngrams: self.number_of_distinct_ngrams,
+        }
+    }
+
+    /// Replaces the query planner.
+    ///
+    /// # Arguments
+    ///
+    /// * `planner` - The query planner.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the query planner.
+    pub fn with_planner<U: QueryPlanner>(self, planner: U) -> Pipeline<'a, E, T, U> {
+        Pipeline {
+            tokenizer: self.tokenizer,
+            planner,
+            executor: self.executor,
+            predictor: self.predictor,
+            confusion_set: self.confusion_set,
+            canonical_words: self.canonical_words,
+            window: self.window,
+            skip_grams: self.skip_grams,
+            sentence_boundaries: self.sentence_boundaries,
+            vocabulary: self.vocabulary,
+            number_of_ngrams: self.number_of_ngrams,
+            number_of_distinct_ngrams: self.number_of_distinct_ngrams,
+        }
+    }
+
+    /// Sets the canonical words.
+    ///
+    /// # Arguments
+    ///
+    /// * `canonical_words` - The canonical words.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the canonical words.
+    pub fn with_canonical_words(mut self, canonical_words: HashSet<String>) -> Self {
+        self.canonical_words = canonical_words;
+        self
+    }
+
+    /// Sets the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The number of words on each side of the examined word.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the window.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets whether the skip-bigrams are queried, see `SolverWithConfusionSet::with_skip_grams`.
+    ///
+    /// # Arguments
+    ///
+    /// * `skip_grams` - Whether the skip-bigrams are queried.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the skip-bigrams set.
+    pub fn with_skip_grams(mut self, skip_grams: bool) -> Self {
+        self.skip_grams = skip_grams;
+        self
+    }
+
+    /// Sets whether the sentence boundaries are queried, see `SolverWithConfusionSet::with_sentence_boundaries`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sentence_boundaries` - Whether the sentence boundaries are queried.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the sentence boundaries set.
+    pub fn with_sentence_boundaries(mut self, sentence_boundaries: bool) -> Self {
+        self.sentence_boundaries = sentence_boundaries;
+        self
+    }
+
+    /// Sets the vocabulary of the edit candidates, see `SolverWithConfusionSet::with_vocabulary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vocabulary` - The lowercased vocabulary, `None` to examine only the confusion set.
+    ///
+    /// # Returns
+    ///
+    /// The `Pipeline` with the vocabulary set.
+    pub fn with_vocabulary(mut self, vocabulary: Option<Arc<HashSet<String>>>) -> Self {
+        self.vocabulary = vocabulary;
+        self
+    }
+
+    /// Creates the solver of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the solver if the confusion set is not empty, otherwise a `String` with the error message.
+    pub fn solver(&self, text: String) -> Result<SolverWithConfusionSet, String> {
+        Ok(SolverWithConfusionSet::new(text, &self.confusion_set)?
+            .with_canonical_words(self.canonical_words.clone())
+            .with_window(self.window)
+            .with_skip_grams(self.skip_grams)
+            .with_sentence_boundaries(self.sentence_boundaries)
+            .with_vocabulary(self.vocabulary.clone()))
+    }
+
+    /// Runs the stages up to the query planning, without executing the queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the planned queries sorted by the context and the examined word, otherwise a `String` with the error message.
+    pub fn plan(&self, text: String) -> Result<Vec<PlannedQueries>, String> {
+        let sentences = self.tokenizer.tokenize(&text);
+        let solver = self.solver(text)?;
+
+        let mut plan: Vec<PlannedQueries> = self
+            .planner
+            .plan(&solver, &sentences)
+            .into_iter()
+            .map(|(key, queries)| PlannedQueries {
+                context: key.context,
+                word: key.word,
+                queries: queries.queries,
+            })
+            .collect();
+        plan.sort_by(|a, b| (&a.context, &a.word).cmp(&(&b.context, &b.word)));
+
+        Ok(plan)
+    }
+
+    /// Runs the stages up to the execution.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the timed sentence results, otherwise a `String` with the error message.
+    pub async fn solve(&self, text: String) -> Result<TimedSentenceResults, String> {
+        Ok(self.solve_with_solver(text).await?.1)
+    }
+
+    /// Runs the stages up to the execution, keeping the solver of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the solver and the timed sentence results, otherwise a `String` with the error message.
+    async fn solve_with_solver(
+        &self,
+        text: String,
+    ) -> Result<(SolverWithConfusionSet, TimedSentenceResults), String> {
+        let sentences = self.tokenizer.tokenize(&text);
+        let solver = self.solver(text)?;
+
+        let queries = self.planner.plan(&solver, &sentences);
+
+        let mut result = self.executor.execute(queries).await;
+        result.sentences = sentences
+            .iter()
+            .filter(|sentence| !sentence.is_empty())
+            .count();
+
+        Ok((solver, result))
+    }
+
+    /// Runs all the stages.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the prediction results, otherwise a `String` with the error message.
+    pub async fn run(&self, text: String) -> Result<PredictionResults, String> {
+        Ok(self.run_with_solver(text).await?.1)
+    }
+
+    /// Runs all the stages, keeping the solver of the text, e.g. for its occurrences.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the solver and the prediction results, otherwise a `String` with the error message.
+    pub async fn run_with_solver(
+        &self,
+        text: String,
+    ) -> Result<(SolverWithConfusionSet, PredictionResults), String> {
+        let (solver, result) = self.solve_with_solver(text).await?;
+
+        let result = predict(
+            &*self.predictor,
+            result,
+            &self.confusion_set,
+            &self.number_of_ngrams,
+            &self.number_of_distinct_ngrams,
+        );
+
+        Ok((solver, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryStore, n_grams::solver::predictor::MaxPredictor};
+
+    /// Keeps the whole text as one sentence and does not sanitize the words.
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn tokenize(&self, text: &str) -> Vec<Vec<String>> {
+            vec![text
+                .split_whitespace()
+                .map(|word| word.to_string())
+                .collect()]
+        }
+    }
+
+    fn pipeline() -> Pipeline<'static, RecordedExecutor> {
+        Pipeline::new(
+            RecordedExecutor {
+                counts: HashMap::from([
+                    ("zahtijeva".to_string(), 412870),
+                    ("zahtjeva".to_string(), 96512),
+                    ("mene zahtijeva".to_string(), 1204),
+                    ("mene zahtjeva".to_string(), 87),
+                ]),
+                skip_backfill: false,
+            },
+            Box::new(MaxPredictor::default()),
+            Cow::Owned(vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]]),
+            Cow::Owned(HashMap::from([
+                (1, 14038305488),
+                (2, 11860749005),
+                (3, 10042562275),
+            ])),
+            Cow::Owned(HashMap::from([
+                (1, 3275612),
+                (2, 443937190),
+                (3, 1744646259),
+            ])),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_default_pipeline() {
+        let result = pipeline()
+            .run("Ana sve zna. On od mene zahtjeva da dođem.".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(result.sentences, 2);
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].context, "od mene zahtjeva da dođem");
+        assert!(result.results[0].results["zahtijeva"] < result.results[0].results["zahtjeva"]);
+    }
+
+    #[tokio::test]
+    async fn test_custom_tokenizer() {
+        let result = pipeline()
+            .with_tokenizer(WhitespaceTokenizer)
+            .run("Ana sve zna. On od mene zahtjeva da dođem.".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(result.sentences, 1);
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].context, "od mene zahtjeva da dođem.");
+    }
+
+    #[tokio::test]
+    async fn test_store_executor_matches_recorded_executor() {
+        let recorded = pipeline();
+        let store = Pipeline::new(
+            StoreExecutor {
+                store: Arc::new(InMemoryStore::new(recorded.executor.counts.clone())),
+                options: ExecuteOptions {
+                    early_stop: None,
+                    skip_backfill: false,
+                },
+            },
+            Box::new(MaxPredictor::default()),
+            Cow::Borrowed(&*recorded.confusion_set),
+            Cow::Borrowed(&*recorded.number_of_ngrams),
+            Cow::Borrowed(&*recorded.number_of_distinct_ngrams),
+        );
+
+        let text = "Ana sve zna. On od mene zahtjeva da dođem.";
+        let expected = recorded.run(text.to_string()).await.unwrap();
+        let result = store.run(text.to_string()).await.unwrap();
+
+        assert_eq!(result.results.len(), expected.results.len());
+        assert_eq!(result.results[0].context, expected.results[0].context);
+        assert_eq!(result.results[0].results, expected.results[0].results);
+    }
+
+    #[tokio::test]
+    async fn test_run_with_solver() {
+        let pipeline = pipeline();
+        let text = "On od mene zahtjeva da dođem.";
+
+        let (solver, result) = pipeline.run_with_solver(text.to_string()).await.unwrap();
+        let expected = pipeline.run(text.to_string()).await.unwrap();
+
+        assert_eq!(solver.text, text);
+        assert_eq!(solver.find_occurrences().len(), 1);
+        assert_eq!(result.results[0].results, expected.results[0].results);
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = pipeline()
+            .plan("On od mene zahtjeva da dođem. Ana zahtjeva.".to_string())
+            .unwrap();
+
+        let keys: Vec<(&str, &str)> = plan
+            .iter()
+            .map(|planned| (planned.context.as_str(), planned.word.as_str()))
+            .collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                ("Ana zahtjeva", "zahtjeva"),
+                ("od mene zahtjeva da dođem", "zahtjeva")
+            ]
+        );
+        assert!(plan[1]
+            .queries
+            .iter()
+            .all(|query| query.varying_params == vec!["zahtijeva", "zahtjeva"]));
+
+        let json = serde_json::to_value(&plan).unwrap();
+
+        assert_eq!(json[1]["queries"][1]["staticParams"][0], "mene");
+    }
+}
@@ -1,17 +1,248 @@
-use crate::n_grams::solver::model::TimedSentenceResults;
+use crate::{
+    csv_row,
+    db::SKIP_MARKER,
+    n_grams::solver::{
+        correction::best_candidate,
+        model::{SentenceResult, TimedSentenceResults},
+    },
+};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
 /// Represents the prediction results.
 ///
 /// # Fields
 ///
-/// * `time_elapsed` - The time elapsed.
+/// * `time_elapsed` - The time elapsed, formatted as `"123 ms"`.
+/// * `time_elapsed_ms` - The time elapsed in milliseconds.
 /// * `results` - The results.
+/// * `sentences` - The number of analyzable sentences, zero means the text had nothing to analyze.
+/// * `errors` - The errors of the queries that failed, their contexts are scored without them.
+/// * `perplexity` - The perplexity of every context, see `sentence_perplexities`.
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PredictionResults {
     pub time_elapsed: String,
+    #[serde(default)]
+    pub time_elapsed_ms: u128,
     pub results: Vec<PredictionResult>,
+    pub sentences: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub perplexity: Vec<SentencePerplexity>,
+}
+
+impl PredictionResults {
+    /// Scales the scores to integers.
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - The scale, e.g. `1000` turns a score of `1.2345` into `1234`.
+    ///
+    /// # Returns
+    ///
+    /// The `ScaledPredictionResults` borrowing from the results.
+    pub fn scaled(&self, scale: i64) -> ScaledPredictionResults<'_> {
+        ScaledPredictionResults {
+            time_elapsed: &self.time_elapsed,
+            time_elapsed_ms: self.time_elapsed_ms,
+            results: self
+                .results
+                .iter()
+                .map(|result| result.scaled(scale))
+                .collect(),
+            sentences: self.sentences,
+            score_scale: scale,
+            errors: &self.errors,
+            perplexity: &self.perplexity,
+        }
+    }
+
+    /// Formats the scores as CSV.
+    ///
+    /// # Returns
+    ///
+    /// The `context,word_examined,candidate,score` header and a row per candidate of every context,
+    /// the candidates of a context sorted by the score, the most probable first.
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_row(&["context", "word_examined", "candidate", "score"]);
+
+        for result in &self.results {
+            let mut candidates: Vec<(&String, &f64)> = result.results.iter().collect();
+            candidates.sort_by(|a, b| a.1.total_cmp(b.1).then_with(|| a.0.cmp(b.0)));
+
+            for (candidate, score) in candidates {
+                csv.push_str(&csv_row(&[
+                    &result.context,
+                    &result.word_examined,
+                    candidate,
+                    &score.to_string(),
+                ]));
+            }
+        }
+
+        csv
+    }
+
+    /// Removes the number of fetched rows, it is only returned in the verbose output.
+    pub fn hide_rows_fetched(&mut self) {
+        for result in self.results.iter_mut() {
+            result.rows_fetched = None;
+        }
+    }
+
+    /// Removes the terms of the scores, they are only returned in the explain output.
+    pub fn hide_explanation(&mut self) {
+        for result in self.results.iter_mut() {
+            result.explanation = None;
+        }
+    }
+
+    /// Limits the results of every context to the best scoring candidates.
+    ///
+    /// The examined word is always kept, so the client can compare it with the suggestions.
+    ///
+    /// # Arguments
+    ///
+    /// * `top_k` - The number of best scoring candidates to keep.
+    pub fn limit_top_k(&mut self, top_k: usize) {
+        for result in self.results.iter_mut() {
+            result.limit_top_k(top_k);
+        }
+    }
+}
+
+/// Represents the prediction results with the scores scaled to integers, for fixed-point clients.
+///
+/// # Fields
+///
+/// * `time_elapsed` - The time elapsed.
+/// * `time_elapsed_ms` - The time elapsed in milliseconds.
+/// * `results` - The results.
+/// * `sentences` - The number of analyzable sentences.
+/// * `score_scale` - The scale the scores were multiplied by.
+/// * `errors` - The errors of the queries that failed.
+/// * `perplexity` - The perplexity of every context, not scaled.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaledPredictionResults<'a> {
+    pub time_elapsed: &'a str,
+    pub time_elapsed_ms: u128,
+    pub results: Vec<ScaledPredictionResult<'a>>,
+    pub sentences: usize,
+    pub score_scale: i64,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    pub errors: &'a [String],
+    #[serde(skip_serializing_if = "<[SentencePerplexity]>::is_empty")]
+    pub perplexity: &'a [SentencePerplexity],
+}
+
+/// Represents the prediction result with the scores scaled to integers.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `word_examined` - The word examined.
+/// * `results` - The scaled scores.
+/// * `normalized` - The normalized probabilities, not scaled.
+/// * `unigram_only` - The candidates that were scored on unigram probability alone.
+/// * `max_matched_order` - The highest order of the context n-grams with a nonzero frequency per candidate.
+/// * `rows_fetched` - The number of n-grams returned by Scylla for the context, only in the verbose output.
+/// * `explanation` - The terms every candidate was scored from, not scaled, only in the explain output.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaledPredictionResult<'a> {
+    pub context: &'a str,
+    pub word_examined: &'a str,
+    pub results: HashMap<&'a str, i64>,
+    pub normalized: &'a HashMap<String, f64>,
+    pub unigram_only: &'a [String],
+    pub max_matched_order: &'a HashMap<String, i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows_fetched: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<&'a HashMap<String, Vec<ScoreTerm>>>,
+}
+
+/// Represents the perplexity of a context.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `words` - The number of examined words of the context the perplexity is computed over.
+/// * `perplexity` - The perplexity of the chosen candidates.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SentencePerplexity {
+    pub context: String,
+    pub words: usize,
+    pub perplexity: f64,
+}
+
+/// Computes the perplexity of every context from the probabilities of its chosen candidates.
+///
+/// The chosen candidate of an examined word is the one `best_candidate` picks, its probability is
+/// recovered from the score as `log_base^-score` and clamped to at least `MIN_PROBABILITY`, so a
+/// zero probability gives a large but finite perplexity. The perplexity of a context with the
+/// chosen probabilities `p_1, ..., p_N` is the geometric mean of their inverses,
+/// `(1 / p_1 * ... * 1 / p_N)^(1 / N)`, computed as `exp(-(ln p_1 + ... + ln p_N) / N)`.
+///
+/// # Arguments
+///
+/// * `results` - The prediction results.
+/// * `log_base` - The base of the logarithm the scores were computed with.
+///
+/// # Returns
+///
+/// The perplexity of every context with a scored candidate, in the order of the results.
+pub fn sentence_perplexities(
+    results: &[PredictionResult],
+    log_base: f64,
+) -> Vec<SentencePerplexity> {
+    let mut log_sums: Vec<(&str, f64, usize)> = vec![];
+
+    for result in results {
+        let score = match best_candidate(result).and_then(|best| result.results.get(best)) {
+            Some(score) => score,
+            None => continue,
+        };
+        let p = log_base.powf(-score).clamp(MIN_PROBABILITY, 1.0);
+
+        match log_sums
+            .iter_mut()
+            .find(|(context, _, _)| *context == result.context)
+        {
+            Some((_, log_sum, words)) => {
+                *log_sum += p.ln();
+                *words += 1;
+            }
+            None => log_sums.push((&result.context, p.ln(), 1)),
+        }
+    }
+
+    log_sums
+        .into_iter()
+        .map(|(context, log_sum, words)| SentencePerplexity {
+            context: context.to_string(),
+            words,
+            perplexity: (-log_sum / words as f64).exp(),
+        })
+        .collect()
+}
+
+/// Scales the score to an integer.
+///
+/// # Arguments
+///
+/// * `score` - The score.
+/// * `scale` - The scale.
+///
+/// # Returns
+///
+/// The score multiplied by the scale, truncated toward zero.
+pub fn scale_score(score: f64, scale: i64) -> i64 {
+    (score * scale as f64).trunc() as i64
 }
 
 /// Represents the prediction result.
@@ -21,11 +252,180 @@ pub struct PredictionResults {
 /// * `sentence` - The sentence.
 /// * `word_examined` - The word examined.
 /// * `results` - The results.
+/// * `normalized` - The probabilities of the candidates divided by their sum, so they sum to one over the confusion group.
+/// * `unigram_only` - The candidates that had no context n-grams and were scored on unigram probability alone.
+/// * `max_matched_order` - The highest order of the context n-grams with a nonzero frequency per candidate, zero if none matched.
+/// * `rows_fetched` - The number of n-grams returned by Scylla for the context, only in the verbose output.
+/// * `explanation` - The terms every candidate was scored from, only in the explain output of the
+///   predictors that keep them.
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PredictionResult {
     pub context: String,
     pub word_examined: String,
     pub results: HashMap<String, f64>,
+    #[serde(default)]
+    pub normalized: HashMap<String, f64>,
+    pub unigram_only: Vec<String>,
+    #[serde(default)]
+    pub max_matched_order: HashMap<String, i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_fetched: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<HashMap<String, Vec<ScoreTerm>>>,
+}
+
+impl PredictionResult {
+    /// Scales the scores to integers, see `PredictionResults::scaled`.
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - The scale.
+    ///
+    /// # Returns
+    ///
+    /// The `ScaledPredictionResult` borrowing from the result.
+    pub fn scaled(&self, scale: i64) -> ScaledPredictionResult<'_> {
+        ScaledPredictionResult {
+            context: &self.context,
+            word_examined: &self.word_examined,
+            results: self
+                .results
+                .iter()
+                .map(|(candidate, score)| (candidate.as_str(), scale_score(*score, scale)))
+                .collect(),
+            normalized: &self.normalized,
+            unigram_only: &self.unigram_only,
+            max_matched_order: &self.max_matched_order,
+            rows_fetched: self.rows_fetched,
+            explanation: self.explanation.as_ref(),
+        }
+    }
+
+    /// Limits the results to the best scoring candidates, see `PredictionResults::limit_top_k`.
+    ///
+    /// # Arguments
+    ///
+    /// * `top_k` - The number of best scoring candidates to keep.
+    pub fn limit_top_k(&mut self, top_k: usize) {
+        let mut candidates: Vec<(&String, &f64)> = self.results.iter().collect();
+        candidates.sort_by(|a, b| a.1.total_cmp(b.1).then_with(|| a.0.cmp(b.0)));
+
+        let mut kept: Vec<String> = candidates
+            .iter()
+            .take(top_k)
+            .map(|(candidate, _)| candidate.to_string())
+            .collect();
+        kept.push(self.word_examined.clone());
+
+        self.results.retain(|candidate, _| kept.contains(candidate));
+        self.normalized
+            .retain(|candidate, _| kept.contains(candidate));
+        self.unigram_only
+            .retain(|candidate| kept.contains(candidate));
+        self.max_matched_order
+            .retain(|candidate, _| kept.contains(candidate));
+        if let Some(explanation) = self.explanation.as_mut() {
+            explanation.retain(|candidate, _| kept.contains(candidate));
+        }
+    }
+}
+
+/// Represents a term of the score of a candidate.
+///
+/// # Fields
+///
+/// * `n_gram` - The context n-gram, or the candidate alone if it was scored on its unigram.
+/// * `frequency` - The smoothed frequency of the n-gram.
+/// * `total` - The smoothed total of the n-grams of the same order.
+/// * `unigram_frequency` - The frequency of the candidate the probability is multiplied by, `None` for a unigram term.
+/// * `probability` - The probability of the term.
+/// * `contribution` - What the term adds to the probability of the candidate, zero for the terms the maximum predictor did not pick.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreTerm {
+    pub n_gram: String,
+    pub frequency: f64,
+    pub total: f64,
+    pub unigram_frequency: Option<i32>,
+    pub probability: f64,
+    pub contribution: f64,
+}
+
+/// Sorts the terms by the order of their n-grams, the highest first.
+///
+/// # Arguments
+///
+/// * `terms` - The terms.
+///
+/// # Returns
+///
+/// The sorted terms.
+fn sorted_terms(mut terms: Vec<ScoreTerm>) -> Vec<ScoreTerm> {
+    terms.sort_by(|a, b| {
+        let order = |term: &ScoreTerm| term.n_gram.split_whitespace().count();
+        order(b)
+            .cmp(&order(a))
+            .then_with(|| a.n_gram.cmp(&b.n_gram))
+    });
+    terms
+}
+
+/// Keeps the contribution of the term the maximum predictor picked, the first most probable one.
+///
+/// # Arguments
+///
+/// * `terms` - The terms, in the order they were scored.
+/// * `max` - The probability of the picked term.
+///
+/// # Returns
+///
+/// The terms, the others contributing zero.
+fn picked_terms(mut terms: Vec<ScoreTerm>, max: f64) -> Vec<ScoreTerm> {
+    let picked = terms.iter().position(|term| term.probability == max);
+    for (i, term) in terms.iter_mut().enumerate() {
+        if Some(i) != picked {
+            term.contribution = 0.0;
+        }
+    }
+    terms
+}
+
+/// Explains the candidates scored on unigram probability alone.
+///
+/// # Arguments
+///
+/// * `explanation` - The terms of every candidate the unigram terms are added to.
+/// * `unigram_only` - The candidates scored on unigram probability alone.
+/// * `unigram_frequencies` - The unigram frequencies.
+/// * `probabilities` - The probabilities of the candidates.
+/// * `k` - The value added to the unigram frequency.
+/// * `unigram_total` - The smoothed number of unigrams.
+fn explain_unigram_only(
+    explanation: &mut HashMap<String, Vec<ScoreTerm>>,
+    unigram_only: &[String],
+    unigram_frequencies: &HashMap<String, i32>,
+    probabilities: &HashMap<String, f64>,
+    k: f64,
+    unigram_total: f64,
+) {
+    for w in unigram_only {
+        let (uf, p) = match (unigram_frequencies.get(w), probabilities.get(w)) {
+            (Some(uf), Some(p)) => (*uf, *p),
+            _ => continue,
+        };
+        explanation.insert(
+            w.clone(),
+            vec![ScoreTerm {
+                n_gram: w.clone(),
+                frequency: uf as f64 + k,
+                total: unigram_total,
+                unigram_frequency: None,
+                probability: p,
+                contribution: p,
+            }],
+        );
+    }
 }
 
 /// Represents the Laplace smoothing result.
@@ -34,12 +434,12 @@ pub struct PredictionResult {
 ///
 /// * `results` - The results.
 /// * `n_gram_counts` - The n-gram counts.
-pub struct LaplaceSmoothingResult {
+pub struct LaplaceSmoothingResult<'a> {
     pub results: HashMap<String, i32>,
-    pub n_gram_counts: HashMap<i32, i64>,
+    pub n_gram_counts: Cow<'a, HashMap<i32, i64>>,
 }
 
-impl LaplaceSmoothingResult {
+impl LaplaceSmoothingResult<'_> {
     /// Gets the Laplace smoothing result.
     ///
     /// # Arguments
@@ -51,27 +451,304 @@ impl LaplaceSmoothingResult {
     /// # Returns
     ///
     /// The Laplace smoothing result.
-    fn get(
+    pub fn get(
         results: HashMap<String, i32>,
         n_gram_counts: HashMap<i32, i64>,
         distinct_n_gram_counts: HashMap<i32, i64>,
     ) -> Self {
-        let mut new_results = results.clone();
-        for (_, v) in new_results.iter_mut() {
-            *v += 1;
-        }
-        let mut new_n_gram_counts = n_gram_counts.clone();
-        for (k, v) in new_n_gram_counts.iter_mut() {
-            *v += distinct_n_gram_counts.get(k).unwrap();
+        LaplaceSmoothingResult {
+            results: smooth_results(&results),
+            n_gram_counts: Cow::Owned(smoothed_n_gram_counts(
+                &n_gram_counts,
+                &distinct_n_gram_counts,
+            )),
         }
+    }
+}
 
+impl<'a> LaplaceSmoothingResult<'a> {
+    /// Gets the Laplace smoothing result using precomputed n-gram totals.
+    ///
+    /// The smoothed totals don't depend on the candidate, so the predictors compute them once
+    /// with `smoothed_n_gram_counts` and share them across all candidates.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - The results.
+    /// * `n_gram_counts` - The smoothed n-gram counts.
+    ///
+    /// # Returns
+    ///
+    /// The Laplace smoothing result.
+    pub fn with_totals(
+        results: &HashMap<String, i32>,
+        n_gram_counts: &'a HashMap<i32, i64>,
+    ) -> Self {
         LaplaceSmoothingResult {
-            results: new_results,
-            n_gram_counts: new_n_gram_counts,
+            results: smooth_results(results),
+            n_gram_counts: Cow::Borrowed(n_gram_counts),
+        }
+    }
+}
+
+/// Adds one to every n-gram frequency.
+///
+/// # Arguments
+///
+/// * `results` - The results.
+///
+/// # Returns
+///
+/// The smoothed results.
+fn smooth_results(results: &HashMap<String, i32>) -> HashMap<String, i32> {
+    let mut new_results = results.clone();
+    for (_, v) in new_results.iter_mut() {
+        *v += 1;
+    }
+    new_results
+}
+
+/// Computes the Laplace smoothed n-gram totals.
+///
+/// # Arguments
+///
+/// * `n_gram_counts` - The n-gram counts.
+/// * `distinct_n_gram_counts` - The distinct n-gram counts.
+///
+/// # Returns
+///
+/// The n-gram counts increased by the number of distinct n-grams of the same order,
+/// an order without the distinct count is left as is.
+pub fn smoothed_n_gram_counts(
+    n_gram_counts: &HashMap<i32, i64>,
+    distinct_n_gram_counts: &HashMap<i32, i64>,
+) -> HashMap<i32, i64> {
+    let mut new_n_gram_counts = n_gram_counts.clone();
+    for (k, v) in new_n_gram_counts.iter_mut() {
+        *v += distinct_count(distinct_n_gram_counts, *k);
+    }
+    new_n_gram_counts
+}
+
+/// Gets the number of distinct n-grams of the order.
+///
+/// # Arguments
+///
+/// * `distinct_n_gram_counts` - The distinct n-gram counts.
+/// * `order` - The order of the n-grams.
+///
+/// # Returns
+///
+/// The number of distinct n-grams, zero with a logged warning if it is missing.
+fn distinct_count(distinct_n_gram_counts: &HashMap<i32, i64>, order: i32) -> i64 {
+    match distinct_n_gram_counts.get(&order) {
+        Some(count) => *count,
+        None => {
+            tracing::warn!(order, "missing the number of distinct n-grams");
+            0
+        }
+    }
+}
+
+/// Gets the total number of n-grams of the order.
+///
+/// # Arguments
+///
+/// * `n_gram_counts` - The n-gram counts.
+/// * `order` - The order of the n-grams.
+///
+/// # Returns
+///
+/// The total, `None` with a logged warning if it is missing or not positive, it can not divide.
+fn n_gram_total<T>(n_gram_counts: &HashMap<i32, T>, order: i32) -> Option<T>
+where
+    T: Copy + Default + PartialOrd,
+{
+    match n_gram_counts.get(&order) {
+        Some(total) if *total > T::default() => Some(*total),
+        _ => {
+            tracing::warn!(order, "missing the number of n-grams");
+            None
+        }
+    }
+}
+
+/// The smallest probability scored, so the `-log10` of a zero probability is finite.
+pub static MIN_PROBABILITY: f64 = 1e-300;
+
+/// The default base of the logarithm of the scores.
+pub static DEFAULT_LOG_BASE: f64 = 10.0;
+
+/// Turns the probability into the score.
+///
+/// # Arguments
+///
+/// * `p` - The probability.
+/// * `log_base` - The base of the logarithm, greater than one.
+///
+/// # Returns
+///
+/// The negative logarithm of the probability rounded to four decimals, the probability is clamped
+/// to at least `MIN_PROBABILITY` so the score is always finite.
+pub fn to_score(p: f64, log_base: f64) -> f64 {
+    let p = if p.is_nan() {
+        MIN_PROBABILITY
+    } else {
+        p.clamp(MIN_PROBABILITY, f64::MAX)
+    };
+    let log = -p.log(log_base);
+    (log * 10000.0).round() / 10000.0
+}
+
+/// Turns the probabilities of the candidates into their scores.
+///
+/// # Arguments
+///
+/// * `probabilities` - The probabilities of the candidates.
+/// * `log_base` - The base of the logarithm.
+///
+/// # Returns
+///
+/// The score of every candidate, see `to_score`.
+fn to_scores(probabilities: &HashMap<String, f64>, log_base: f64) -> HashMap<String, f64> {
+    probabilities
+        .iter()
+        .map(|(candidate, p)| (candidate.clone(), to_score(*p, log_base)))
+        .collect()
+}
+
+/// Normalizes the probabilities of the candidates so they sum to one.
+///
+/// # Arguments
+///
+/// * `probabilities` - The probabilities of the candidates.
+///
+/// # Returns
+///
+/// The probability of every candidate divided by the sum over the candidates, empty if the
+/// sum is not a positive number.
+pub fn normalize(probabilities: &HashMap<String, f64>) -> HashMap<String, f64> {
+    let sum: f64 = probabilities.values().sum();
+
+    if !(sum > 0.0 && sum.is_finite()) {
+        return HashMap::new();
+    }
+
+    probabilities
+        .iter()
+        .map(|(candidate, p)| (candidate.clone(), p / sum))
+        .collect()
+}
+
+/// Represents the Lidstone (add-k) smoothing result.
+///
+/// # Fields
+///
+/// * `results` - The results.
+/// * `n_gram_counts` - The n-gram counts.
+pub struct LidstoneSmoothingResult<'a> {
+    pub results: HashMap<String, f64>,
+    pub n_gram_counts: Cow<'a, HashMap<i32, f64>>,
+}
+
+impl LidstoneSmoothingResult<'_> {
+    /// Gets the Lidstone smoothing result.
+    ///
+    /// With `k` set to one it is the Laplace smoothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - The results.
+    /// * `n_gram_counts` - The n-gram counts.
+    /// * `distinct_n_gram_counts` - The distinct n-gram counts.
+    /// * `k` - The value added to every n-gram frequency.
+    ///
+    /// # Returns
+    ///
+    /// The Lidstone smoothing result.
+    pub fn get(
+        results: &HashMap<String, i32>,
+        n_gram_counts: &HashMap<i32, i64>,
+        distinct_n_gram_counts: &HashMap<i32, i64>,
+        k: f64,
+    ) -> Self {
+        LidstoneSmoothingResult {
+            results: lidstone_results(results, k),
+            n_gram_counts: Cow::Owned(lidstone_n_gram_counts(
+                n_gram_counts,
+                distinct_n_gram_counts,
+                k,
+            )),
+        }
+    }
+}
+
+impl<'a> LidstoneSmoothingResult<'a> {
+    /// Gets the Lidstone smoothing result using precomputed n-gram totals.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - The results.
+    /// * `n_gram_counts` - The smoothed n-gram counts, see `lidstone_n_gram_counts`.
+    /// * `k` - The value added to every n-gram frequency.
+    ///
+    /// # Returns
+    ///
+    /// The Lidstone smoothing result.
+    pub fn with_totals(
+        results: &HashMap<String, i32>,
+        n_gram_counts: &'a HashMap<i32, f64>,
+        k: f64,
+    ) -> Self {
+        LidstoneSmoothingResult {
+            results: lidstone_results(results, k),
+            n_gram_counts: Cow::Borrowed(n_gram_counts),
         }
     }
 }
 
+/// Adds `k` to every n-gram frequency.
+///
+/// # Arguments
+///
+/// * `results` - The results.
+/// * `k` - The value added to every n-gram frequency.
+///
+/// # Returns
+///
+/// The smoothed results.
+fn lidstone_results(results: &HashMap<String, i32>, k: f64) -> HashMap<String, f64> {
+    results
+        .iter()
+        .map(|(n_gram, freq)| (n_gram.clone(), *freq as f64 + k))
+        .collect()
+}
+
+/// Computes the Lidstone smoothed n-gram totals.
+///
+/// # Arguments
+///
+/// * `n_gram_counts` - The n-gram counts.
+/// * `distinct_n_gram_counts` - The distinct n-gram counts.
+/// * `k` - The value added to every n-gram frequency.
+///
+/// # Returns
+///
+/// The n-gram counts increased by `k` times the number of distinct n-grams of the same order.
+pub fn lidstone_n_gram_counts(
+    n_gram_counts: &HashMap<i32, i64>,
+    distinct_n_gram_counts: &HashMap<i32, i64>,
+    k: f64,
+) -> HashMap<i32, f64> {
+    n_gram_counts
+        .iter()
+        .map(|(order, count)| {
+            let distinct = distinct_count(distinct_n_gram_counts, *order) as f64;
+            (*order, *count as f64 + k * distinct)
+        })
+        .collect()
+}
+
 /// Represents the predictor.
 ///
 /// This trait is used to define the predictor.
@@ -79,6 +756,7 @@ impl LaplaceSmoothingResult {
 /// # Methods
 ///
 /// * `predict` - Predicts the results.
+/// * `log_base` - The base of the logarithm of the scores.
 pub trait Predict {
     /// Predicts the results.
     ///
@@ -91,246 +769,794 @@ pub trait Predict {
     fn predict(
         &self,
         data: TimedSentenceResults,
-        confusion_set: Vec<Vec<String>>,
-        number_of_ngrams: HashMap<i32, i64>,
-        number_of_distinct_ngrams: HashMap<i32, i64>,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        number_of_distinct_ngrams: &HashMap<i32, i64>,
     ) -> PredictionResults;
+
+    /// Gets the base of the logarithm of the scores.
+    fn log_base(&self) -> f64 {
+        DEFAULT_LOG_BASE
+    }
+}
+
+/// Predicts every context of the sentence results, the sentence results in parallel.
+///
+/// The contexts are independent, so every sentence result is scored on its own thread of the
+/// rayon pool. The results keep the order of the sentence results and of the confusion groups.
+/// A word outside the confusion set is predicted against its edit candidates instead.
+///
+/// # Arguments
+///
+/// * `data` - The timed sentence results.
+/// * `confusion_set` - The confusion set.
+/// * `predict_context` - Predicts the context of a sentence result against a confusion group it is in.
+///
+/// # Returns
+///
+/// The prediction results.
+fn predict_contexts<F>(
+    data: &TimedSentenceResults,
+    confusion_set: &[Vec<String>],
+    predict_context: F,
+) -> Vec<PredictionResult>
+where
+    F: Fn(&SentenceResult, &[String]) -> PredictionResult + Sync,
+{
+    data.results
+        .par_iter()
+        .flat_map_iter(|r| {
+            if !r.edit_candidates.is_empty() {
+                return vec![predict_context(r, &r.edit_candidates)];
+            }
+
+            confusion_set
+                .iter()
+                .filter(|cs| cs.contains(&r.word))
+                .map(|cs| predict_context(r, cs))
+                .collect::<Vec<PredictionResult>>()
+        })
+        .collect()
+}
+
+fn fill_results(
+    d: &mut HashMap<String, HashMap<String, i32>>,
+    qr: &crate::n_grams::solver::model::QueryResult,
+    cs: &[String],
+) {
+    for w in cs.iter() {
+        if qr.input.split_whitespace().any(|token| token == w) {
+            if d.contains_key(w) {
+                let h = d.get_mut(w).unwrap();
+                let mut found = false;
+                for (k2, v2) in h.iter_mut() {
+                    if k2.to_lowercase().as_str() == qr.input.to_lowercase().as_str() {
+                        *v2 += qr.frequency;
+                        found = true;
+                    }
+                }
+                if !found {
+                    h.insert(qr.input.clone(), qr.frequency);
+                }
+            } else {
+                let mut h: HashMap<String, i32> = HashMap::new();
+                h.insert(qr.input.clone(), qr.frequency);
+                d.insert(w.clone(), h);
+            }
+        }
+    }
+}
+
+/// Checks whether the n-gram is a skip-gram.
+///
+/// The skip-grams are scored as an additional n-gram of the order of the trigrams they are read
+/// from, every trigram holds exactly one skip-bigram of its first and third word, so the trigram
+/// totals are the skip-bigram totals as well.
+///
+/// # Arguments
+///
+/// * `n_gram` - The n-gram.
+///
+/// # Returns
+///
+/// `true` if one of the words is the `SKIP_MARKER`.
+fn is_skip_gram(n_gram: &str) -> bool {
+    n_gram.split_whitespace().any(|word| word == SKIP_MARKER)
+}
+
+/// Finds the highest order of the context n-grams with a nonzero frequency of every scored candidate.
+///
+/// Trigram evidence is stronger than bigram evidence, so the order is a signal of the score quality.
+/// A skip-gram only matches the words around the skipped one, so it counts as a bigram.
+///
+/// # Arguments
+///
+/// * `d` - The context n-grams of every candidate, before the smoothing.
+/// * `results` - The probabilities or the scores, every scored candidate gets an order.
+///
+/// # Returns
+///
+/// The order of every scored candidate, zero if none of its context n-grams was found.
+fn max_matched_orders(
+    d: &HashMap<String, HashMap<String, i32>>,
+    results: &HashMap<String, f64>,
+) -> HashMap<String, i32> {
+    results
+        .keys()
+        .map(|candidate| {
+            let order = d
+                .get(candidate)
+                .into_iter()
+                .flatten()
+                .filter(|(_, freq)| **freq > 0)
+                .map(|(n_gram, _)| {
+                    let order = n_gram.split_whitespace().count() as i32;
+                    order - i32::from(is_skip_gram(n_gram))
+                })
+                .max()
+                .unwrap_or(0);
+            (candidate.clone(), order)
+        })
+        .collect()
+}
+
+/// Gives a probability to the candidates that have unigram data but no context n-grams.
+///
+/// Without this, a short context that only produced unigram queries would silently omit the candidate.
+/// The probability is the smoothed unigram probability.
+///
+/// # Arguments
+///
+/// * `probabilities` - The probabilities of the candidates the unigram probabilities are added to.
+/// * `unigram_frequencies` - The unigram frequencies.
+/// * `cs` - The confusion set.
+/// * `k` - The value added to the unigram frequency, one for the Laplace smoothing.
+/// * `unigram_total` - The smoothed number of unigrams.
+///
+/// # Returns
+///
+/// The candidates that were scored on unigram probability alone.
+fn add_unigram_only_probabilities(
+    probabilities: &mut HashMap<String, f64>,
+    unigram_frequencies: &HashMap<String, i32>,
+    cs: &[String],
+    k: f64,
+    unigram_total: f64,
+) -> Vec<String> {
+    let mut unigram_only = vec![];
+
+    for w in cs.iter() {
+        if probabilities.contains_key(w) {
+            continue;
+        }
+        if let Some(uf) = unigram_frequencies.get(w) {
+            let p = (*uf as f64 + k) / unigram_total;
+            probabilities.insert(w.clone(), p);
+            unigram_only.push(w.clone());
+        }
+    }
+
+    unigram_only
+}
+
+/// Scores every candidate by its most probable context n-gram, with the Lidstone (add-k) smoothing.
+///
+/// The scorer of the `MaxPredictor`, with `k` set to one for the Laplace smoothing, and of the
+/// `LidstonePredictor`.
+///
+/// # Arguments
+///
+/// * `data` - The timed sentence results.
+/// * `confusion_set` - The confusion set.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+/// * `k` - The value added to every n-gram frequency.
+/// * `log_base` - The base of the logarithm of the scores.
+///
+/// # Returns
+///
+/// The prediction results.
+fn predict_max(
+    data: TimedSentenceResults,
+    confusion_set: &[Vec<String>],
+    number_of_ngrams: &HashMap<i32, i64>,
+    number_of_distinct_ngrams: &HashMap<i32, i64>,
+    k: f64,
+    log_base: f64,
+) -> PredictionResults {
+    let n_gram_counts = lidstone_n_gram_counts(number_of_ngrams, number_of_distinct_ngrams, k);
+    let unigram_total = n_gram_total(&n_gram_counts, 1);
+    let pr = predict_contexts(&data, confusion_set, |r, cs| {
+        let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
+        let mut unigram_frequencies = HashMap::new();
+
+        for qr in r.results.iter() {
+            if qr.length == 1 {
+                unigram_frequencies.insert(qr.input.clone(), qr.frequency);
+            } else {
+                fill_results(&mut d, qr, cs);
+            }
+        }
+
+        let mut probabilities = HashMap::new();
+        let mut explanation = HashMap::new();
+
+        for (w, v) in d.iter() {
+            let lidstone = LidstoneSmoothingResult::with_totals(v, &n_gram_counts, k);
+            let mut max = None;
+            // without the backfill a candidate can lack its unigram
+            let (uf, unigram_total) = match (unigram_frequencies.get(w), unigram_total) {
+                (Some(uf), Some(unigram_total)) => (*uf, unigram_total),
+                _ => continue,
+            };
+            let mut terms = vec![];
+            for (k1, v1) in lidstone.results.iter() {
+                let length = k1.split_whitespace().count() as i32;
+                let context_total = match n_gram_total(&lidstone.n_gram_counts, length) {
+                    Some(context_total) => context_total,
+                    None => continue,
+                };
+                let p = (uf as f64 / unigram_total) * (v1 / context_total);
+                terms.push(ScoreTerm {
+                    n_gram: k1.clone(),
+                    frequency: *v1,
+                    total: context_total,
+                    unigram_frequency: Some(uf),
+                    probability: p,
+                    contribution: p,
+                });
+                if max.is_none_or(|max| p > max) {
+                    max = Some(p);
+                }
+            }
+            // a candidate without a scored n-gram falls back to the unigram score
+            if let Some(max) = max {
+                probabilities.insert(w.clone(), max);
+                explanation.insert(w.clone(), sorted_terms(picked_terms(terms, max)));
+            }
+        }
+
+        let unigram_only = match unigram_total {
+            Some(unigram_total) => {
+                let unigram_only = add_unigram_only_probabilities(
+                    &mut probabilities,
+                    &unigram_frequencies,
+                    cs,
+                    k,
+                    unigram_total,
+                );
+                explain_unigram_only(
+                    &mut explanation,
+                    &unigram_only,
+                    &unigram_frequencies,
+                    &probabilities,
+                    k,
+                    unigram_total,
+                );
+                unigram_only
+            }
+            None => vec![],
+        };
+
+        let max_matched_order = max_matched_orders(&d, &probabilities);
+
+        PredictionResult {
+            context: r.sentence.clone(),
+            word_examined: r.word.clone(),
+            results: to_scores(&probabilities, log_base),
+            normalized: normalize(&probabilities),
+            unigram_only,
+            max_matched_order,
+            rows_fetched: Some(r.rows_fetched),
+            explanation: Some(explanation),
+        }
+    });
+
+    PredictionResults {
+        results: pr,
+        time_elapsed: data.time_taken,
+        time_elapsed_ms: data.time_taken_ms,
+        sentences: data.sentences,
+        errors: data.errors,
+        perplexity: vec![],
+    }
+}
+
+/// Scores an n-gram of a candidate.
+///
+/// The score is the unigram probability of the candidate multiplied by the probability of the context n-gram,
+/// the counts are expected to be Laplace smoothed.
+///
+/// # Arguments
+///
+/// * `unigram_freq` - The frequency of the candidate.
+/// * `unigram_total` - The total number of unigrams.
+/// * `context_count` - The frequency of the context n-gram.
+/// * `context_total` - The total number of n-grams of the context order.
+///
+/// # Returns
+///
+/// The probability of the n-gram.
+pub fn score_ngram(
+    unigram_freq: i32,
+    unigram_total: i64,
+    context_count: i32,
+    context_total: i64,
+) -> f64 {
+    (unigram_freq as f64 / unigram_total as f64) * (context_count as f64 / context_total as f64)
+}
+
+/// The names of the predictors.
+pub static PREDICTORS: [&str; 5] = ["max", "sum", "power_sum", "lidstone", "stupid_backoff"];
+
+/// The predictor used when none is chosen.
+pub static DEFAULT_PREDICTOR: &str = "max";
+
+/// The default power of the `PowerSumPredictor`.
+pub static DEFAULT_POWER: f64 = 0.5;
+
+/// The default k of the `LidstonePredictor`, the Laplace smoothing.
+pub static DEFAULT_K: f64 = 1.0;
+
+/// The multiplier of the `StupidBackoffPredictor` for every dropped n-gram order.
+pub static BACKOFF_FACTOR: f64 = 0.4;
+
+/// Represents the maximum predictor.
+///
+/// This struct is used to define the maximum predictor.
+///
+/// # Fields
+///
+/// * `log_base` - The base of the logarithm of the scores, `DEFAULT_LOG_BASE` by default.
+pub struct MaxPredictor {
+    pub log_base: f64,
+}
+
+impl Default for MaxPredictor {
+    fn default() -> Self {
+        MaxPredictor {
+            log_base: DEFAULT_LOG_BASE,
+        }
+    }
+}
+
+impl Predict for MaxPredictor {
+    fn log_base(&self) -> f64 {
+        self.log_base
+    }
+
+    fn predict(
+        &self,
+        data: TimedSentenceResults,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        number_of_distinct_ngrams: &HashMap<i32, i64>,
+    ) -> PredictionResults {
+        predict_max(
+            data,
+            confusion_set,
+            number_of_ngrams,
+            number_of_distinct_ngrams,
+            1.0,
+            self.log_base,
+        )
+    }
+}
+
+/// Represents the sum predictor.
+///
+/// # Fields
+///
+/// * `log_base` - The base of the logarithm of the scores, `DEFAULT_LOG_BASE` by default.
+pub struct SumPredictor {
+    pub log_base: f64,
 }
 
-fn fill_results(
-    d: &mut HashMap<String, HashMap<String, i32>>,
-    qr: &crate::n_grams::solver::model::QueryResult,
-    cs: &Vec<String>,
-) {
-    for w in cs.iter() {
-        if qr.input.contains(w) {
-            if d.contains_key(w) {
-                let h = d.get_mut(w).unwrap();
-                let mut found = false;
-                for (k2, v2) in h.iter_mut() {
-                    if k2.to_lowercase().as_str() == qr.input.to_lowercase().as_str() {
-                        *v2 += qr.frequency;
-                        found = true;
-                    }
-                }
-                if !found {
-                    h.insert(qr.input.clone(), qr.frequency);
-                }
-            } else {
-                let mut h: HashMap<String, i32> = HashMap::new();
-                h.insert(qr.input.clone(), qr.frequency);
-                d.insert(w.clone(), h);
-            }
+impl Default for SumPredictor {
+    fn default() -> Self {
+        SumPredictor {
+            log_base: DEFAULT_LOG_BASE,
         }
     }
 }
 
-/// Represents the maximum predictor.
-///
-/// This struct is used to define the maximum predictor.
-pub struct MaxPredictor {}
+impl Predict for SumPredictor {
+    fn log_base(&self) -> f64 {
+        self.log_base
+    }
 
-impl Predict for MaxPredictor {
     fn predict(
         &self,
         data: TimedSentenceResults,
-        confusion_set: Vec<Vec<String>>,
-        number_of_ngrams: HashMap<i32, i64>,
-        number_of_distinct_ngrams: HashMap<i32, i64>,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        number_of_distinct_ngrams: &HashMap<i32, i64>,
     ) -> PredictionResults {
-        let mut pr: Vec<PredictionResult> = Vec::new();
-        for r in data.results.iter() {
-            for cs in confusion_set.iter() {
-                if cs.contains(&r.word) {
-                    let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
-                    let mut unigram_frequencies = HashMap::new();
-
-                    for qr in r.results.iter() {
-                        if qr.length == 1 {
-                            unigram_frequencies.insert(qr.input.clone(), qr.frequency);
-                        } else {
-                            fill_results(&mut d, qr, cs);
-                        }
-                    }
+        let n_gram_counts = smoothed_n_gram_counts(number_of_ngrams, number_of_distinct_ngrams);
+        let unigram_total = n_gram_total(&n_gram_counts, 1);
+        let pr = predict_contexts(&data, confusion_set, |r, cs| {
+            let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
+            let mut unigram_frequencies = HashMap::new();
 
-                    let mut results = HashMap::new();
-
-                    for (k, v) in d.iter() {
-                        let laplace = LaplaceSmoothingResult::get(
-                            v.clone(),
-                            number_of_ngrams.clone(),
-                            number_of_distinct_ngrams.clone(),
-                        );
-                        let mut max = -1.0;
-                        let uf = unigram_frequencies.get(k).unwrap();
-                        for (k1, v1) in laplace.results.iter() {
-                            let p: f64 = ((*uf as f64)
-                                / (*laplace.n_gram_counts.get(&1).unwrap() as f64))
-                                * ((*v1 as f64)
-                                    / (*laplace
-                                        .n_gram_counts
-                                        .get(&(k1.split_whitespace().count() as i32))
-                                        .unwrap() as f64));
-                            if p > max {
-                                max = p;
-                            }
-                        }
-                        let log = max.log(10.0) * -1.0;
-                        let log = (log * 10000.0).round() / 10000.0;
-                        results.insert(k.clone(), log);
-                    }
+            for qr in r.results.iter() {
+                if qr.length == 1 {
+                    unigram_frequencies.insert(qr.input.clone(), qr.frequency);
+                } else {
+                    fill_results(&mut d, qr, cs);
+                }
+            }
 
-                    pr.push(PredictionResult {
-                        context: r.sentence.clone(),
-                        word_examined: r.word.clone(),
-                        results,
+            let mut probabilities = HashMap::new();
+            let mut explanation = HashMap::new();
+            for (k, v) in d.iter() {
+                let laplace = LaplaceSmoothingResult::with_totals(v, &n_gram_counts);
+                let mut sum = None;
+                // without the backfill a candidate can lack its unigram
+                let (uf, unigram_total) = match (unigram_frequencies.get(k), unigram_total) {
+                    (Some(uf), Some(unigram_total)) => (uf, unigram_total),
+                    _ => continue,
+                };
+                let mut terms = vec![];
+                for (k1, v1) in laplace.results.iter() {
+                    let length = k1.split_whitespace().count() as i32;
+                    let context_total = match n_gram_total(&laplace.n_gram_counts, length) {
+                        Some(context_total) => context_total,
+                        None => continue,
+                    };
+                    let p = score_ngram(*uf, unigram_total, *v1, context_total);
+                    terms.push(ScoreTerm {
+                        n_gram: k1.clone(),
+                        frequency: *v1 as f64,
+                        total: context_total as f64,
+                        unigram_frequency: Some(*uf),
+                        probability: p,
+                        contribution: p,
                     });
-                    break;
+                    sum = Some(sum.unwrap_or(0.0) + p);
+                }
+                // a candidate without a scored n-gram falls back to the unigram score
+                if let Some(sum) = sum {
+                    probabilities.insert(k.clone(), sum);
+                    explanation.insert(k.clone(), sorted_terms(terms));
                 }
             }
-        }
+
+            let unigram_only = match unigram_total {
+                Some(unigram_total) => {
+                    let unigram_only = add_unigram_only_probabilities(
+                        &mut probabilities,
+                        &unigram_frequencies,
+                        cs,
+                        1.0,
+                        unigram_total as f64,
+                    );
+                    explain_unigram_only(
+                        &mut explanation,
+                        &unigram_only,
+                        &unigram_frequencies,
+                        &probabilities,
+                        1.0,
+                        unigram_total as f64,
+                    );
+                    unigram_only
+                }
+                None => vec![],
+            };
+
+            let max_matched_order = max_matched_orders(&d, &probabilities);
+
+            PredictionResult {
+                context: r.sentence.clone(),
+                word_examined: r.word.clone(),
+                results: to_scores(&probabilities, self.log_base),
+                normalized: normalize(&probabilities),
+                unigram_only,
+                max_matched_order,
+                rows_fetched: Some(r.rows_fetched),
+                explanation: Some(explanation),
+            }
+        });
 
         PredictionResults {
             results: pr,
             time_elapsed: data.time_taken,
+            time_elapsed_ms: data.time_taken_ms,
+            sentences: data.sentences,
+            errors: data.errors,
+            perplexity: vec![],
         }
     }
 }
 
-pub struct SumPredictor {}
+pub struct PowerSumPredictor {
+    pub power: f64,
+}
 
-impl Predict for SumPredictor {
+impl Predict for PowerSumPredictor {
     fn predict(
         &self,
         data: TimedSentenceResults,
-        confusion_set: Vec<Vec<String>>,
-        number_of_ngrams: HashMap<i32, i64>,
-        number_of_distinct_ngrams: HashMap<i32, i64>,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        number_of_distinct_ngrams: &HashMap<i32, i64>,
     ) -> PredictionResults {
-        let mut pr: Vec<PredictionResult> = Vec::new();
-        for r in data.results.iter() {
-            for cs in confusion_set.iter() {
-                if cs.contains(&r.word) {
-                    let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
-                    let mut unigram_frequencies = HashMap::new();
-
-                    for qr in r.results.iter() {
-                        if qr.length == 1 {
-                            unigram_frequencies.insert(qr.input.clone(), qr.frequency);
-                        } else {
-                            fill_results(&mut d, qr, cs);
-                        }
-                    }
+        let n_gram_counts = smoothed_n_gram_counts(number_of_ngrams, number_of_distinct_ngrams);
+        let unigram_total = n_gram_total(&n_gram_counts, 1);
+        let pr = predict_contexts(&data, confusion_set, |r, cs| {
+            let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
+            let mut unigram_frequencies = HashMap::new();
 
-                    let mut results = HashMap::new();
-                    for (k, v) in d.iter() {
-                        let laplace = LaplaceSmoothingResult::get(
-                            v.clone(),
-                            number_of_ngrams.clone(),
-                            number_of_distinct_ngrams.clone(),
-                        );
-                        let mut sum = 0.0;
-                        let uf = unigram_frequencies.get(k).unwrap();
-                        for (k1, v1) in laplace.results.iter() {
-                            let p: f64 = ((*uf as f64)
-                                / (*laplace.n_gram_counts.get(&1).unwrap() as f64))
-                                * ((*v1 as f64)
-                                    / (*laplace
-                                        .n_gram_counts
-                                        .get(&(k1.split_whitespace().count() as i32))
-                                        .unwrap() as f64));
-                            sum += p;
-                        }
-                        let log = sum.log(10.0) * -1.0;
-                        let log = (log * 10000.0).round() / 10000.0;
-                        results.insert(k.clone(), log);
-                    }
+            for qr in r.results.iter() {
+                if qr.length == 1 {
+                    unigram_frequencies.insert(qr.input.clone(), qr.frequency);
+                } else {
+                    fill_results(&mut d, qr, cs);
+                }
+            }
 
-                    pr.push(PredictionResult {
-                        context: r.sentence.clone(),
-                        word_examined: r.word.clone(),
-                        results,
-                    });
-                    break;
+            let mut probabilities = HashMap::new();
+            for (k, v) in d.iter() {
+                let laplace = LaplaceSmoothingResult::with_totals(v, &n_gram_counts);
+                let mut sum = None;
+                // without the backfill a candidate can lack its unigram
+                let (uf, unigram_total) = match (unigram_frequencies.get(k), unigram_total) {
+                    (Some(uf), Some(unigram_total)) => (uf, unigram_total),
+                    _ => continue,
+                };
+                for (k1, v1) in laplace.results.iter() {
+                    let length = k1.split_whitespace().count() as i32;
+                    let context_total = match n_gram_total(&laplace.n_gram_counts, length) {
+                        Some(context_total) => context_total,
+                        None => continue,
+                    };
+                    // the power only applies to the probability of the context
+                    let context = score_ngram(1, 1, *v1, context_total);
+                    let p = score_ngram(*uf, unigram_total, 1, 1)
+                        * context.powf(1_f64 / (length as f64).powf(self.power));
+                    sum = Some(sum.unwrap_or(0.0) + p);
+                }
+                // a candidate without a scored n-gram falls back to the unigram score
+                if let Some(sum) = sum {
+                    probabilities.insert(k.clone(), sum);
                 }
             }
-        }
+
+            let unigram_only = match unigram_total {
+                Some(unigram_total) => add_unigram_only_probabilities(
+                    &mut probabilities,
+                    &unigram_frequencies,
+                    cs,
+                    1.0,
+                    unigram_total as f64,
+                ),
+                None => vec![],
+            };
+
+            let max_matched_order = max_matched_orders(&d, &probabilities);
+
+            PredictionResult {
+                context: r.sentence.clone(),
+                word_examined: r.word.clone(),
+                results: to_scores(&probabilities, DEFAULT_LOG_BASE),
+                normalized: normalize(&probabilities),
+                unigram_only,
+                max_matched_order,
+                rows_fetched: Some(r.rows_fetched),
+                explanation: None,
+            }
+        });
 
         PredictionResults {
             results: pr,
             time_elapsed: data.time_taken,
+            time_elapsed_ms: data.time_taken_ms,
+            sentences: data.sentences,
+            errors: data.errors,
+            perplexity: vec![],
         }
     }
 }
 
-pub struct PowerSumPredictor {
-    pub power: f64,
+/// Represents the maximum predictor with the Lidstone (add-k) smoothing.
+///
+/// The candidate is scored by its most probable context n-gram, like the `MaxPredictor`,
+/// but `k` instead of one is added to every n-gram frequency.
+///
+/// # Fields
+///
+/// * `k` - The value added to every n-gram frequency, must be positive.
+pub struct LidstonePredictor {
+    pub k: f64,
 }
 
-impl Predict for PowerSumPredictor {
+impl Predict for LidstonePredictor {
     fn predict(
         &self,
         data: TimedSentenceResults,
-        confusion_set: Vec<Vec<String>>,
-        number_of_ngrams: HashMap<i32, i64>,
-        number_of_distinct_ngrams: HashMap<i32, i64>,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        number_of_distinct_ngrams: &HashMap<i32, i64>,
     ) -> PredictionResults {
-        let mut pr: Vec<PredictionResult> = Vec::new();
-        for r in data.results.iter() {
-            for cs in confusion_set.iter() {
-                if cs.contains(&r.word) {
-                    let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
-                    let mut unigram_frequencies = HashMap::new();
-
-                    for qr in r.results.iter() {
-                        if qr.length == 1 {
-                            unigram_frequencies.insert(qr.input.clone(), qr.frequency);
-                        } else {
-                            fill_results(&mut d, qr, cs);
-                        }
-                    }
+        predict_max(
+            data,
+            confusion_set,
+            number_of_ngrams,
+            number_of_distinct_ngrams,
+            self.k,
+            DEFAULT_LOG_BASE,
+        )
+    }
+}
 
-                    let mut results = HashMap::new();
-                    for (k, v) in d.iter() {
-                        let laplace = LaplaceSmoothingResult::get(
-                            v.clone(),
-                            number_of_ngrams.clone(),
-                            number_of_distinct_ngrams.clone(),
-                        );
-                        let mut sum = 0.0;
-                        let uf = unigram_frequencies.get(k).unwrap();
-                        for (k1, v1) in laplace.results.iter() {
-                            let length = k1.split_whitespace().count() as u32;
-                            let v = *v1 as i32;
-                            let p: f64 = ((*uf as f64)
-                                / (*laplace.n_gram_counts.get(&1).unwrap() as f64))
-                                * ((v as f64)
-                                    / (*laplace
-                                        .n_gram_counts
-                                        .get(&(k1.split_whitespace().count() as i32))
-                                        .unwrap() as f64))
-                                    .powf(1 as f64 / (length as f64).powf(self.power) as f64);
-                            sum += p;
-                        }
-                        let log = sum.log(10.0) * -1.0;
-                        let log = (log * 10000.0).round() / 10000.0;
-                        results.insert(k.clone(), log);
-                    }
+/// Represents the Stupid Backoff predictor.
+///
+/// The candidate is scored by the relative frequency of its n-grams of the highest order with a
+/// nonzero frequency, multiplied by `BACKOFF_FACTOR` for every order dropped below the highest
+/// order queried for the context, down to its unigram frequency. The raw frequencies are used,
+/// without smoothing. Only the contiguous n-grams back off, the skip-grams are left out.
+///
+/// The backoff score is not a probability, so the returned score is the `-log10` of a relative
+/// score, comparable between the candidates of a context but not with the other predictors.
+pub struct StupidBackoffPredictor {}
 
-                    pr.push(PredictionResult {
-                        context: r.sentence.clone(),
-                        word_examined: r.word.clone(),
-                        results,
-                    });
-                    break;
+impl Predict for StupidBackoffPredictor {
+    fn predict(
+        &self,
+        data: TimedSentenceResults,
+        confusion_set: &[Vec<String>],
+        number_of_ngrams: &HashMap<i32, i64>,
+        _number_of_distinct_ngrams: &HashMap<i32, i64>,
+    ) -> PredictionResults {
+        let pr = predict_contexts(&data, confusion_set, |r, cs| {
+            let mut d: HashMap<String, HashMap<String, i32>> = HashMap::new();
+            let mut unigram_frequencies = HashMap::new();
+
+            for qr in r.results.iter() {
+                if qr.length == 1 {
+                    unigram_frequencies.insert(qr.input.clone(), qr.frequency);
+                } else {
+                    fill_results(&mut d, qr, cs);
                 }
             }
-        }
+
+            let top_order = r.results.iter().map(|qr| qr.length).max().unwrap_or(1);
+
+            let mut probabilities = HashMap::new();
+            let mut unigram_only = vec![];
+
+            for w in cs.iter() {
+                let (score, order) = match backoff_score(
+                    d.get(w),
+                    unigram_frequencies.get(w),
+                    top_order,
+                    number_of_ngrams,
+                ) {
+                    Some(scored) => scored,
+                    None => continue,
+                };
+                if order == 1 {
+                    unigram_only.push(w.clone());
+                }
+                probabilities.insert(w.clone(), score);
+            }
+
+            let max_matched_order = max_matched_orders(&d, &probabilities);
+
+            PredictionResult {
+                context: r.sentence.clone(),
+                word_examined: r.word.clone(),
+                results: to_scores(&probabilities, DEFAULT_LOG_BASE),
+                normalized: normalize(&probabilities),
+                unigram_only,
+                max_matched_order,
+                rows_fetched: Some(r.rows_fetched),
+                explanation: None,
+            }
+        });
 
         PredictionResults {
             results: pr,
             time_elapsed: data.time_taken,
+            time_elapsed_ms: data.time_taken_ms,
+            sentences: data.sentences,
+            errors: data.errors,
+            perplexity: vec![],
+        }
+    }
+}
+
+/// Computes the Stupid Backoff score of a candidate.
+///
+/// # Arguments
+///
+/// * `n_grams` - The context n-grams of the candidate with their frequencies.
+/// * `unigram_freq` - The frequency of the candidate.
+/// * `top_order` - The highest order queried for the context.
+/// * `number_of_ngrams` - The number of n-grams.
+///
+/// # Returns
+///
+/// The score with the order it was computed from, `None` if the candidate can not be scored.
+fn backoff_score(
+    n_grams: Option<&HashMap<String, i32>>,
+    unigram_freq: Option<&i32>,
+    top_order: i32,
+    number_of_ngrams: &HashMap<i32, i64>,
+) -> Option<(f64, i32)> {
+    let mut multiplier = 1.0;
+
+    for order in (2..=top_order).rev() {
+        let freq: i64 = n_grams
+            .into_iter()
+            .flatten()
+            .filter(|(n_gram, _)| {
+                n_gram.split_whitespace().count() as i32 == order && !is_skip_gram(n_gram)
+            })
+            .map(|(_, freq)| *freq as i64)
+            .sum();
+
+        if freq > 0 {
+            if let Some(total) = n_gram_total(number_of_ngrams, order) {
+                return Some((multiplier * freq as f64 / total as f64, order));
+            }
+        }
+
+        multiplier *= BACKOFF_FACTOR;
+    }
+
+    let unigram_total = n_gram_total(number_of_ngrams, 1)?;
+
+    Some((multiplier * *unigram_freq? as f64 / unigram_total as f64, 1))
+}
+
+/// Gets the predictor by its name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the predictor, one of `PREDICTORS`.
+/// * `power` - The power of the `power_sum` predictor, `DEFAULT_POWER` if not given.
+/// * `k` - The k of the `lidstone` predictor, `DEFAULT_K` if not given.
+/// * `log_base` - The base of the logarithm of the `max` and `sum` predictors, `DEFAULT_LOG_BASE` if not given.
+///
+/// # Returns
+///
+/// A `Result` containing the predictor if the name is known, otherwise a `String` with the error message.
+pub fn get_predictor(
+    name: &str,
+    power: Option<f64>,
+    k: Option<f64>,
+    log_base: Option<f64>,
+) -> Result<Box<dyn Predict>, String> {
+    let log_base = match log_base {
+        Some(log_base) if !(log_base > 1.0 && log_base.is_finite()) => {
+            return Err(format!(
+                "Invalid log_base: {}, expected a number greater than 1",
+                log_base
+            ))
         }
+        Some(_) if PREDICTORS.contains(&name) && !matches!(name, "max" | "sum") => {
+            return Err("log_base is only supported by the max and sum predictors".to_string())
+        }
+        Some(log_base) => log_base,
+        None => DEFAULT_LOG_BASE,
+    };
+
+    match name {
+        "max" => Ok(Box::new(MaxPredictor { log_base })),
+        "sum" => Ok(Box::new(SumPredictor { log_base })),
+        "power_sum" => Ok(Box::new(PowerSumPredictor {
+            power: power.unwrap_or(DEFAULT_POWER),
+        })),
+        "lidstone" => match k.unwrap_or(DEFAULT_K) {
+            k if k > 0.0 && k.is_finite() => Ok(Box::new(LidstonePredictor { k })),
+            k => Err(format!("Invalid k: {}, expected a positive number", k)),
+        },
+        "stupid_backoff" => Ok(Box::new(StupidBackoffPredictor {})),
+        _ => Err(format!(
+            "Unknown predictor: {}, expected one of: {}",
+            name,
+            PREDICTORS.join(", ")
+        )),
     }
 }
 
@@ -345,21 +1571,906 @@ impl Predict for PowerSumPredictor {
 ///
 /// # Returns
 ///
-/// The prediction results.
-pub fn predict<T>(
-    predictor: T,
+/// The prediction results with the perplexity of every context.
+pub fn predict(
+    predictor: &dyn Predict,
     data: TimedSentenceResults,
-    confusion_set: Vec<Vec<String>>,
-    number_of_ngrams: HashMap<i32, i64>,
-    number_of_distinct_ngrams: HashMap<i32, i64>,
-) -> PredictionResults
-where
-    T: Predict,
-{
-    predictor.predict(
+    confusion_set: &[Vec<String>],
+    number_of_ngrams: &HashMap<i32, i64>,
+    number_of_distinct_ngrams: &HashMap<i32, i64>,
+) -> PredictionResults {
+    let mut results = predictor.predict(
         data,
         confusion_set,
         number_of_ngrams,
         number_of_distinct_ngrams,
-    )
+    );
+    results.perplexity = sentence_perplexities(&results.results, predictor.log_base());
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_grams::solver::model::{QueryResult, SentenceResult};
+
+    #[test]
+    fn test_scaled() {
+        let results = PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results: vec![PredictionResult {
+                context: "On od mene zahtjeva da".to_string(),
+                word_examined: "zahtjeva".to_string(),
+                results: HashMap::from([
+                    ("zahtijeva".to_string(), 1.2345),
+                    ("zahtjeva".to_string(), 2.5),
+                ]),
+                normalized: HashMap::new(),
+                unigram_only: vec![],
+                max_matched_order: HashMap::new(),
+                rows_fetched: None,
+                explanation: None,
+            }],
+            sentences: 1,
+            errors: vec![],
+            perplexity: vec![],
+        };
+
+        let scaled = results.scaled(1000);
+
+        assert_eq!(scaled.results[0].results["zahtijeva"], 1234);
+        assert_eq!(scaled.results[0].results["zahtjeva"], 2500);
+        assert_eq!(
+            serde_json::to_value(&scaled).unwrap()["results"][0]["results"]["zahtijeva"],
+            1234
+        );
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let results = PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results: vec![PredictionResult {
+                context: "mene zahtjeva, da".to_string(),
+                word_examined: "zahtjeva".to_string(),
+                results: HashMap::from([
+                    ("zahtjeva".to_string(), 2.5),
+                    ("zahtijeva".to_string(), 1.25),
+                ]),
+                normalized: HashMap::new(),
+                unigram_only: vec![],
+                max_matched_order: HashMap::new(),
+                rows_fetched: None,
+                explanation: None,
+            }],
+            sentences: 1,
+            errors: vec![],
+            perplexity: vec![],
+        };
+
+        assert_eq!(
+            results.to_csv(),
+            "context,word_examined,candidate,score\n\
+             \"mene zahtjeva, da\",zahtjeva,zahtijeva,1.25\n\
+             \"mene zahtjeva, da\",zahtjeva,zahtjeva,2.5\n"
+        );
+    }
+
+    #[test]
+    fn test_time_elapsed_serializes_both_forms() {
+        let results = PredictionResults {
+            time_elapsed: "123 ms".to_string(),
+            time_elapsed_ms: 123,
+            results: vec![],
+            sentences: 0,
+            errors: vec![],
+            perplexity: vec![],
+        };
+
+        let value = serde_json::to_value(&results).unwrap();
+
+        assert_eq!(value["timeElapsed"], "123 ms");
+        assert_eq!(value["timeElapsedMs"], 123);
+
+        let decoded: PredictionResults =
+            serde_json::from_str(r#"{"timeElapsed":"5 ms","results":[],"sentences":0}"#).unwrap();
+
+        assert_eq!(decoded.time_elapsed_ms, 0);
+    }
+
+    #[test]
+    fn test_get_predictor() {
+        assert!(get_predictor("max", None, None, None).is_ok());
+        assert!(get_predictor("power_sum", Some(2.0), None, None).is_ok());
+        assert!(get_predictor("lidstone", None, Some(0.5), None).is_ok());
+        assert!(get_predictor("sum", None, None, Some(std::f64::consts::E)).is_ok());
+        assert_eq!(
+            get_predictor("lidstone", None, Some(0.0), None).err(),
+            Some("Invalid k: 0, expected a positive number".to_string())
+        );
+        assert_eq!(
+            get_predictor("max", None, None, Some(1.0)).err(),
+            Some("Invalid log_base: 1, expected a number greater than 1".to_string())
+        );
+        assert!(get_predictor("lidstone", None, None, Some(2.0)).is_err());
+        assert_eq!(
+            get_predictor("min", None, None, None).err(),
+            Some(
+                "Unknown predictor: min, expected one of: max, sum, power_sum, lidstone, stupid_backoff"
+                    .to_string()
+            )
+        );
+    }
+
+    fn query_result(input: &str, frequency: i32) -> QueryResult {
+        QueryResult {
+            input: input.to_string(),
+            frequency,
+            length: input.split_whitespace().count() as i32,
+        }
+    }
+
+    fn sample_data() -> TimedSentenceResults {
+        TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "On od mene zahtjeva da".to_string(),
+                word: "zahtjeva".to_string(),
+                results: vec![
+                    query_result("zahtijeva", 100),
+                    query_result("zahtjeva", 50),
+                    query_result("mene zahtijeva", 3),
+                    query_result("mene zahtjeva", 10),
+                    query_result("od mene zahtijeva", 0),
+                    query_result("od mene zahtjeva", 4),
+                ],
+                rows_fetched: 5,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        }
+    }
+
+    fn sample_confusion_set() -> Vec<Vec<String>> {
+        vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]]
+    }
+
+    fn sample_number_of_ngrams() -> HashMap<i32, i64> {
+        HashMap::from([(1, 1000), (2, 800), (3, 600)])
+    }
+
+    #[test]
+    fn test_smoothed_n_gram_counts_match_per_candidate_computation() {
+        let number_of_ngrams = HashMap::from([(1, 1000), (2, 800), (3, 600)]);
+        let number_of_distinct_ngrams = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let results = HashMap::from([
+            ("sam gledao".to_string(), 5),
+            ("ja sam gledao".to_string(), 2),
+        ]);
+
+        let totals = smoothed_n_gram_counts(&number_of_ngrams, &number_of_distinct_ngrams);
+        let precomputed = LaplaceSmoothingResult::with_totals(&results, &totals);
+        let per_candidate = LaplaceSmoothingResult::get(
+            results.clone(),
+            number_of_ngrams.clone(),
+            number_of_distinct_ngrams.clone(),
+        );
+
+        assert_eq!(precomputed.n_gram_counts, per_candidate.n_gram_counts);
+        assert_eq!(precomputed.results, per_candidate.results);
+        assert_eq!(totals, HashMap::from([(1, 1100), (2, 880), (3, 660)]));
+    }
+
+    #[test]
+    fn test_distinct_counts_override_changes_scores() {
+        let distinct = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let overridden = HashMap::from([(1, 100), (2, 200), (3, 60)]);
+
+        let score = |distinct: HashMap<i32, i64>| {
+            let result = predict(
+                &MaxPredictor::default(),
+                sample_data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &distinct,
+            );
+            result.results[0].results["zahtjeva"]
+        };
+
+        // the bigram "mene zahtjeva" dominates in both cases
+        let expected = |bigram_total: f64| {
+            let p: f64 = (50.0 / 1100.0) * (11.0 / bigram_total);
+            (-p.log(10.0) * 10000.0).round() / 10000.0
+        };
+
+        assert_eq!(score(distinct), expected(880.0));
+        assert_eq!(score(overridden), expected(1000.0));
+    }
+
+    #[test]
+    fn test_score_ngram() {
+        assert_eq!(score_ngram(50, 1000, 10, 800), 0.05 * 0.0125);
+        assert_eq!(score_ngram(1, 1, 1, 1), 1.0);
+        assert_eq!(score_ngram(0, 1000, 10, 800), 0.0);
+        assert_eq!(score_ngram(1000, 1000, 800, 800), 1.0);
+        assert!(score_ngram(1, i64::MAX, 1, i64::MAX) > 0.0);
+    }
+
+    #[test]
+    fn test_predictors_match_inlined_math() {
+        let distinct = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let score = |predictor: &dyn Predict| {
+            let result = predict(
+                predictor,
+                sample_data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &distinct,
+            );
+            result.results[0].results["zahtjeva"]
+        };
+        let round = |p: f64| (-p.log(10.0) * 10000.0).round() / 10000.0;
+
+        // "zahtjeva" 50 / 1100, smoothed "mene zahtjeva" 11 / 880, "od mene zahtjeva" 5 / 660
+        let unigram = 50.0 / 1100.0;
+        let (bigram, trigram) = (11.0 / 880.0, 5.0 / 660.0);
+
+        assert_eq!(
+            score(&MaxPredictor::default()),
+            round(f64::max(unigram * bigram, unigram * trigram))
+        );
+        assert_eq!(
+            score(&SumPredictor::default()),
+            round(unigram * bigram + unigram * trigram)
+        );
+
+        let power_sum = |p: f64, length: f64| unigram * p.powf(1.0 / length.powf(DEFAULT_POWER));
+        let expected = round(power_sum(bigram, 2.0) + power_sum(trigram, 3.0));
+        let actual = score(&PowerSumPredictor {
+            power: DEFAULT_POWER,
+        });
+
+        assert!((actual - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_limit_top_k() {
+        let scores = [("a", 5.0), ("b", 1.0), ("c", 4.0), ("d", 2.0), ("e", 3.0)];
+        let mut result = PredictionResults {
+            time_elapsed: "0 ms".to_string(),
+            time_elapsed_ms: 0,
+            results: vec![PredictionResult {
+                context: "x a y".to_string(),
+                word_examined: "a".to_string(),
+                results: scores
+                    .iter()
+                    .map(|(word, score)| (word.to_string(), *score))
+                    .collect(),
+                normalized: HashMap::new(),
+                unigram_only: vec!["c".to_string()],
+                max_matched_order: HashMap::from([("a".to_string(), 3), ("c".to_string(), 0)]),
+                rows_fetched: None,
+                explanation: None,
+            }],
+            sentences: 1,
+            errors: vec![],
+            perplexity: vec![],
+        };
+
+        result.limit_top_k(2);
+
+        let mut kept: Vec<&String> = result.results[0].results.keys().collect();
+        kept.sort();
+
+        assert_eq!(kept, vec!["a", "b", "d"]);
+        assert!(result.results[0].unigram_only.is_empty());
+        assert_eq!(
+            result.results[0].max_matched_order,
+            HashMap::from([("a".to_string(), 3)])
+        );
+    }
+
+    #[test]
+    fn test_overlapping_groups_are_all_scored() {
+        let mut confusion_set = sample_confusion_set();
+        confusion_set.push(vec!["zahtjeva".to_string(), "traži".to_string()]);
+
+        let result = predict(
+            &MaxPredictor::default(),
+            sample_data(),
+            &confusion_set,
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+
+        assert_eq!(result.results.len(), 2);
+        assert!(result.results[0].results.contains_key("zahtijeva"));
+        assert!(!result.results[1].results.contains_key("zahtijeva"));
+        assert!(result.results[1].results.contains_key("zahtjeva"));
+    }
+
+    #[test]
+    fn test_candidate_inside_another_candidate() {
+        let data = TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "ona mama".to_string(),
+                word: "mama".to_string(),
+                results: vec![
+                    query_result("ma", 10),
+                    query_result("mama", 500),
+                    query_result("ona ma", 1),
+                    query_result("ona mama", 200),
+                ],
+                rows_fetched: 4,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        };
+
+        let result = predict(
+            &MaxPredictor::default(),
+            data,
+            &[vec!["ma".to_string(), "mama".to_string()]],
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+        let explanation = result.results[0].explanation.as_ref().unwrap();
+
+        let n_grams = |candidate: &str| -> Vec<String> {
+            explanation[candidate]
+                .iter()
+                .map(|term| term.n_gram.clone())
+                .collect()
+        };
+        assert_eq!(n_grams("ma"), vec!["ona ma"]);
+        assert_eq!(n_grams("mama"), vec!["ona mama"]);
+        assert_eq!(explanation["ma"][0].unigram_frequency, Some(10));
+    }
+
+    #[test]
+    fn test_unigram_only_context() {
+        let data = TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "zahtjeva".to_string(),
+                word: "zahtjeva".to_string(),
+                results: vec![query_result("zahtijeva", 100), query_result("zahtjeva", 50)],
+                rows_fetched: 2,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        };
+
+        let result = predict(
+            &SumPredictor::default(),
+            data,
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+        let result = &result.results[0];
+
+        let expected = |uf: f64| {
+            let p: f64 = (uf + 1.0) / 1100.0;
+            (-p.log(10.0) * 10000.0).round() / 10000.0
+        };
+
+        assert_eq!(result.results["zahtijeva"], expected(100.0));
+        assert_eq!(result.results["zahtjeva"], expected(50.0));
+        assert_eq!(result.unigram_only, vec!["zahtijeva", "zahtjeva"]);
+        assert_eq!(result.rows_fetched, Some(2));
+    }
+
+    #[test]
+    fn test_normalized_sums_to_one() {
+        let distinct = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let predictors: Vec<Box<dyn Predict>> = vec![
+            Box::new(MaxPredictor::default()),
+            Box::new(SumPredictor::default()),
+        ];
+
+        for predictor in predictors {
+            let result = predict(
+                &*predictor,
+                sample_data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &distinct,
+            );
+            let result = &result.results[0];
+
+            let sum: f64 = result.normalized.values().sum();
+            assert!((sum - 1.0).abs() < 1e-9);
+            assert_eq!(result.normalized.len(), result.results.len());
+            assert_eq!(
+                result.normalized["zahtjeva"] > result.normalized["zahtijeva"],
+                result.results["zahtjeva"] < result.results["zahtijeva"]
+            );
+        }
+
+        assert!(normalize(&HashMap::from([("zahtjeva".to_string(), 0.0)])).is_empty());
+    }
+
+    #[test]
+    fn test_explanation() {
+        let distinct = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+
+        let mut max = predict(
+            &MaxPredictor::default(),
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &distinct,
+        );
+        let result = &max.results[0];
+        let terms = &result.explanation.as_ref().unwrap()["zahtjeva"];
+
+        let n_grams: Vec<&str> = terms.iter().map(|term| term.n_gram.as_str()).collect();
+        assert_eq!(n_grams, vec!["od mene zahtjeva", "mene zahtjeva"]);
+        assert_eq!(terms[1].frequency, 11.0);
+        assert_eq!(terms[1].total, 880.0);
+        assert_eq!(terms[1].unigram_frequency, Some(50));
+        let picked: Vec<&ScoreTerm> = terms
+            .iter()
+            .filter(|term| term.contribution > 0.0)
+            .collect();
+        assert_eq!(picked.len(), 1);
+        assert_eq!(
+            to_score(picked[0].contribution, DEFAULT_LOG_BASE),
+            result.results["zahtjeva"]
+        );
+
+        max.hide_explanation();
+        assert!(max.results[0].explanation.is_none());
+        assert!(!serde_json::to_string(&max).unwrap().contains("explanation"));
+
+        let sum = predict(
+            &SumPredictor::default(),
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &distinct,
+        );
+        let result = &sum.results[0];
+        for (candidate, terms) in result.explanation.as_ref().unwrap() {
+            let total: f64 = terms.iter().map(|term| term.contribution).sum();
+            assert_eq!(to_score(total, DEFAULT_LOG_BASE), result.results[candidate]);
+        }
+
+        let lidstone = predict(
+            &LidstonePredictor { k: 0.5 },
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &distinct,
+        );
+        let result = &lidstone.results[0];
+        let terms = &result.explanation.as_ref().unwrap()["zahtjeva"];
+        assert_eq!(terms[1].frequency, 10.5);
+        assert_eq!(terms[1].total, 840.0);
+    }
+
+    #[test]
+    fn test_lidstone_with_k_one_matches_max_predictor() {
+        let distinct = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let scores = |predictor: &dyn Predict| {
+            predict(
+                predictor,
+                sample_data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &distinct,
+            )
+            .results[0]
+                .results
+                .clone()
+        };
+
+        assert_eq!(
+            scores(&LidstonePredictor { k: 1.0 }),
+            scores(&MaxPredictor::default())
+        );
+    }
+
+    #[test]
+    fn test_lidstone_predictor() {
+        let result = predict(
+            &LidstonePredictor { k: 0.5 },
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+        let round = |p: f64| (-p.log(10.0) * 10000.0).round() / 10000.0;
+
+        // "zahtjeva" 50 / 1050, "mene zahtjeva" 10.5 / 840, "od mene zahtjeva" 4.5 / 630
+        let unigram = 50.0 / 1050.0;
+        let expected = round(f64::max(unigram * 10.5 / 840.0, unigram * 4.5 / 630.0));
+
+        assert_eq!(result.results[0].results["zahtjeva"], expected);
+    }
+
+    #[test]
+    fn test_lidstone_smoothing_result() {
+        let results = HashMap::from([("sam gledao".to_string(), 5)]);
+        let smoothed = LidstoneSmoothingResult::get(
+            &results,
+            &HashMap::from([(1, 1000), (2, 800)]),
+            &HashMap::from([(1, 100), (2, 80)]),
+            0.25,
+        );
+
+        assert_eq!(smoothed.results["sam gledao"], 5.25);
+        assert_eq!(
+            *smoothed.n_gram_counts,
+            HashMap::from([(1, 1025.0), (2, 820.0)])
+        );
+    }
+
+    #[test]
+    fn test_max_matched_order() {
+        let result = predict(
+            &MaxPredictor::default(),
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+        let result = &result.results[0];
+
+        // "od mene zahtijeva" was not found, only the bigram "mene zahtijeva"
+        assert_eq!(result.max_matched_order["zahtjeva"], 3);
+        assert_eq!(result.max_matched_order["zahtijeva"], 2);
+    }
+
+    #[test]
+    fn test_sentence_perplexities() {
+        let result = |context: &str, word: &str, scores: &[(&str, f64)]| PredictionResult {
+            context: context.to_string(),
+            word_examined: word.to_string(),
+            results: scores
+                .iter()
+                .map(|(candidate, score)| (candidate.to_string(), *score))
+                .collect(),
+            normalized: HashMap::new(),
+            unigram_only: vec![],
+            max_matched_order: HashMap::new(),
+            rows_fetched: None,
+            explanation: None,
+        };
+
+        let results = vec![
+            result(
+                "od mene zahtjeva",
+                "zahtjeva",
+                &[("zahtijeva", 1.0), ("zahtjeva", 2.0)],
+            ),
+            result("od mene zahtjeva", "mene", &[("mene", 3.0), ("meni", 4.0)]),
+            result("da dođem", "dođem", &[]),
+            result("nema ga", "ga", &[("ga", 400.0)]),
+        ];
+
+        let perplexities = sentence_perplexities(&results, DEFAULT_LOG_BASE);
+
+        assert_eq!(perplexities.len(), 2);
+        assert_eq!(perplexities[0].context, "od mene zahtjeva");
+        assert_eq!(perplexities[0].words, 2);
+        // the geometric mean of 1 / 0.1 and 1 / 0.001
+        assert!((perplexities[0].perplexity - 100.0).abs() < 1e-9);
+        // the zero probability is clamped to `MIN_PROBABILITY`
+        assert!(perplexities[1].perplexity.is_finite());
+        assert!((perplexities[1].perplexity / 1e300 - 1.0).abs() < 1e-9);
+
+        let predictions = predict(
+            &MaxPredictor::default(),
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+
+        assert_eq!(predictions.perplexity.len(), 1);
+        assert!(predictions.perplexity[0].perplexity > 1.0);
+    }
+
+    #[test]
+    fn test_log_base() {
+        let number_of_distinct_ngrams = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+        let base_10 = |predictor: &dyn Predict| {
+            predict(
+                predictor,
+                sample_data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &number_of_distinct_ngrams,
+            )
+        };
+
+        let pairs: [(Box<dyn Predict>, Box<dyn Predict>); 2] = [
+            (
+                Box::new(MaxPredictor::default()),
+                Box::new(MaxPredictor {
+                    log_base: std::f64::consts::E,
+                }),
+            ),
+            (
+                Box::new(SumPredictor::default()),
+                Box::new(SumPredictor {
+                    log_base: std::f64::consts::E,
+                }),
+            ),
+        ];
+
+        for (default, natural) in pairs {
+            let default = base_10(&*default);
+            let natural = base_10(&*natural);
+
+            for (candidate, score) in &default.results[0].results {
+                // ln(p) = log10(p) * ln(10), up to the rounding of both scores
+                let expected = score * std::f64::consts::LN_10;
+                assert!((natural.results[0].results[candidate] - expected).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edit_candidates() {
+        let mut data = sample_data();
+        data.results[0].word = "zahtjva".to_string();
+        // the backfilled results of the word, it is not in the corpus
+        data.results[0].results.extend([
+            query_result("zahtjva", 0),
+            query_result("mene zahtjva", 0),
+            query_result("od mene zahtjva", 0),
+        ]);
+        data.results[0].edit_candidates = vec![
+            "zahtjva".to_string(),
+            "zahtjeva".to_string(),
+            "zahtijeva".to_string(),
+        ];
+
+        // the word is in no confusion group, it is predicted against its edit candidates
+        let result = predict(
+            &MaxPredictor::default(),
+            data,
+            &[],
+            &sample_number_of_ngrams(),
+            &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+        );
+
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(result.results[0].word_examined, "zahtjva");
+        assert_eq!(result.results[0].results.len(), 3);
+        assert!(result.results[0].results["zahtijeva"] < result.results[0].results["zahtjva"]);
+    }
+
+    #[test]
+    fn test_skip_grams() {
+        let mut data = sample_data();
+        data.results[0].results.extend([
+            query_result("od * zahtijeva", 40),
+            query_result("od * zahtjeva", 2),
+        ]);
+        let number_of_distinct_ngrams = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+
+        let without = predict(
+            &SumPredictor::default(),
+            sample_data(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &number_of_distinct_ngrams,
+        );
+        let with = predict(
+            &SumPredictor::default(),
+            data.clone(),
+            &sample_confusion_set(),
+            &sample_number_of_ngrams(),
+            &number_of_distinct_ngrams,
+        );
+
+        // the skip-gram is an additional term of the sum, with the trigram totals
+        assert!(with.results[0].results["zahtijeva"] < without.results[0].results["zahtijeva"]);
+        // but it only matches two words of the context
+        assert_eq!(with.results[0].max_matched_order["zahtijeva"], 2);
+
+        let backoff = |data| {
+            predict(
+                &StupidBackoffPredictor {},
+                data,
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &number_of_distinct_ngrams,
+            )
+            .results[0]
+                .results
+                .clone()
+        };
+
+        assert_eq!(backoff(data), backoff(sample_data()));
+    }
+
+    #[test]
+    fn test_to_score_is_finite() {
+        assert_eq!(to_score(0.01, DEFAULT_LOG_BASE), 2.0);
+        assert_eq!(to_score(0.0, DEFAULT_LOG_BASE), 300.0);
+        assert!(to_score(f64::NAN, DEFAULT_LOG_BASE).is_finite());
+        assert!(to_score(f64::INFINITY, DEFAULT_LOG_BASE).is_finite());
+        assert!(to_score(-1.0, DEFAULT_LOG_BASE).is_finite());
+    }
+
+    fn all_predictors() -> Vec<Box<dyn Predict>> {
+        vec![
+            Box::new(MaxPredictor::default()),
+            Box::new(SumPredictor::default()),
+            Box::new(PowerSumPredictor {
+                power: DEFAULT_POWER,
+            }),
+            Box::new(LidstonePredictor { k: 0.5 }),
+            Box::new(StupidBackoffPredictor {}),
+        ]
+    }
+
+    #[test]
+    fn test_empty_results_set() {
+        let data = || TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "On od mene zahtjeva da".to_string(),
+                word: "zahtjeva".to_string(),
+                results: vec![],
+                rows_fetched: 0,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        };
+
+        for predictor in all_predictors() {
+            let result = predict(
+                &*predictor,
+                data(),
+                &sample_confusion_set(),
+                &sample_number_of_ngrams(),
+                &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+            );
+
+            assert_eq!(result.results.len(), 1);
+            assert!(result.results[0].results.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_missing_counts_give_finite_scores() {
+        let mut data = sample_data();
+        // a zero unigram frequency and a zero n-gram total would give log(0) and a division by zero
+        data.results[0].results[0] = query_result("zahtijeva", 0);
+        let number_of_ngrams = HashMap::from([(1, 1000), (2, 0), (3, 600)]);
+
+        for predictor in all_predictors() {
+            let result = predict(
+                &*predictor,
+                data.clone(),
+                &sample_confusion_set(),
+                &number_of_ngrams,
+                &HashMap::from([(1, 100), (3, 60)]),
+            );
+            let scores = &result.results[0].results;
+
+            assert_eq!(scores.len(), 2);
+            assert!(scores.values().all(|score| score.is_finite()));
+        }
+
+        for predictor in all_predictors() {
+            let result = predict(
+                &*predictor,
+                sample_data(),
+                &sample_confusion_set(),
+                &HashMap::from([(2, 800), (3, 600)]),
+                &HashMap::from([(1, 100), (2, 80), (3, 60)]),
+            );
+            let scores = &result.results[0].results;
+
+            // only the backoff scores the context n-grams without the unigram total
+            assert!(scores.values().all(|score| score.is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_stupid_backoff_predictor() {
+        let mut data = sample_data();
+        data.results[0].results.push(query_result("traži", 30));
+        data.results[0].results.push(query_result("mene traži", 0));
+        data.results[0]
+            .results
+            .push(query_result("od mene traži", 0));
+        let mut confusion_set = sample_confusion_set();
+        confusion_set[0].push("traži".to_string());
+
+        let result = predict(
+            &StupidBackoffPredictor {},
+            data,
+            &confusion_set,
+            &sample_number_of_ngrams(),
+            &HashMap::new(),
+        );
+        let result = &result.results[0];
+        let round = |p: f64| (-p.log(10.0) * 10000.0).round() / 10000.0;
+
+        // "od mene zahtjeva" 4 / 600, "mene zahtijeva" 3 / 800 backed off once,
+        // "traži" 30 / 1000 backed off twice
+        assert_eq!(result.results["zahtjeva"], round(4.0 / 600.0));
+        assert_eq!(result.results["zahtijeva"], round(0.4 * 3.0 / 800.0));
+        assert_eq!(result.results["traži"], round(0.4 * 0.4 * 30.0 / 1000.0));
+        assert_eq!(result.unigram_only, vec!["traži"]);
+    }
+
+    #[test]
+    fn test_parallel_predictions_match_per_sentence() {
+        let confusion_set = vec![
+            vec!["zahtijeva".to_string(), "zahtjeva".to_string()],
+            vec!["zahtjeva".to_string(), "zahtijevao".to_string()],
+        ];
+        let sentence = |i: i32| SentenceResult {
+            sentence: format!("On {} od mene zahtjeva da", i),
+            word: "zahtjeva".to_string(),
+            results: vec![
+                query_result("zahtijeva", 100 + i),
+                query_result("zahtjeva", 50 + 2 * i),
+                query_result("zahtijevao", i % 7),
+                query_result("mene zahtijeva", i % 5),
+                query_result("mene zahtjeva", 10 + i % 3),
+                query_result("od mene zahtjeva", i % 4),
+            ],
+            rows_fetched: 6,
+            edit_candidates: vec![],
+        };
+        let data = |results: Vec<SentenceResult>| TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            sentences: results.len(),
+            results,
+            errors: vec![],
+        };
+        let number_of_distinct_ngrams = HashMap::from([(1, 100), (2, 80), (3, 60)]);
+
+        for predictor in all_predictors() {
+            let parallel = predict(
+                &*predictor,
+                data((0..200).map(sentence).collect()),
+                &confusion_set,
+                &sample_number_of_ngrams(),
+                &number_of_distinct_ngrams,
+            );
+
+            let serial: Vec<PredictionResult> = (0..200)
+                .flat_map(|i| {
+                    predict(
+                        &*predictor,
+                        data(vec![sentence(i)]),
+                        &confusion_set,
+                        &sample_number_of_ngrams(),
+                        &number_of_distinct_ngrams,
+                    )
+                    .results
+                })
+                .collect();
+
+            assert_eq!(parallel.results.len(), 400);
+            assert_eq!(
+                serde_json::to_value(&parallel.results).unwrap(),
+                serde_json::to_value(&serial).unwrap()
+            );
+        }
+    }
 }
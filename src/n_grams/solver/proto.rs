@@ -0,0 +1,199 @@
+use crate::n_grams::solver::predictor;
+use prost::Message;
+use std::collections::HashMap;
+
+/// The content type of the protobuf encoded predictions.
+pub static PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Represents the prediction results, see `proto/prediction.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct PredictionResults {
+    #[prost(string, tag = "1")]
+    pub time_elapsed: String,
+    #[prost(message, repeated, tag = "2")]
+    pub results: Vec<PredictionResult>,
+    #[prost(uint64, tag = "3")]
+    pub sentences: u64,
+    #[prost(uint64, tag = "4")]
+    pub time_elapsed_ms: u64,
+    #[prost(string, repeated, tag = "5")]
+    pub errors: Vec<String>,
+    #[prost(message, repeated, tag = "6")]
+    pub perplexity: Vec<SentencePerplexity>,
+}
+
+/// Represents the perplexity of a context, see `proto/prediction.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct SentencePerplexity {
+    #[prost(string, tag = "1")]
+    pub context: String,
+    #[prost(uint64, tag = "2")]
+    pub words: u64,
+    #[prost(double, tag = "3")]
+    pub perplexity: f64,
+}
+
+/// Represents the prediction result, see `proto/prediction.proto`.
+///
+/// The explanation of the scores is not encoded.
+#[derive(Clone, PartialEq, Message)]
+pub struct PredictionResult {
+    #[prost(string, tag = "1")]
+    pub context: String,
+    #[prost(string, tag = "2")]
+    pub word_examined: String,
+    #[prost(map = "string, double", tag = "3")]
+    pub results: HashMap<String, f64>,
+    #[prost(string, repeated, tag = "4")]
+    pub unigram_only: Vec<String>,
+    #[prost(uint64, optional, tag = "5")]
+    pub rows_fetched: Option<u64>,
+    #[prost(map = "string, int32", tag = "6")]
+    pub max_matched_order: HashMap<String, i32>,
+    #[prost(map = "string, double", tag = "7")]
+    pub normalized: HashMap<String, f64>,
+}
+
+impl From<&predictor::PredictionResults> for PredictionResults {
+    fn from(results: &predictor::PredictionResults) -> Self {
+        PredictionResults {
+            time_elapsed: results.time_elapsed.clone(),
+            results: results
+                .results
+                .iter()
+                .map(|result| PredictionResult {
+                    context: result.context.clone(),
+                    word_examined: result.word_examined.clone(),
+                    results: result.results.clone(),
+                    unigram_only: result.unigram_only.clone(),
+                    rows_fetched: result.rows_fetched.map(|rows| rows as u64),
+                    max_matched_order: result.max_matched_order.clone(),
+                    normalized: result.normalized.clone(),
+                })
+                .collect(),
+            sentences: results.sentences as u64,
+            time_elapsed_ms: results.time_elapsed_ms as u64,
+            errors: results.errors.clone(),
+            perplexity: results
+                .perplexity
+                .iter()
+                .map(|perplexity| SentencePerplexity {
+                    context: perplexity.context.clone(),
+                    words: perplexity.words as u64,
+                    perplexity: perplexity.perplexity,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<PredictionResults> for predictor::PredictionResults {
+    fn from(results: PredictionResults) -> Self {
+        predictor::PredictionResults {
+            time_elapsed: results.time_elapsed,
+            time_elapsed_ms: results.time_elapsed_ms as u128,
+            results: results
+                .results
+                .into_iter()
+                .map(|result| predictor::PredictionResult {
+                    context: result.context,
+                    word_examined: result.word_examined,
+                    results: result.results,
+                    normalized: result.normalized,
+                    unigram_only: result.unigram_only,
+                    max_matched_order: result.max_matched_order,
+                    rows_fetched: result.rows_fetched.map(|rows| rows as usize),
+                    explanation: None,
+                })
+                .collect(),
+            sentences: results.sentences as usize,
+            errors: results.errors,
+            perplexity: results
+                .perplexity
+                .into_iter()
+                .map(|perplexity| predictor::SentencePerplexity {
+                    context: perplexity.context,
+                    words: perplexity.words as usize,
+                    perplexity: perplexity.perplexity,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Encodes the prediction results.
+///
+/// # Arguments
+///
+/// * `results` - The prediction results.
+///
+/// # Returns
+///
+/// The protobuf encoded bytes.
+pub fn encode(results: &predictor::PredictionResults) -> Vec<u8> {
+    PredictionResults::from(results).encode_to_vec()
+}
+
+/// Decodes the prediction results.
+///
+/// # Arguments
+///
+/// * `bytes` - The protobuf encoded bytes.
+///
+/// # Returns
+///
+/// A `Result` containing the prediction results if the bytes are valid, otherwise a `String` with the error message.
+pub fn decode(bytes: &[u8]) -> Result<predictor::PredictionResults, String> {
+    match PredictionResults::decode(bytes) {
+        Ok(results) => Ok(results.into()),
+        Err(_) => Err("Invalid protobuf encoding".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let results = predictor::PredictionResults {
+            time_elapsed: "12 ms".to_string(),
+            time_elapsed_ms: 12,
+            results: vec![predictor::PredictionResult {
+                context: "On od mene zahtjeva da".to_string(),
+                word_examined: "zahtjeva".to_string(),
+                results: HashMap::from([
+                    ("zahtijeva".to_string(), 2.1234),
+                    ("zahtjeva".to_string(), 3.5),
+                ]),
+                normalized: HashMap::new(),
+                unigram_only: vec!["zahtjeva".to_string()],
+                max_matched_order: HashMap::from([("zahtijeva".to_string(), 3)]),
+                rows_fetched: Some(4),
+                explanation: None,
+            }],
+            sentences: 1,
+            errors: vec![],
+            perplexity: vec![predictor::SentencePerplexity {
+                context: "On od mene zahtjeva da".to_string(),
+                words: 1,
+                perplexity: 132.8095,
+            }],
+        };
+
+        let decoded = decode(&encode(&results)).unwrap();
+
+        assert_eq!(decoded.time_elapsed, "12 ms");
+        assert_eq!(decoded.time_elapsed_ms, 12);
+        assert_eq!(decoded.sentences, 1);
+        assert_eq!(decoded.results.len(), 1);
+        assert_eq!(decoded.results[0].context, results.results[0].context);
+        assert_eq!(decoded.results[0].results, results.results[0].results);
+        assert_eq!(
+            decoded.results[0].unigram_only,
+            results.results[0].unigram_only
+        );
+        assert_eq!(decoded.results[0].rows_fetched, Some(4));
+        assert_eq!(decoded.perplexity, results.perplexity);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::n_grams::solver::{
+    model::{replay_queries, SolverWithConfusionSet, TimedSentenceResults, DEFAULT_WINDOW},
+    predictor::{get_predictor, predict, PredictionResult, PredictionResults},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// The tolerance of the score comparison, the scores are rounded to four decimals.
+const SCORE_TOLERANCE: f64 = 1e-9;
+
+/// Represents a recorded `/check` request and its response.
+///
+/// # Fields
+///
+/// * `text` - The checked text.
+/// * `confusion_set` - The confusion set.
+/// * `canonical_words` - The canonical words of the directional confusion groups.
+/// * `window` - The number of words on each side of the examined word.
+/// * `skip_grams` - Whether the skip-bigrams were queried.
+/// * `sentence_boundaries` - Whether the sentence boundaries were queried.
+/// * `predictor` - The name of the predictor.
+/// * `power` - The power of the `power_sum` predictor.
+/// * `k` - The k of the `lidstone` predictor.
+/// * `log_base` - The base of the logarithm of the `max` and `sum` predictors.
+/// * `number_of_ngrams` - The number of n-grams.
+/// * `number_of_distinct_ngrams` - The number of distinct n-grams.
+/// * `counts` - The n-gram frequencies returned by Scylla, keyed by the n-gram.
+/// * `expected` - The recorded prediction results.
+///
+/// # Methods
+///
+/// * `replay` - Replays the request against the recorded frequencies.
+/// * `check` - Replays the request and compares it with the recorded results.
+#[derive(Deserialize, Serialize)]
+pub struct RecordedCase {
+    pub text: String,
+    pub confusion_set: Vec<Vec<String>>,
+    #[serde(default)]
+    pub canonical_words: HashSet<String>,
+    #[serde(default)]
+    pub window: Option<usize>,
+    #[serde(default)]
+    pub skip_grams: bool,
+    #[serde(default)]
+    pub sentence_boundaries: bool,
+    pub predictor: String,
+    #[serde(default)]
+    pub power: Option<f64>,
+    #[serde(default)]
+    pub k: Option<f64>,
+    #[serde(default)]
+    pub log_base: Option<f64>,
+    pub number_of_ngrams: HashMap<i32, i64>,
+    pub number_of_distinct_ngrams: HashMap<i32, i64>,
+    pub counts: HashMap<String, i32>,
+    pub expected: PredictionResults,
+}
+
+impl RecordedCase {
+    /// Replays the request against the recorded frequencies.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the prediction results if the case is valid, otherwise a `String` with the error message.
+    pub fn replay(&self) -> Result<PredictionResults, String> {
+        let predictor = get_predictor(&self.predictor, self.power, self.k, self.log_base)?;
+
+        let solver = SolverWithConfusionSet::new(self.text.clone(), &self.confusion_set)?
+            .with_canonical_words(self.canonical_words.clone())
+            .with_window(self.window.unwrap_or(DEFAULT_WINDOW))
+            .with_skip_grams(self.skip_grams)
+            .with_sentence_boundaries(self.sentence_boundaries);
+
+        let data = TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: replay_queries(solver.find_queries(), &self.counts, false),
+            sentences: solver.count_sentences(),
+            errors: vec![],
+        };
+
+        Ok(predict(
+            &*predictor,
+            data,
+            &self.confusion_set,
+            &self.number_of_ngrams,
+            &self.number_of_distinct_ngrams,
+        ))
+    }
+
+    /// Replays the request and compares it with the recorded results.
+    ///
+    /// The contexts are matched by the context and the examined word, the timing is ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the results match, otherwise a `String` describing the first difference.
+    pub fn check(&self) -> Result<(), String> {
+        let actual = self.replay()?;
+
+        if actual.sentences != self.expected.sentences {
+            return Err(format!(
+                "Expected {} sentences, got {}",
+                self.expected.sentences, actual.sentences
+            ));
+        }
+
+        if actual.results.len() != self.expected.results.len() {
+            return Err(format!(
+                "Expected {} contexts, got {}",
+                self.expected.results.len(),
+                actual.results.len()
+            ));
+        }
+
+        for expected in &self.expected.results {
+            let result = actual
+                .results
+                .iter()
+                .find(|result| {
+                    result.context == expected.context
+                        && result.word_examined == expected.word_examined
+                })
+                .ok_or(format!(
+                    "Missing context: {} ({})",
+                    expected.context, expected.word_examined
+                ))?;
+
+            compare_result(expected, result)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the result of a context with the recorded one.
+///
+/// # Arguments
+///
+/// * `expected` - The recorded result.
+/// * `actual` - The replayed result.
+///
+/// # Returns
+///
+/// A `Result` containing `()` if the results match, otherwise a `String` describing the difference.
+fn compare_result(expected: &PredictionResult, actual: &PredictionResult) -> Result<(), String> {
+    let context = format!("{} ({})", expected.context, expected.word_examined);
+
+    if expected.results.len() != actual.results.len() {
+        return Err(format!(
+            "{}: expected candidates {:?}, got {:?}",
+            context, expected.results, actual.results
+        ));
+    }
+
+    for (candidate, score) in &expected.results {
+        match actual.results.get(candidate) {
+            Some(actual_score) if (actual_score - score).abs() <= SCORE_TOLERANCE => {}
+            Some(actual_score) => {
+                return Err(format!(
+                    "{}: expected {} to score {}, got {}",
+                    context, candidate, score, actual_score
+                ))
+            }
+            None => return Err(format!("{}: missing candidate {}", context, candidate)),
+        }
+    }
+
+    if expected.unigram_only != actual.unigram_only {
+        return Err(format!(
+            "{}: expected unigram only {:?}, got {:?}",
+            context, expected.unigram_only, actual.unigram_only
+        ));
+    }
+
+    Ok(())
+}
+
+/// Collects the frequencies returned by Scylla, to record them with a case.
+///
+/// # Arguments
+///
+/// * `data` - The timed sentence results.
+///
+/// # Returns
+///
+/// The frequencies keyed by the n-gram, the zero-filled candidates are left out.
+pub fn recorded_counts(data: &TimedSentenceResults) -> HashMap<String, i32> {
+    data.results
+        .iter()
+        .flat_map(|result| result.results.iter())
+        .filter(|result| result.frequency > 0)
+        .map(|result| (result.input.clone(), result.frequency))
+        .collect()
+}
+
+/// Loads the recorded cases from a directory.
+///
+/// # Arguments
+///
+/// * `dir` - The directory, every `.json` file in it is a case.
+///
+/// # Returns
+///
+/// A `Result` containing the file names with the cases sorted by the name, otherwise a `String` with the error message.
+pub fn load_cases(dir: &Path) -> Result<Vec<(String, RecordedCase)>, String> {
+    let entries = fs::read_dir(dir).map_err(|_| format!("Can not read {}", dir.display()))?;
+
+    let mut cases = vec![];
+
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+
+        let name = path.display().to_string();
+        let content = fs::read_to_string(&path).map_err(|_| format!("Can not read {}", name))?;
+        let case = serde_json::from_str(&content)
+            .map_err(|err| format!("Invalid recorded case {}: {}", name, err))?;
+
+        cases.push((name, case));
+    }
+
+    cases.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(cases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases_dir() -> &'static Path {
+        Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/recorded_cases"
+        ))
+    }
+
+    #[test]
+    fn test_recorded_cases() {
+        let cases = load_cases(cases_dir()).unwrap();
+
+        assert!(!cases.is_empty());
+
+        for (name, case) in cases {
+            if let Err(err) = case.check() {
+                panic!("{}: {}", name, err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_detects_changed_score() {
+        let (_, mut case) = load_cases(cases_dir()).unwrap().remove(0);
+
+        let score = case.expected.results[0]
+            .results
+            .values_mut()
+            .next()
+            .unwrap();
+        *score += 0.5;
+
+        assert!(case.check().is_err());
+    }
+}
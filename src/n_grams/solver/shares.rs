@@ -0,0 +1,165 @@
+use crate::n_grams::solver::model::TimedSentenceResults;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Represents the shares of the candidates in one context.
+///
+/// # Fields
+///
+/// * `context` - The context.
+/// * `word_examined` - The word examined.
+/// * `shares` - The share of every candidate in the total context frequency of the candidates.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareResult {
+    pub context: String,
+    pub word_examined: String,
+    pub shares: HashMap<String, f64>,
+}
+
+/// Represents the shares of the candidates in every context.
+///
+/// # Fields
+///
+/// * `time_elapsed` - The time elapsed.
+/// * `time_elapsed_ms` - The time elapsed in milliseconds.
+/// * `results` - The results.
+/// * `sentences` - The number of analyzable sentences.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareResults {
+    pub time_elapsed: String,
+    #[serde(default)]
+    pub time_elapsed_ms: u128,
+    pub results: Vec<ShareResult>,
+    pub sentences: usize,
+}
+
+/// Computes the share of every candidate in the context frequencies of its confusion groups, or of
+/// its edit candidates for a word outside the confusion set.
+///
+/// The frequencies of the context n-grams of a candidate are summed and divided by the sum over
+/// all the candidates, without smoothing and without the unigram factor, so the shares of a
+/// context sum to 1. A context without any observed n-grams has no shares.
+///
+/// # Arguments
+///
+/// * `data` - The timed sentence results.
+/// * `confusion_set` - The confusion set.
+///
+/// # Returns
+///
+/// The share results, with one entry per context.
+pub fn shares(data: &TimedSentenceResults, confusion_set: &[Vec<String>]) -> ShareResults {
+    let mut results: Vec<ShareResult> = vec![];
+
+    for r in data.results.iter() {
+        let mut frequencies: HashMap<String, i64> = HashMap::new();
+
+        for group in confusion_set.iter().filter(|group| group.contains(&r.word)) {
+            for candidate in group {
+                frequencies.entry(candidate.clone()).or_insert(0);
+            }
+        }
+        for candidate in &r.edit_candidates {
+            frequencies.entry(candidate.clone()).or_insert(0);
+        }
+
+        for qr in r.results.iter().filter(|qr| qr.length > 1) {
+            for word in qr.input.split_whitespace() {
+                if let Some(frequency) = frequencies.get_mut(word) {
+                    *frequency += qr.frequency as i64;
+                }
+            }
+        }
+
+        let total: i64 = frequencies.values().sum();
+
+        let shares = match total {
+            0 => HashMap::new(),
+            _ => frequencies
+                .into_iter()
+                .map(|(candidate, frequency)| (candidate, frequency as f64 / total as f64))
+                .collect(),
+        };
+
+        results.push(ShareResult {
+            context: r.sentence.clone(),
+            word_examined: r.word.clone(),
+            shares,
+        });
+    }
+
+    ShareResults {
+        time_elapsed: data.time_taken.clone(),
+        time_elapsed_ms: data.time_taken_ms,
+        results,
+        sentences: data.sentences,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_grams::solver::model::{QueryResult, SentenceResult};
+
+    fn query_result(input: &str, frequency: i32) -> QueryResult {
+        QueryResult {
+            input: input.to_string(),
+            frequency,
+            length: input.split_whitespace().count() as i32,
+        }
+    }
+
+    #[test]
+    fn test_shares_sum_to_one() {
+        let data = TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "On od mene zahtjeva da".to_string(),
+                word: "zahtjeva".to_string(),
+                results: vec![
+                    query_result("zahtijeva", 100),
+                    query_result("zahtjeva", 50),
+                    query_result("mene zahtijeva", 30),
+                    query_result("mene zahtjeva", 10),
+                    query_result("od mene zahtijeva", 0),
+                    query_result("od mene zahtjeva", 0),
+                ],
+                rows_fetched: 4,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        };
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+
+        let result = shares(&data, &confusion_set);
+        let shares = &result.results[0].shares;
+
+        assert_eq!(shares["zahtijeva"], 0.75);
+        assert_eq!(shares["zahtjeva"], 0.25);
+        assert!((shares.values().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_shares_without_observations() {
+        let data = TimedSentenceResults {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            results: vec![SentenceResult {
+                sentence: "mene zahtjeva".to_string(),
+                word: "zahtjeva".to_string(),
+                results: vec![query_result("mene zahtjeva", 0)],
+                rows_fetched: 0,
+                edit_candidates: vec![],
+            }],
+            sentences: 1,
+            errors: vec![],
+        };
+        let confusion_set = vec![vec!["zahtijeva".to_string(), "zahtjeva".to_string()]];
+
+        assert!(shares(&data, &confusion_set).results[0].shares.is_empty());
+    }
+}
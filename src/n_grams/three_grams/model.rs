@@ -3,6 +3,7 @@ use crate::{
         GET_BY_FIRST_AND_SECOND_3, GET_BY_FIRST_AND_THIRD_3, GET_BY_SECOND_AND_THIRD_3, GET_FREQ_3,
     },
     n_grams::{Printable, Queryable},
+    sanitize_word,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -33,6 +34,8 @@ impl ThreeGramInput {
     ///
     /// * `query` - The query that contains the three-gram.
     ///
+    /// The words are sanitized, see `sanitize_word`.
+    ///
     /// # Returns
     ///
     /// A `Result` containing the `ThreeGramInput` if the query is valid, otherwise a `String` with the error message.
@@ -53,9 +56,9 @@ impl ThreeGramInput {
         };
 
         Ok(ThreeGramInput {
-            word1: word1.to_string(),
-            word2: word2.to_string(),
-            word3: word3.to_string(),
+            word1: sanitize_word(word1),
+            word2: sanitize_word(word2),
+            word3: sanitize_word(word3),
         })
     }
 }
@@ -154,7 +157,7 @@ mod tests {
 
         let three_gram = ThreeGramInput::from(&query);
 
-        assert_eq!(three_gram.is_err(), true);
+        assert!(three_gram.is_err());
     }
 
     #[test]
@@ -241,7 +244,7 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -250,7 +253,7 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -259,6 +262,6 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 }
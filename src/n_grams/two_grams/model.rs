@@ -1,6 +1,7 @@
 use crate::{
     db::{GET_BY_FIRST_2, GET_BY_SECOND_2, GET_FREQ_2},
     n_grams::{Printable, Queryable},
+    sanitize_word,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -29,6 +30,8 @@ impl TwoGramInput {
     ///
     /// * `query` - The query that contains the two-gram.
     ///
+    /// The words are sanitized, see `sanitize_word`.
+    ///
     /// # Returns
     ///
     /// A `Result` containing the `TwoGramInput` if the query is valid, otherwise a `String` with the error message.
@@ -44,8 +47,8 @@ impl TwoGramInput {
         };
 
         Ok(TwoGramInput {
-            word1: word1.to_string(),
-            word2: word2.to_string(),
+            word1: sanitize_word(word1),
+            word2: sanitize_word(word2),
         })
     }
 }
@@ -131,6 +134,18 @@ mod tests {
         assert_eq!(result.word1, "word1");
     }
 
+    #[test]
+    fn test_two_gram_input_from_sanitizes_words() {
+        let mut query = HashMap::new();
+
+        query.insert("word1".to_string(), "on".to_string());
+        query.insert("word2".to_string(), "zahtj\u{200D}eva".to_string());
+
+        let result = TwoGramInput::from(&query).unwrap();
+
+        assert_eq!(result.word2, "zahtjeva");
+    }
+
     #[test]
     fn test_two_gram_input_from_missing_word1() {
         let mut query = HashMap::new();
@@ -139,7 +154,7 @@ mod tests {
 
         let result = TwoGramInput::from(&query);
 
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -220,7 +235,7 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -229,7 +244,7 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -238,6 +253,6 @@ mod tests {
 
         let result = validate(&indexes);
 
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 }
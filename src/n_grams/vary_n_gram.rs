@@ -1,15 +1,15 @@
 use crate::{
-    db::{QueryError, QueryFactory},
-    n_grams::{word_freq_pair::WordFreqPair, Printable, Queryable},
+    csv_row,
+    db::NgramStore,
+    n_grams::{
+        normalizer::{normalize_all, Normalizer},
+        word_freq_pair::WordFreqPair,
+        Printable, Queryable,
+    },
+    SortOrder,
 };
-use futures::stream::StreamExt;
-use scylla::{statement::Consistency, Session};
 use serde::{Deserialize, Serialize};
-use std::{
-    sync::{mpsc, Arc},
-    thread,
-};
-use tokio;
+use std::{collections::HashMap, sync::Arc};
 
 /// Represents a varying n-gram.
 ///
@@ -18,16 +18,22 @@ use tokio;
 /// * `index` - The index of the word.
 /// * `word` - The word.
 /// * `solutions` - The solutions of the word.
+/// * `total_solutions` - The number of solutions before the options are applied, e.g. before the
+///   amount truncates them.
 ///
 /// # Methods
 ///
 /// * `new` - Creates a new `VaryingNGram`.
 /// * `find_freq` - Finds the frequency of the word in the given vector of `VaryingNGram`.
+/// * `find_rank` - Finds the rank of the word among the solutions.
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct VaryingNGram {
     pub index: i32,
     pub word: String,
     pub solutions: Vec<WordFreqPair>,
+    #[serde(default)]
+    pub total_solutions: usize,
 }
 
 impl VaryingNGram {
@@ -46,6 +52,7 @@ impl VaryingNGram {
         VaryingNGram {
             index: *index,
             word,
+            total_solutions: solutions.len(),
             solutions,
         }
     }
@@ -60,38 +67,129 @@ impl VaryingNGram {
     /// # Returns
     ///
     /// A `Result` containing the frequency of the word if the word is found, otherwise a `String` with the error message.
-    fn find_freq(vary: &VaryingNGram, word: &String) -> Result<i32, String> {
+    fn find_freq(vary: &VaryingNGram, word: &str) -> Result<i32, String> {
         let pair = WordFreqPair::find(&vary.solutions, word);
         match pair {
-            Some(pair) => return Ok(pair.frequency),
-            None => return Err("No pair found".to_string()),
+            Some(pair) => Ok(pair.frequency),
+            None => Err("No pair found".to_string()),
         }
     }
+
+    /// Finds the rank of the word among the solutions.
+    ///
+    /// # Arguments
+    ///
+    /// * `vary` - The varying n-gram, its solutions are sorted by the frequency, descending.
+    /// * `word` - The word.
+    ///
+    /// # Returns
+    ///
+    /// The 1-based position of the word in the solutions, `None` if the word is not among them.
+    fn find_rank(vary: &VaryingNGram, word: &str) -> Option<i32> {
+        vary.solutions
+            .iter()
+            .position(|pair| pair.word == word)
+            .map(|position| position as i32 + 1)
+    }
+}
+
+/// Represents the options of the varying query.
+///
+/// # Fields
+///
+/// * `amount` - The amount of word freq pairs to return, negative, such as `ALL_WORD_FREQ_PAIRS`, returns all.
+/// * `offset` - The number of word freq pairs to skip before the amount is applied.
+/// * `min_freq` - The minimum frequency of the returned word freq pairs.
+/// * `order` - The order of the returned word freq pairs by the frequency.
+///
+/// # Methods
+///
+/// * `apply` - Applies the options to the solutions of a varying n-gram.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VaryingOptions {
+    pub amount: i32,
+    pub offset: usize,
+    pub min_freq: i32,
+    pub order: SortOrder,
+}
+
+impl VaryingOptions {
+    /// Applies the options to the solutions of a varying n-gram.
+    ///
+    /// # Arguments
+    ///
+    /// * `varying` - The varying n-gram, its solutions are sorted by the frequency, descending.
+    pub fn apply(&self, varying: &mut VaryingNGram) {
+        varying
+            .solutions
+            .retain(|pair| pair.frequency >= self.min_freq);
+        if self.order == SortOrder::Asc {
+            varying.solutions.sort_by_key(|pair| pair.frequency);
+        }
+        let offset = self.offset.min(varying.solutions.len());
+        varying.solutions.drain(..offset);
+        if self.amount >= 0 {
+            varying.solutions.truncate(self.amount as usize);
+        }
+    }
+}
+
+/// Represents the error of a varying index.
+///
+/// # Fields
+///
+/// * `index` - The varying index.
+/// * `error` - The error message.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct IndexError {
+    pub index: i32,
+    pub error: String,
 }
 
 /// Represents the varying query result.
 ///
 /// # Fields
 ///
-/// * `time_taken` - The time taken to execute the query.
+/// * `time_taken` - The time taken to execute the query, formatted as `"123 ms"`.
+/// * `time_taken_ms` - The time taken to execute the query in milliseconds.
 /// * `n_gram_length` - The length of the n-gram.
 /// * `provided_n_gram` - The provided n-gram.
 /// * `provided_n_gram_frequency` - The frequency of the provided n-gram.
-/// * `varying_indexes` - The varying indexes.
-/// * `vary` - The varying n-grams.
+/// * `exists` - Whether the provided n-gram is in the corpus, a zero frequency with `false` means
+///   it was not found. With varying indexes, whether the provided word is among the rows read for
+///   the first of them.
+/// * `provided_n_gram_rank` - The 1-based rank of the provided word among the candidates of the
+///   first varying index, by the frequency before the options are applied. `None` if the word
+///   is not among them. If the first varying index failed, the frequency, `exists` and the rank
+///   are left unset and its error is in `errors`.
+/// * `varying_indexes` - The varying indexes, in the requested order.
+/// * `vary` - The varying n-grams, in the order of `varying_indexes`.
+/// * `corpus_total` - The total number of n-grams of the same order, for client-side normalization.
+/// * `errors` - The errors of the varying indexes that failed, their n-grams are left out of `vary`.
 ///
 /// # Methods
 ///
 /// * `get_one` - Gets the query result with one n-gram.
 /// * `get_varying` - Gets the query result with varying n-grams.
+/// * `with_corpus_total` - Sets the corpus total.
+/// * `to_csv` - Formats the solutions as CSV.
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct VaryingQueryResult {
     pub time_taken: String,
+    #[serde(default)]
+    pub time_taken_ms: u128,
     pub n_gram_length: i32,
     pub provided_n_gram: String,
     pub provided_n_gram_frequency: i32,
+    #[serde(default)]
+    pub exists: bool,
+    pub provided_n_gram_rank: Option<i32>,
     pub varying_indexes: Vec<i32>,
     pub vary: Vec<VaryingNGram>,
+    pub corpus_total: Option<i64>,
+    #[serde(default)]
+    pub errors: Vec<IndexError>,
 }
 
 impl VaryingQueryResult {
@@ -99,8 +197,9 @@ impl VaryingQueryResult {
     ///
     /// # Arguments
     ///
-    /// * `session` - The ScyllaDB session.
+    /// * `store` - The n-gram store.
     /// * `input` - Generic input that implements `Queryable`.
+    /// * `normalizer` - Normalizes the words before querying.
     ///
     /// # Returns
     ///
@@ -109,80 +208,62 @@ impl VaryingQueryResult {
     /// # Errors
     ///
     /// If the query can not be executed, a `String` with the error message will be returned.
-    pub async fn get_one<T>(session: Arc<Session>, input: T) -> Result<Self, String>
+    pub async fn get_one<T>(
+        store: &dyn NgramStore,
+        input: T,
+        normalizer: &dyn Normalizer,
+    ) -> Result<Self, String>
     where
         T: Queryable + Printable + Clone + Send + Sync + 'static,
     {
-        let query = match input.get_query(None) {
-            Ok(query) => query,
-            Err(err) => return Err(err.to_string()),
-        };
-        let consistency = Consistency::One;
-
+        let query = input.get_query(None)?;
         let start_time = std::time::Instant::now();
 
-        let s = Arc::clone(&session);
-
-        let query = match QueryFactory::build(s, query, consistency).await {
-            Ok(query) => query,
-            Err(err) => return Err(err.to_string()),
-        };
-
-        let s = Arc::clone(&session);
-
-        let mut row_stream = match query.execute_one(s, input.to_vec()).await {
-            Ok(rows) => rows.into_typed::<(i32,)>(),
-            Err(err) => match err {
-                QueryError::ScyllaError => return Err("Can not execute query".to_string()),
-                QueryError::NotFound => {
-                    let end_time = format!("{} ms", start_time.elapsed().as_millis());
-                    return Ok(VaryingQueryResult {
-                        time_taken: end_time,
-                        n_gram_length: input.print().split_whitespace().count() as i32,
-                        provided_n_gram: input.print(),
-                        provided_n_gram_frequency: 0,
-                        varying_indexes: vec![],
-                        vary: vec![],
-                    });
-                }
-            },
-        };
-
-        let mut provided_n_gram_frequency = 0;
-
-        if let Some(row) = row_stream.next().await {
-            let (freq,) = row.unwrap();
-            provided_n_gram_frequency = freq;
-        }
+        let frequency = store
+            .frequency(query, normalize_all(normalizer, &input.to_vec()))
+            .await?;
 
-        let end_time = format!("{} ms", start_time.elapsed().as_millis());
-        return Ok(VaryingQueryResult {
-            time_taken: end_time,
+        let elapsed = start_time.elapsed().as_millis();
+        Ok(VaryingQueryResult {
+            time_taken: format!("{} ms", elapsed),
+            time_taken_ms: elapsed,
             n_gram_length: input.print().split_whitespace().count() as i32,
             provided_n_gram: input.print(),
-            provided_n_gram_frequency,
+            provided_n_gram_frequency: frequency.unwrap_or(0),
+            exists: frequency.is_some(),
+            provided_n_gram_rank: None,
             varying_indexes: vec![],
             vary: vec![],
-        });
+            corpus_total: None,
+            errors: vec![],
+        })
     }
 
     /// Gets the query result with varying n-grams.
     ///
     /// # Arguments
     ///
-    /// * `session` - The ScyllaDB session.
+    /// * `store` - The n-gram store.
     /// * `input` - Generic input that implements `Queryable`.
     /// * `varying_indexed` - The varying indexes.
-    /// * `amount` - The amount of word freq pairs to return.
+    /// * `options` - The amount and the minimum frequency of the word freq pairs to return.
+    /// * `normalizer` - Normalizes the words before querying.
+    /// * `strict` - Whether a failed varying index fails the whole query, otherwise its error is returned with the other indexes.
     ///
     /// # Returns
     ///
     /// A `Result` containing the `VaryingQueryResult` if the query is successful, otherwise a `String` with the error message.
+    ///
+    /// # Errors
+    ///
+    /// If every varying index failed, or any of them with `strict`, the first error will be returned.
     pub async fn get_varying<T>(
-        session: Arc<Session>,
+        store: Arc<dyn NgramStore>,
         input: T,
         varying_indexed: Vec<i32>,
-        amount: i32,
+        options: VaryingOptions,
+        normalizer: Arc<dyn Normalizer>,
+        strict: bool,
     ) -> Result<VaryingQueryResult, String>
     where
         T: Queryable + Printable + Clone + Send + Sync + 'static,
@@ -191,120 +272,214 @@ impl VaryingQueryResult {
         let vary_indexes_copy = varying_indexed.clone();
 
         let start_time = std::time::Instant::now();
-        let (tx, rx) = mpsc::channel();
 
         let mut handlers = vec![];
 
         for index in &varying_indexed {
-            let s = Arc::clone(&session);
+            let store = Arc::clone(&store);
             let index = *index;
             let i = input.clone();
-            let tx_clone = tx.clone();
-
-            // not the best approach
-            // should use tokio::spawn
-            let handle = thread::spawn(move || {
-                let rt = tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap();
-                rt.block_on(process(s, &i, index, tx_clone)).unwrap();
-            });
-            handlers.push(handle);
-        }
+            let normalizer = Arc::clone(&normalizer);
 
-        for handle in handlers {
-            handle.join().unwrap();
+            let handle =
+                tokio::spawn(async move { process(&*store, &i, index, &*normalizer).await });
+            handlers.push(handle);
         }
 
-        drop(tx);
-
-        let mut i = 0;
         let mut provided_n_gram_frequency = 0;
+        let mut exists = false;
+        let mut provided_n_gram_rank = None;
+        let mut errors: Vec<IndexError> = vec![];
+
+        for (position, (index, handle)) in varying_indexed.iter().zip(handlers).enumerate() {
+            let received = match handle.await {
+                Ok(received) => received,
+                Err(_) => Err("Can not execute query".to_string()),
+            };
 
-        for received in rx {
             match received {
                 Ok(mut varying) => {
-                    if i == 0 {
+                    // only the first requested index describes the provided n-gram
+                    if position == 0 {
                         let word = varying.word.clone();
                         if let Ok(freq) = VaryingNGram::find_freq(&varying, &word) {
                             provided_n_gram_frequency = freq;
+                            exists = true;
                         }
-                        i += 1;
-                    }
-                    if amount >= 0 {
-                        varying.solutions.truncate(amount as usize);
+                        provided_n_gram_rank = VaryingNGram::find_rank(&varying, &word);
                     }
+                    options.apply(&mut varying);
                     vary.push(varying);
                 }
-                Err(err) => return Err(err),
+                Err(err) if strict => return Err(err),
+                Err(error) => errors.push(IndexError {
+                    index: *index,
+                    error,
+                }),
             }
         }
 
-        let end_time = format!("{} ms", start_time.elapsed().as_millis());
+        if vary.is_empty() && !errors.is_empty() {
+            return Err(errors.remove(0).error);
+        }
+
+        // the handles are awaited in the requested order, the sort keeps the order explicit
+        vary.sort_by_key(|varying| {
+            vary_indexes_copy
+                .iter()
+                .position(|index| *index == varying.index)
+        });
+
+        let elapsed = start_time.elapsed().as_millis();
         Ok(VaryingQueryResult {
-            time_taken: end_time,
+            time_taken: format!("{} ms", elapsed),
+            time_taken_ms: elapsed,
             n_gram_length: input.print().split_whitespace().count() as i32,
             provided_n_gram: input.print(),
             provided_n_gram_frequency,
+            exists,
+            provided_n_gram_rank,
             varying_indexes: vary_indexes_copy,
             vary,
+            corpus_total: None,
+            errors,
         })
     }
+
+    /// Sets the corpus total for the order of the n-gram.
+    ///
+    /// # Arguments
+    ///
+    /// * `number_of_ngrams` - The number of n-grams.
+    ///
+    /// # Returns
+    ///
+    /// The `VaryingQueryResult` with the corpus total set, `None` if the order is not counted.
+    pub fn with_corpus_total(mut self, number_of_ngrams: &HashMap<i32, i64>) -> Self {
+        self.corpus_total = number_of_ngrams.get(&self.n_gram_length).copied();
+        self
+    }
+
+    /// Formats the solutions as CSV.
+    ///
+    /// # Returns
+    ///
+    /// The `index,word,frequency` header and a row per solution of every varying index.
+    /// Without varying indexes the provided n-gram is the only row, with an empty index.
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_row(&["index", "word", "frequency"]);
+
+        if self.varying_indexes.is_empty() {
+            csv.push_str(&csv_row(&[
+                "",
+                &self.provided_n_gram,
+                &self.provided_n_gram_frequency.to_string(),
+            ]));
+        }
+
+        for varying in &self.vary {
+            let index = varying.index.to_string();
+            for pair in &varying.solutions {
+                csv.push_str(&csv_row(&[&index, &pair.word, &pair.frequency.to_string()]));
+            }
+        }
+
+        csv
+    }
 }
 
 /// Processes the query.
 ///
 /// # Arguments
 ///
-/// * `session` - The ScyllaDB session.
+/// * `store` - The n-gram store.
 /// * `input` - Generic input that implements `Queryable`.
 /// * `index` - The index of the word.
-/// * `tx` - The sender.
+/// * `normalizer` - Normalizes the words before querying.
 ///
 /// # Returns
 ///
-/// A `Result` containing unit if the query is successful, otherwise a `String` with the error message.
+/// A `Result` containing the `VaryingNGram` if the query is successful, otherwise a `String` with the error message.
 ///
 /// # Errors
 ///
 /// If the query can not be executed, a `String` with the error message will be returned.
 async fn process<T>(
-    session: Arc<Session>,
+    store: &dyn NgramStore,
     input: &T,
     index: i32,
-    tx: mpsc::Sender<Result<VaryingNGram, String>>,
-) -> Result<(), std::io::Error>
+    normalizer: &dyn Normalizer,
+) -> Result<VaryingNGram, String>
 where
     T: Queryable + Printable + Clone + Send + Sync + 'static,
 {
-    let s = Arc::clone(&session);
-    let solutions = WordFreqPair::from(s, &index, input).await;
-    let solutions = match solutions {
-        Ok(solutions) => solutions,
-        Err(err) => {
-            tx.send(Err(err)).unwrap();
-            return Ok(());
-        }
-    };
+    let solutions = WordFreqPair::from(store, &index, input, normalizer).await?;
 
-    let word = match input.get_word(index) {
-        Ok(word) => word,
-        Err(err) => {
-            tx.send(Err(err)).unwrap();
-            return Ok(());
-        }
-    };
+    let word = input.get_word(index)?;
 
-    let varying = VaryingNGram::new(&index, word.to_string(), solutions);
-    tx.send(Ok(varying)).unwrap();
-
-    Ok(())
+    Ok(VaryingNGram::new(
+        &index,
+        normalizer.normalize(word),
+        solutions,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        db::{InMemoryStore, VaryingQuery},
+        n_grams::{
+            normalizer::IdentityNormalizer, three_grams::model::ThreeGramInput,
+            two_grams::model::TwoGramInput,
+        },
+        parse_amount,
+    };
+    use futures::future::BoxFuture;
+
+    #[test]
+    fn test_to_csv() {
+        let result = VaryingQueryResult {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            n_gram_length: 2,
+            provided_n_gram: "od mene".to_string(),
+            provided_n_gram_frequency: 7,
+            exists: true,
+            provided_n_gram_rank: Some(2),
+            varying_indexes: vec![1, 2],
+            vary: vec![
+                VaryingNGram::new(
+                    &1,
+                    "od".to_string(),
+                    vec![
+                        WordFreqPair::new("za".to_string(), 12),
+                        WordFreqPair::new("od".to_string(), 7),
+                    ],
+                ),
+                VaryingNGram::new(
+                    &2,
+                    "mene".to_string(),
+                    vec![WordFreqPair::new("mene".to_string(), 7)],
+                ),
+            ],
+            corpus_total: None,
+            errors: vec![],
+        };
+
+        assert_eq!(
+            result.to_csv(),
+            "index,word,frequency\n1,za,12\n1,od,7\n2,mene,7\n"
+        );
+
+        let one = VaryingQueryResult {
+            varying_indexes: vec![],
+            vary: vec![],
+            ..result
+        };
+
+        assert_eq!(one.to_csv(), "index,word,frequency\n,od mene,7\n");
+    }
 
     #[test]
     fn test_find_freq() {
@@ -315,11 +490,168 @@ mod tests {
                 WordFreqPair::new("hello".to_string(), 1),
                 WordFreqPair::new("world".to_string(), 2),
             ],
+            total_solutions: 2,
         };
         let word = "hello".to_string();
         assert_eq!(VaryingNGram::find_freq(&vary, &word), Ok(1));
     }
 
+    #[test]
+    fn test_varying_options_apply() {
+        let mut vary = VaryingNGram {
+            index: 1,
+            word: "hello".to_string(),
+            solutions: vec![
+                WordFreqPair::new("world".to_string(), 10),
+                WordFreqPair::new("hello".to_string(), 5),
+                WordFreqPair::new("there".to_string(), 1),
+            ],
+            total_solutions: 3,
+        };
+        let options = VaryingOptions {
+            amount: 50,
+            offset: 0,
+            min_freq: 2,
+            order: SortOrder::Desc,
+        };
+
+        options.apply(&mut vary);
+
+        let words: Vec<&str> = vary.solutions.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, vec!["world", "hello"]);
+    }
+
+    #[test]
+    fn test_varying_options_apply_all() {
+        let mut vary = VaryingNGram {
+            index: 1,
+            word: "hello".to_string(),
+            solutions: vec![
+                WordFreqPair::new("world".to_string(), 10),
+                WordFreqPair::new("hello".to_string(), 5),
+                WordFreqPair::new("there".to_string(), 1),
+            ],
+            total_solutions: 3,
+        };
+        let options = VaryingOptions {
+            amount: parse_amount("all").unwrap(),
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        options.apply(&mut vary);
+
+        assert_eq!(vary.solutions.len(), 3);
+    }
+
+    #[test]
+    fn test_varying_options_apply_offset() {
+        let vary = || VaryingNGram {
+            index: 1,
+            word: "hello".to_string(),
+            solutions: vec![
+                WordFreqPair::new("world".to_string(), 10),
+                WordFreqPair::new("hello".to_string(), 5),
+                WordFreqPair::new("there".to_string(), 1),
+            ],
+            total_solutions: 3,
+        };
+        let options = |offset: usize| VaryingOptions {
+            amount: 1,
+            offset,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        let mut second_page = vary();
+        options(1).apply(&mut second_page);
+
+        assert_eq!(second_page.solutions.len(), 1);
+        assert_eq!(second_page.solutions[0].word, "hello");
+
+        let mut past_the_end = vary();
+        options(5).apply(&mut past_the_end);
+
+        assert!(past_the_end.solutions.is_empty());
+    }
+
+    #[test]
+    fn test_varying_options_apply_order() {
+        let vary = || VaryingNGram {
+            index: 1,
+            word: "hello".to_string(),
+            solutions: vec![
+                WordFreqPair::new("world".to_string(), 10),
+                WordFreqPair::new("hello".to_string(), 5),
+                WordFreqPair::new("there".to_string(), 1),
+            ],
+            total_solutions: 3,
+        };
+        let words = |vary: &VaryingNGram| -> Vec<String> {
+            vary.solutions.iter().map(|p| p.word.clone()).collect()
+        };
+
+        let mut desc = vary();
+        VaryingOptions {
+            amount: 2,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        }
+        .apply(&mut desc);
+
+        let mut asc = vary();
+        VaryingOptions {
+            amount: 2,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Asc,
+        }
+        .apply(&mut asc);
+
+        assert_eq!(words(&desc), vec!["world", "hello"]);
+        assert_eq!(words(&asc), vec!["there", "hello"]);
+    }
+
+    #[test]
+    fn test_with_corpus_total() {
+        let result = VaryingQueryResult {
+            time_taken: "0 ms".to_string(),
+            time_taken_ms: 0,
+            n_gram_length: 3,
+            provided_n_gram: "ja sam gledao".to_string(),
+            provided_n_gram_frequency: 10,
+            exists: true,
+            provided_n_gram_rank: None,
+            varying_indexes: vec![],
+            vary: vec![],
+            corpus_total: None,
+            errors: vec![],
+        };
+        let number_of_ngrams = HashMap::from([(1, 1000), (2, 800), (3, 600)]);
+
+        let result = result.with_corpus_total(&number_of_ngrams);
+
+        assert_eq!(result.corpus_total, Some(600));
+    }
+
+    #[test]
+    fn test_find_rank() {
+        let vary = VaryingNGram {
+            index: 3,
+            word: "vidio".to_string(),
+            solutions: vec![
+                WordFreqPair::new("gledao".to_string(), 120),
+                WordFreqPair::new("vidio".to_string(), 90),
+            ],
+            total_solutions: 2,
+        };
+
+        assert_eq!(VaryingNGram::find_rank(&vary, "vidio"), Some(2));
+        assert_eq!(VaryingNGram::find_rank(&vary, "čuo"), None);
+    }
+
     #[test]
     fn test_find_freq_fail() {
         let vary = VaryingNGram {
@@ -329,6 +661,7 @@ mod tests {
                 WordFreqPair::new("hello".to_string(), 1),
                 WordFreqPair::new("world".to_string(), 2),
             ],
+            total_solutions: 2,
         };
         let word = "test".to_string();
         assert_eq!(
@@ -336,4 +669,300 @@ mod tests {
             Err("No pair found".to_string())
         );
     }
+
+    #[tokio::test]
+    async fn test_get_varying() {
+        let store: Arc<dyn NgramStore> = Arc::new(InMemoryStore::new(HashMap::from([
+            ("ja sam gledao".to_string(), 120),
+            ("ti sam gledao".to_string(), 7),
+            ("on sam gledao".to_string(), 3),
+            ("ja sam vidio".to_string(), 90),
+        ])));
+
+        let query_map = HashMap::from([
+            ("word1".to_string(), "ja".to_string()),
+            ("word2".to_string(), "sam".to_string()),
+            ("word3".to_string(), "gledao".to_string()),
+        ]);
+        let input = ThreeGramInput::from(&query_map).unwrap();
+
+        let options = VaryingOptions {
+            amount: 2,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        let result = VaryingQueryResult::get_varying(
+            store,
+            input,
+            vec![1, 3],
+            options,
+            Arc::new(IdentityNormalizer),
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.provided_n_gram_frequency, 120);
+        assert!(result.exists);
+        assert_eq!(result.provided_n_gram_rank, Some(1));
+        assert_eq!(result.varying_indexes, vec![1, 3]);
+
+        let words = |vary: &VaryingNGram| -> Vec<(String, i32)> {
+            vary.solutions
+                .iter()
+                .map(|pair| (pair.word.clone(), pair.frequency))
+                .collect()
+        };
+
+        assert_eq!(
+            words(&result.vary[0]),
+            vec![("ja".to_string(), 120), ("ti".to_string(), 7)]
+        );
+        assert_eq!(
+            words(&result.vary[1]),
+            vec![("gledao".to_string(), 120), ("vidio".to_string(), 90)]
+        );
+        // the amount truncated the solutions of the first index, not their total
+        assert_eq!(result.vary[0].total_solutions, 3);
+        assert_eq!(result.vary[1].total_solutions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_one_exists() {
+        let store = InMemoryStore::new(HashMap::from([
+            ("od mene".to_string(), 7),
+            ("za mene".to_string(), 0),
+        ]));
+
+        let lookup = |word1: &str| {
+            TwoGramInput::from(&HashMap::from([
+                ("word1".to_string(), word1.to_string()),
+                ("word2".to_string(), "mene".to_string()),
+            ]))
+            .unwrap()
+        };
+
+        let found = VaryingQueryResult::get_one(&store, lookup("od"), &IdentityNormalizer)
+            .await
+            .unwrap();
+        let zero = VaryingQueryResult::get_one(&store, lookup("za"), &IdentityNormalizer)
+            .await
+            .unwrap();
+        let missing = VaryingQueryResult::get_one(&store, lookup("do"), &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        assert_eq!((found.provided_n_gram_frequency, found.exists), (7, true));
+        assert_eq!((zero.provided_n_gram_frequency, zero.exists), (0, true));
+        assert_eq!(
+            (missing.provided_n_gram_frequency, missing.exists),
+            (0, false)
+        );
+
+        let three_gram = ThreeGramInput::from(&HashMap::from([
+            ("word1".to_string(), "od".to_string()),
+            ("word2".to_string(), "mene".to_string()),
+            ("word3".to_string(), "traži".to_string()),
+        ]))
+        .unwrap();
+        let missing = VaryingQueryResult::get_one(&store, three_gram, &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        assert!(!missing.exists);
+        assert_eq!(
+            serde_json::to_value(&missing).unwrap()["exists"],
+            serde_json::json!(false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_varying_keeps_requested_order() {
+        let store: Arc<dyn NgramStore> = Arc::new(InMemoryStore::new(HashMap::from([
+            ("ja sam gledao".to_string(), 120),
+            ("ti sam gledao".to_string(), 7),
+            ("ja sam vidio".to_string(), 90),
+        ])));
+
+        let query_map = HashMap::from([
+            ("word1".to_string(), "ja".to_string()),
+            ("word2".to_string(), "sam".to_string()),
+            ("word3".to_string(), "gledao".to_string()),
+        ]);
+        let input = ThreeGramInput::from(&query_map).unwrap();
+
+        let options = VaryingOptions {
+            amount: 50,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        let result = VaryingQueryResult::get_varying(
+            store,
+            input,
+            vec![3, 1],
+            options,
+            Arc::new(IdentityNormalizer),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let indexes: Vec<i32> = result.vary.iter().map(|vary| vary.index).collect();
+
+        assert_eq!(result.varying_indexes, vec![3, 1]);
+        assert_eq!(indexes, vec![3, 1]);
+        assert_eq!(result.vary[0].word, "gledao");
+        assert_eq!(result.vary[1].word, "ja");
+    }
+
+    /// Fails the queries of one varying index.
+    struct FailingStore {
+        store: InMemoryStore,
+        index: usize,
+    }
+
+    impl NgramStore for FailingStore {
+        fn frequency<'a>(
+            &'a self,
+            query: &'a str,
+            n_gram: Vec<String>,
+        ) -> BoxFuture<'a, Result<Option<i32>, String>> {
+            self.store.frequency(query, n_gram)
+        }
+
+        fn varying<'a>(
+            &'a self,
+            query: &'a VaryingQuery,
+        ) -> BoxFuture<'a, Result<Vec<(String, i32)>, String>> {
+            if query.index == self.index {
+                return Box::pin(async { Err("Can not execute query".to_string()) });
+            }
+            self.store.varying(query)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_varying_returns_partial_results() {
+        let store: Arc<dyn NgramStore> = Arc::new(FailingStore {
+            store: InMemoryStore::new(HashMap::from([
+                ("ja sam gledao".to_string(), 120),
+                ("ti sam gledao".to_string(), 7),
+                ("ja je gledao".to_string(), 40),
+                ("ja sam vidio".to_string(), 90),
+            ])),
+            index: 1,
+        });
+
+        let query_map = HashMap::from([
+            ("word1".to_string(), "ja".to_string()),
+            ("word2".to_string(), "sam".to_string()),
+            ("word3".to_string(), "gledao".to_string()),
+        ]);
+        let input = ThreeGramInput::from(&query_map).unwrap();
+
+        let options = VaryingOptions {
+            amount: 50,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        let result = VaryingQueryResult::get_varying(
+            Arc::clone(&store),
+            input.clone(),
+            vec![1, 2, 3],
+            options.clone(),
+            Arc::new(IdentityNormalizer),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let indexes: Vec<i32> = result.vary.iter().map(|vary| vary.index).collect();
+
+        assert_eq!(indexes, vec![1, 3]);
+        assert_eq!(result.provided_n_gram_frequency, 120);
+        assert_eq!(
+            result.errors,
+            vec![IndexError {
+                index: 2,
+                error: "Can not execute query".to_string(),
+            }]
+        );
+
+        let strict = VaryingQueryResult::get_varying(
+            Arc::clone(&store),
+            input.clone(),
+            vec![1, 2, 3],
+            options.clone(),
+            Arc::new(IdentityNormalizer),
+            true,
+        )
+        .await;
+
+        assert_eq!(strict.err(), Some("Can not execute query".to_string()));
+
+        let all_failed = VaryingQueryResult::get_varying(
+            store,
+            input,
+            vec![2],
+            options,
+            Arc::new(IdentityNormalizer),
+            false,
+        )
+        .await;
+
+        assert!(all_failed.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_varying_first_index_failed() {
+        let store: Arc<dyn NgramStore> = Arc::new(FailingStore {
+            store: InMemoryStore::new(HashMap::from([
+                ("ja sam gledao".to_string(), 120),
+                ("ti sam gledao".to_string(), 7),
+                ("ja sam vidio".to_string(), 90),
+            ])),
+            // the store indexes the words from zero, this fails the varying index 1
+            index: 0,
+        });
+
+        let query_map = HashMap::from([
+            ("word1".to_string(), "ja".to_string()),
+            ("word2".to_string(), "sam".to_string()),
+            ("word3".to_string(), "gledao".to_string()),
+        ]);
+        let input = ThreeGramInput::from(&query_map).unwrap();
+
+        let options = VaryingOptions {
+            amount: 50,
+            offset: 0,
+            min_freq: 0,
+            order: SortOrder::Desc,
+        };
+
+        let result = VaryingQueryResult::get_varying(
+            store,
+            input,
+            vec![1, 3],
+            options,
+            Arc::new(IdentityNormalizer),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let indexes: Vec<i32> = result.vary.iter().map(|vary| vary.index).collect();
+
+        assert_eq!(indexes, vec![3]);
+        // the third index found the n-gram, but only the first one describes it
+        assert_eq!(result.provided_n_gram_frequency, 0);
+        assert!(!result.exists);
+        assert_eq!(result.provided_n_gram_rank, None);
+        assert_eq!(result.errors[0].index, 1);
+    }
 }
@@ -1,15 +1,113 @@
 use crate::{
-    db::{QueryError, QueryFactory},
-    n_grams::Queryable,
+    db::{NgramStore, VaryingQuery},
+    n_grams::{
+        normalizer::{normalize_all, Normalizer},
+        Queryable,
+    },
 };
-use futures::stream::StreamExt;
-use scylla::{statement::Consistency, Session};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::HashMap, sync::OnceLock};
 
 /// The default amount of word frequency pairs to return.
 pub static DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS: i32 = 50;
 
+/// The amount that returns all the word frequency pairs, parsed from `amount=all`.
+pub static ALL_WORD_FREQ_PAIRS: i32 = -1;
+
+/// The number of rows fetched per page.
+pub static PAGE_SIZE: i32 = 5000;
+
+/// The default upper bound of the rows read for one varying index.
+pub static DEFAULT_MAX_ROWS_SCANNED: usize = 100_000;
+
+/// The upper bound of the rows read for one varying index, read once from `MAX_ROWS_SCANNED`.
+static MAX_ROWS_SCANNED: OnceLock<usize> = OnceLock::new();
+
+/// Gets the upper bound of the rows read for one varying index.
+///
+/// # Returns
+///
+/// The value of `MAX_ROWS_SCANNED`, or `DEFAULT_MAX_ROWS_SCANNED` if it is not set.
+pub fn max_rows_scanned() -> usize {
+    *MAX_ROWS_SCANNED.get_or_init(|| {
+        std::env::var("MAX_ROWS_SCANNED")
+            .ok()
+            .and_then(|max| max.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_ROWS_SCANNED)
+    })
+}
+
+/// Represents how the duplicate rows of the same word are merged.
+///
+/// The tables should hold one row per word, duplicates are a data quality issue.
+///
+/// # Variants
+///
+/// * `Sum` - The frequencies of the duplicates are added.
+/// * `Max` - The highest frequency of the duplicates is kept.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    Sum,
+    Max,
+}
+
+impl DuplicatePolicy {
+    /// Merges the frequency of a duplicate row.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The frequency merged so far.
+    /// * `duplicate` - The frequency of the duplicate row.
+    ///
+    /// # Returns
+    ///
+    /// The merged frequency.
+    pub fn merge(self, current: i32, duplicate: i32) -> i32 {
+        match self {
+            DuplicatePolicy::Sum => current.saturating_add(duplicate),
+            DuplicatePolicy::Max => current.max(duplicate),
+        }
+    }
+}
+
+/// The default policy of the duplicate rows.
+pub static DEFAULT_DUPLICATE_POLICY: DuplicatePolicy = DuplicatePolicy::Max;
+
+/// The policy of the duplicate rows, read once from `DUPLICATE_ROWS`.
+static DUPLICATE_POLICY: OnceLock<DuplicatePolicy> = OnceLock::new();
+
+/// Parses the policy of the duplicate rows.
+///
+/// # Arguments
+///
+/// * `policy` - The policy, `sum` or `max`.
+///
+/// # Returns
+///
+/// A `Result` containing the `DuplicatePolicy` if the policy is valid, otherwise a `String` with the error message.
+pub fn parse_duplicate_policy(policy: &str) -> Result<DuplicatePolicy, String> {
+    match policy {
+        "sum" => Ok(DuplicatePolicy::Sum),
+        "max" => Ok(DuplicatePolicy::Max),
+        _ => Err(format!("Invalid duplicate policy: {}", policy)),
+    }
+}
+
+/// Gets the policy of the duplicate rows.
+///
+/// # Returns
+///
+/// The value of `DUPLICATE_ROWS`, or `DEFAULT_DUPLICATE_POLICY` if it is not set or invalid.
+pub fn duplicate_policy() -> DuplicatePolicy {
+    *DUPLICATE_POLICY.get_or_init(|| {
+        std::env::var("DUPLICATE_ROWS")
+            .ok()
+            .and_then(|policy| parse_duplicate_policy(&policy).ok())
+            .unwrap_or(DEFAULT_DUPLICATE_POLICY)
+    })
+}
+
 /// Represents a word and its frequency.
 ///
 /// # Fields
@@ -23,6 +121,7 @@ pub static DEFAULT_AMOUNT_OF_WORD_FREQ_PAIRS: i32 = 50;
 /// * `from` - Creates a `WordFreqPair` from the given session, index, and n-gram.
 /// * `find` - Finds the word in the given vector of `WordFreqPair`.
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WordFreqPair {
     pub word: String,
     pub frequency: i32,
@@ -45,11 +144,18 @@ impl WordFreqPair {
 
     /// Creates a `WordFreqPair` from the given session, index, and n-gram.
     ///
+    /// The rows are clustered by the word, not by the frequency, so the most frequent words can be
+    /// anywhere in the partition and the global top pairs need every row. To keep very common
+    /// contexts from materializing millions of rows, the rows are fetched in pages and reading stops
+    /// after `MAX_ROWS_SCANNED` rows, in which case the pairs are the top of the rows read.
+    /// Duplicate rows of the same word are merged by the `DUPLICATE_ROWS` policy.
+    ///
     /// # Arguments
     ///
-    /// * `session` - The ScyllaDB session.
+    /// * `store` - The n-gram store.
     /// * `index` - The index of the word.
     /// * `input` - Generic input that implements `Queryable`.
+    /// * `normalizer` - Normalizes the words before querying.
     ///
     /// # Returns
     ///
@@ -58,54 +164,70 @@ impl WordFreqPair {
     /// # Errors
     ///
     /// If the query can not be executed, a `String` with the error message will be returned.
-    /// If the word is not found, a `String` with the error message will be returned.
     /// If the index is invalid, a `String` with the error message will be returned.
     pub async fn from<T>(
-        session: Arc<Session>,
+        store: &dyn NgramStore,
         index: &i32,
         input: &T,
+        normalizer: &dyn Normalizer,
     ) -> Result<Vec<WordFreqPair>, String>
     where
         T: Queryable,
     {
-        let query = match input.get_query(Some(*index)) {
-            Ok(query) => query,
-            Err(err) => return Err(err),
-        };
-        let consistency = Consistency::One;
-
-        let s = Arc::clone(&session);
+        let query = input.get_query(Some(*index))?;
 
-        let query = match QueryFactory::build(s, query, consistency).await {
-            Ok(query) => query,
-            Err(err) => return Err(err.to_string()),
-        };
-
-        let input = match input.get_input(*index) {
-            Ok(input) => input.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            Err(err) => return Err(err),
-        };
+        let static_params = normalize_all(
+            normalizer,
+            &input
+                .get_input(*index)?
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<&str>>(),
+        );
 
-        let s = Arc::clone(&session);
+        let rows = store
+            .varying(&VaryingQuery {
+                query: query.to_string(),
+                static_params,
+                index: (*index - 1) as usize,
+                candidates: vec![],
+                max_rows: Some(max_rows_scanned()),
+                skipped: None,
+            })
+            .await?;
 
-        let mut row_stream = match query.execute_one(s, input).await {
-            Ok(rows) => rows.into_typed::<(String, i32)>(),
-            Err(err) => match err {
-                QueryError::ScyllaError => return Err("Can not execute query".to_string()),
-                QueryError::NotFound => return Err("Word not found".to_string()),
-            },
-        };
+        Ok(WordFreqPair::collect(rows, duplicate_policy()))
+    }
 
+    /// Collects the rows into pairs sorted by the frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The rows.
+    /// * `policy` - How the duplicate rows of the same word are merged.
+    ///
+    /// # Returns
+    ///
+    /// The `Vec` of `WordFreqPair`, one per word.
+    fn collect(rows: Vec<(String, i32)>, policy: DuplicatePolicy) -> Vec<WordFreqPair> {
         let mut result: Vec<WordFreqPair> = vec![];
+        let mut indexes: HashMap<String, usize> = HashMap::new();
 
-        while let Some(rows) = row_stream.next().await {
-            let (word, frequency) = rows.unwrap();
-            result.push(WordFreqPair::new(word, frequency));
+        for (word, frequency) in rows {
+            match indexes.get(&word) {
+                Some(&index) => {
+                    result[index].frequency = policy.merge(result[index].frequency, frequency)
+                }
+                None => {
+                    indexes.insert(word.clone(), result.len());
+                    result.push(WordFreqPair::new(word, frequency));
+                }
+            }
         }
 
-        result.sort_by(|a, b| b.frequency.cmp(&a.frequency));
+        result.sort_by_key(|pair| std::cmp::Reverse(pair.frequency));
 
-        Ok(result)
+        result
     }
 
     /// Finds the word in the given vector of `WordFreqPair`.
@@ -118,17 +240,18 @@ impl WordFreqPair {
     /// # Returns
     ///
     /// An `Option` containing the `WordFreqPair` if the word is found, otherwise `None`.
-    pub fn find<'a>(pairs: &'a Vec<WordFreqPair>, word: &str) -> Option<&'a WordFreqPair> {
+    pub fn find<'a>(pairs: &'a [WordFreqPair], word: &str) -> Option<&'a WordFreqPair> {
         pairs.iter().find(|pair| pair.word == word)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::super::db::init;
     use super::*;
-    use crate::n_grams::three_grams::model::ThreeGramInput;
-    use std::collections::HashMap;
+    use crate::{
+        db::InMemoryStore,
+        n_grams::{normalizer::IdentityNormalizer, three_grams::model::ThreeGramInput},
+    };
 
     #[test]
     fn test_new() {
@@ -140,7 +263,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_from() {
-        let session = init().await.unwrap();
+        let store = InMemoryStore::new(HashMap::from([
+            ("ja sam gledao".to_string(), 120),
+            ("on sam gledao".to_string(), 3),
+            ("ti sam gledao".to_string(), 7),
+            ("ja je gledao".to_string(), 40),
+            ("ja sam vidio".to_string(), 90),
+            ("sam gledao".to_string(), 500),
+        ]));
 
         let mut query_map = HashMap::new();
 
@@ -152,12 +282,50 @@ mod tests {
 
         let input = ThreeGramInput::from(&query_map).unwrap();
 
-        let result = WordFreqPair::from(Arc::clone(&session), &1, &input).await;
+        let result = WordFreqPair::from(&store, &1, &input, &IdentityNormalizer)
+            .await
+            .unwrap();
+
+        let words: Vec<(&str, i32)> = result
+            .iter()
+            .map(|pair| (pair.word.as_str(), pair.frequency))
+            .collect();
+        assert_eq!(words, vec![("ja", 120), ("ti", 7), ("on", 3)]);
 
-        assert!(result.is_ok());
-        let result = result.unwrap();
+        let result = WordFreqPair::from(&store, &3, &input, &IdentityNormalizer)
+            .await
+            .unwrap();
 
-        assert!(result.len() >= 50);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].word, "gledao");
+    }
+
+    #[test]
+    fn test_collect_merges_duplicate_rows() {
+        let rows = || {
+            vec![
+                ("a".to_string(), 4),
+                ("b".to_string(), 3),
+                ("a".to_string(), 2),
+            ]
+        };
+
+        let result = WordFreqPair::collect(rows(), DuplicatePolicy::Max);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(WordFreqPair::find(&result, "a").unwrap().frequency, 4);
+
+        let result = WordFreqPair::collect(rows(), DuplicatePolicy::Sum);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(WordFreqPair::find(&result, "a").unwrap().frequency, 6);
+    }
+
+    #[test]
+    fn test_parse_duplicate_policy() {
+        assert_eq!(parse_duplicate_policy("sum"), Ok(DuplicatePolicy::Sum));
+        assert_eq!(parse_duplicate_policy("max"), Ok(DuplicatePolicy::Max));
+        assert!(parse_duplicate_policy("first").is_err());
     }
 
     #[test]